@@ -3,6 +3,7 @@ use chess_engine::prelude::evaluators::{AdvancedEvaluator, BasicEvaluator};
 use chess_engine::prelude::Engine;
 use chess_engine::prelude::TreeSearch;
 use chess_engine::prelude::{print_board, string_from_move};
+use chess_engine::static_evaluation::evaluator_trait::Evaluator;
 use chess_engine::tree_search_v2::tree::TreeSearchBuilder;
 
 use std::collections::HashMap;
@@ -38,7 +39,7 @@ macro_rules! input {
 }
 
 #[allow(dead_code)]
-fn play_against_robot(mut engine: Engine, mut tree: TreeSearch) {
+fn play_against_robot<E: Evaluator>(mut engine: Engine, mut tree: TreeSearch<E>) {
     loop {
         let played_str = {
             if engine.white_to_play() {
@@ -121,7 +122,7 @@ fn main() {
     let tree = TreeSearchBuilder::default()
         .pool_capacity(1e7 as usize)
         .tt_capacity(1e7 as usize)
-        .evaluator(Box::new(evaluator))
+        .evaluator(evaluator)
         .max_depth(10)
         .max_q_depth(0)
         .window_margin(0.)
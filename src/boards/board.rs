@@ -1,5 +1,12 @@
+use std::fmt;
+
+use super::zobrist_hash::HASHER;
 use super::{CastlingRights, ColorBoard};
-use crate::pieces::{static_positions as init, Color, Piece};
+use crate::game_engine::move_info::MoveInfo;
+use crate::game_engine::move_results::CorrectMoveResults;
+use crate::game_engine::player_move::{CastlingMove, PlayerMove};
+use crate::game_engine::utility::{get_final_castling_positions, get_initial_castling_positions};
+use crate::pieces::{moves, static_positions as init, Color, Piece};
 
 /// Represents a chess board with separate bitboards for white and black pieces.
 #[derive(Debug, Clone)]
@@ -38,6 +45,82 @@ impl Board {
         }
     }
 
+    /// Creates a `Board` without any piece.
+    ///
+    /// # Returns
+    /// A new `Board` instance, without castling rights.
+    pub fn empty() -> Self {
+        Board {
+            white: ColorBoard::empty(),
+            black: ColorBoard::empty(),
+        }
+    }
+
+    /// Creates a `Board` with the given pieces, without castling rights nor
+    /// en passant square.
+    ///
+    /// # Arguments
+    /// * `pieces` - The color, the type and the square (a single bit set) of each piece.
+    pub fn from_piece_list(pieces: &[(Color, Piece, u64)]) -> Self {
+        let mut board = Board::empty();
+        for &(color, piece, square) in pieces {
+            let bitboard = board.get_bitboard_by_type(piece, color);
+            board.set_bitboard_by_type(piece, color, bitboard | square);
+        }
+        board
+    }
+
+    /// Creates a `Board` from a grid of piece letters, without castling
+    /// rights nor en passant square.
+    ///
+    /// The grid is read as printed by `print_board`: the 8th rank first, from
+    /// the a-file to the h-file, uppercase letters for white, lowercase for
+    /// black and `.` for an empty square. Other characters are ignored, and
+    /// so are the lines without exactly 8 squares (the file letters of
+    /// `print_board`), so its output can be read back.
+    ///
+    /// # Arguments
+    /// * `ascii` - The grid, one rank per line.
+    ///
+    /// # Returns
+    /// The board of the grid, `None` if it doesn't have exactly 8 ranks.
+    pub fn from_ascii(ascii: &str) -> Option<Self> {
+        let ranks: Vec<Vec<char>> = ascii
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .filter(|&c| c == '.' || Piece::from_char(c).is_some())
+                    .collect::<Vec<char>>()
+            })
+            .filter(|squares| squares.len() == 8)
+            .collect();
+        if ranks.len() != 8 {
+            return None;
+        }
+
+        let mut pieces = Vec::new();
+        for (rank_index, squares) in ranks.iter().enumerate() {
+            let rank = 7 - rank_index;
+            for (file, &c) in squares.iter().enumerate() {
+                if let Some((color, piece)) = Piece::from_char(c) {
+                    pieces.push((color, piece, 1u64 << (rank * 8 + file)));
+                }
+            }
+        }
+        Some(Board::from_piece_list(&pieces))
+    }
+
+    /// Returns the board mirrored vertically with the colors swapped.
+    ///
+    /// The white pieces become black pieces on the mirrored squares and the
+    /// other way around, castling rights and en passant follow their pieces.
+    pub fn flipped(&self) -> Self {
+        Board {
+            white: self.black.flipped(),
+            black: self.white.flipped(),
+        }
+    }
+
     /// Returns a combined bitboard of all pieces on the board.
     ///
     /// # Returns
@@ -74,26 +157,75 @@ impl Board {
         }
     }
 
-    /// Returns a vector of tuples containing the bitboard, piece type, and color for each piece.
+    /// Returns the files occupied by at least one pawn of a color.
+    ///
+    /// # Arguments
+    /// * `color` - The color of the pawns.
     ///
     /// # Returns
-    /// A `Vec` of tuples where each tuple contains a `u64` bitboard, a reference to `Pieces`, and a reference to `Color`.
-    pub fn individual_pieces(&self) -> Vec<(u64, Piece, Color)> {
+    /// A `u8` where bit `n` is set if file `n` (0 = a-file) holds a pawn.
+    pub fn pawn_file_mask(&self, color: Color) -> u8 {
+        match color {
+            Color::White => self.white.pawn_file_mask(),
+            Color::Black => self.black.pawn_file_mask(),
+        }
+    }
+
+    /// Returns the files without any pawn.
+    ///
+    /// # Returns
+    /// A `u8` file mask (bit 0 = a-file).
+    pub fn open_files(&self) -> u8 {
+        !(self.white.pawn_file_mask() | self.black.pawn_file_mask())
+    }
+
+    /// Returns the files without pawns of `color` but with at least one opponent pawn.
+    ///
+    /// # Arguments
+    /// * `color` - The color for which the files are half-open.
+    ///
+    /// # Returns
+    /// A `u8` file mask (bit 0 = a-file).
+    pub fn half_open_files(&self, color: Color) -> u8 {
+        let (own, opponent) = match color {
+            Color::White => (self.white.pawn_file_mask(), self.black.pawn_file_mask()),
+            Color::Black => (self.black.pawn_file_mask(), self.white.pawn_file_mask()),
+        };
+        !own & opponent
+    }
+
+    /// Returns an iterator over the bitboard, piece type, and color of each piece, black pieces first.
+    ///
+    /// Unlike `individual_pieces`, nothing is allocated.
+    ///
+    /// # Returns
+    /// An iterator of tuples where each tuple contains a `u64` bitboard, a `Piece`, and a `Color`.
+    pub fn individual_pieces_iter(&self) -> impl Iterator<Item = (u64, Piece, Color)> {
         let black_pieces = self
             .black
-            .individual_pieces()
-            .iter()
-            .map(|x| (x.0, x.1, Color::Black))
-            .collect::<Vec<_>>();
+            .individual_pieces_iter()
+            .map(|(position, piece)| (position, piece, Color::Black));
         let white_pieces = self
             .white
-            .individual_pieces()
-            .iter()
-            .map(|x| (x.0, x.1, Color::White))
-            .collect::<Vec<_>>();
-        let mut all_pieces = black_pieces;
-        all_pieces.extend(white_pieces);
-        all_pieces
+            .individual_pieces_iter()
+            .map(|(position, piece)| (position, piece, Color::White));
+        black_pieces.chain(white_pieces)
+    }
+
+    /// Returns a vector of tuples containing the bitboard, piece type, and color for each piece.
+    ///
+    /// # Returns
+    /// A `Vec` of tuples where each tuple contains a `u64` bitboard, a reference to `Pieces`, and a reference to `Color`.
+    pub fn individual_pieces(&self) -> Vec<(u64, Piece, Color)> {
+        self.individual_pieces_iter().collect()
+    }
+
+    /// Returns a Zobrist hash of the pawns only, to key pawn structure caches.
+    ///
+    /// # Returns
+    /// A `u64` that only changes when a pawn moves, is captured or promotes.
+    pub fn pawn_hash(&self) -> u64 {
+        HASHER.compute_pawn_hash(self)
     }
 
     /// Returns the color and the piece at the specified square, if any.
@@ -112,4 +244,462 @@ impl Board {
             None
         }
     }
+
+    /// Returns the color and the piece at the given coordinates, if any.
+    ///
+    /// # Arguments
+    /// * `rank` - The rank, from 0 (rank 1) to 7 (rank 8)
+    /// * `file` - The file, from 0 (a-file) to 7 (h-file)
+    ///
+    /// # Returns
+    /// `None` if the square is empty or the coordinates are out of the board
+    pub fn piece_at_coords(&self, rank: usize, file: usize) -> Option<(Color, Piece)> {
+        if rank >= 8 || file >= 8 {
+            return None;
+        }
+        self.get_piece_at(1 << (rank * 8 + file))
+    }
+
+    /// Returns the color and the piece at the given square index, if any.
+    ///
+    /// # Arguments
+    /// * `index` - The square index, from 0 (a1) to 63 (h8), rank by rank
+    ///
+    /// # Returns
+    /// `None` if the square is empty or the index is out of the board
+    pub fn piece_at_square_index(&self, index: u8) -> Option<(Color, Piece)> {
+        if index >= 64 {
+            return None;
+        }
+        self.get_piece_at(1 << index)
+    }
+
+    /// Returns the pieces of a color attacking a square.
+    ///
+    /// # Arguments
+    /// * `square` - A u64 with a single bit set, the square attacked.
+    /// * `by` - The color of the attackers.
+    ///
+    /// # Returns
+    /// A `u64` bitboard of the attackers. Pawns attack diagonally only and
+    /// pinned pieces still count as attackers.
+    pub fn attackers_to(&self, square: u64, by: Color) -> u64 {
+        self.attackers_to_with_occupancy(square, by, self.bitboard())
+    }
+
+    /// Same as `attackers_to`, with the sliders blocked by `occupancy`
+    /// instead of the pieces on the board.
+    ///
+    /// Removing a piece from `occupancy` reveals the sliders behind it, as
+    /// needed by an exchange on the square. Pieces missing from `occupancy`
+    /// don't attack.
+    pub fn attackers_to_with_occupancy(&self, square: u64, by: Color, occupancy: u64) -> u64 {
+        let attackers = match by {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        };
+        let opponent = match by {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+
+        // A piece attacks the square if the same piece on the square attacks it back
+        let rooks = attackers.rook | attackers.queen;
+        let bishops = attackers.bishop | attackers.queen;
+        let attacks = moves::pawn_captures(square, attackers.pawn, opponent)
+            | (moves::knight_moves(square, 0) & attackers.knight)
+            | (moves::king_moves(square, 0) & attackers.king)
+            | (moves::rooks_moves(square, 0, occupancy) & rooks)
+            | (moves::bishops_moves(square, 0, occupancy) & bishops);
+        attacks & occupancy
+    }
+
+    /// Returns `true` if at least one piece of `by` attacks the square.
+    pub fn is_attacked(&self, square: u64, by: Color) -> bool {
+        self.attackers_to(square, by) != 0
+    }
+
+    /// Applies a move of `color` and returns the resulting board, leaving `self` untouched.
+    ///
+    /// Every kind of move is fully applied: captures (en passant included), promotion,
+    /// the rook of a castling, en passant squares and castling rights. The move is
+    /// **not** checked for legality, that's the job of the `Engine`.
+    ///
+    /// # Arguments
+    /// * `player_move` - The move to apply.
+    /// * `color` - The color of the player making the move.
+    ///
+    /// # Returns
+    /// The new board and what changed on it, `None` if `color` has no piece on the source square.
+    pub fn apply_move(&self, player_move: &PlayerMove, color: Color) -> Option<(Board, MoveInfo)> {
+        let (from, to, promotion, rook_move) = match *player_move {
+            PlayerMove::Normal(normal_move) => {
+                let (from, to) = normal_move.squares();
+                (from, to, None, None)
+            }
+            PlayerMove::Promotion(promotion_move) => {
+                let (from, to) = promotion_move.squares();
+                (from, to, Some(promotion_move.promotion_piece()), None)
+            }
+            PlayerMove::Castling(castling) => {
+                let (initial_king, initial_short_rook, initial_long_rook) =
+                    get_initial_castling_positions(color);
+                let (final_king, final_rook) = get_final_castling_positions(castling, color);
+                let initial_rook = match castling {
+                    CastlingMove::Short => initial_short_rook,
+                    CastlingMove::Long => initial_long_rook,
+                };
+                (
+                    initial_king,
+                    final_king,
+                    None,
+                    Some((initial_rook, final_rook)),
+                )
+            }
+        };
+
+        let mut board = self.clone();
+        let (player_board, opponent_board) = match color {
+            Color::White => (&mut board.white, &mut board.black),
+            Color::Black => (&mut board.black, &mut board.white),
+        };
+        let piece = player_board.piece_at(from)?;
+
+        // A pawn landing on the en passant square takes the pawn behind it
+        let captured = match opponent_board.piece_at(to) {
+            Some(captured_piece) => Some((captured_piece, to)),
+            None if piece == Piece::Pawn && opponent_board.en_passant & to != 0 => {
+                let pawn_position = match color {
+                    Color::White => to >> 8,
+                    Color::Black => to << 8,
+                };
+                Some((Piece::Pawn, pawn_position))
+            }
+            None => None,
+        };
+        if let Some((captured_piece, captured_square)) = captured {
+            let bitboard = opponent_board.get_bitboard_by_type(captured_piece);
+            opponent_board.set_bitboard_by_type(captured_piece, bitboard & !captured_square);
+        }
+
+        // Move the piece, replacing the pawn by its promotion
+        let bitboard = player_board.get_bitboard_by_type(piece);
+        player_board.set_bitboard_by_type(piece, bitboard & !from);
+        let landing_piece = promotion.unwrap_or(piece);
+        let bitboard = player_board.get_bitboard_by_type(landing_piece);
+        player_board.set_bitboard_by_type(landing_piece, bitboard | to);
+
+        if let Some((rook_from, rook_to)) = rook_move {
+            player_board.rook = (player_board.rook & !rook_from) | rook_to;
+        }
+
+        // The opponent's en passant square expires, a double push creates a new one
+        opponent_board.en_passant = 0;
+        let is_double_push =
+            piece == Piece::Pawn && from.trailing_zeros().abs_diff(to.trailing_zeros()) == 16;
+        player_board.en_passant = if is_double_push {
+            match color {
+                Color::White => to >> 8,
+                Color::Black => to << 8,
+            }
+        } else {
+            0
+        };
+
+        board.update_castling_rights();
+
+        let move_info = MoveInfo {
+            color,
+            piece,
+            from,
+            to,
+            captured,
+            rook_move,
+            promotion,
+            result: CorrectMoveResults::Ok,
+        };
+        Some((board, move_info))
+    }
+
+    /// Updates castling rights of both colors based on the pieces still on their initial squares.
+    ///
+    /// Moving or losing the king removes both rights of its color, moving or losing a rook
+    /// removes the right of its side.
+    pub fn update_castling_rights(&mut self) {
+        let (initial_white_king, initial_white_short_rook, initial_white_long_rook) =
+            get_initial_castling_positions(Color::White);
+        let (initial_black_king, initial_black_short_rook, initial_black_long_rook) =
+            get_initial_castling_positions(Color::Black);
+
+        self.white.castling_rights.update_castling_rights(
+            self.white.king,
+            self.white.rook,
+            initial_white_king,
+            initial_white_short_rook,
+            initial_white_long_rook,
+        );
+
+        self.black.castling_rights.update_castling_rights(
+            self.black.king,
+            self.black.rook,
+            initial_black_king,
+            initial_black_short_rook,
+            initial_black_long_rook,
+        );
+    }
+}
+
+/// Renders the board as an 8x8 grid seen from white, rank 8 on top, with
+/// FEN letters for the pieces and `.` for empty squares.
+///
+/// The alternate form (`{:#}`) colors the squares with ANSI backgrounds
+/// instead of the dots, for terminals.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let files_header: String = (b'a'..=b'h')
+            .map(|file| format!(" {} ", file as char))
+            .collect();
+        writeln!(f, "    {}", files_header)?;
+
+        for rank in (0..8).rev() {
+            write!(f, "{} | ", rank + 1)?;
+            for file in 0..8 {
+                let piece_char = self
+                    .get_piece_at(1 << (rank * 8 + file))
+                    .map(|(color, piece)| piece.to_char(color));
+
+                if f.alternate() {
+                    let bg_color = if (rank + file) % 2 == 0 {
+                        init::WHITE_BG
+                    } else {
+                        init::BLACK_BG
+                    };
+                    write!(
+                        f,
+                        "{} {} {}",
+                        bg_color,
+                        piece_char.unwrap_or(' '),
+                        init::RESET
+                    )?;
+                } else {
+                    write!(f, " {} ", piece_char.unwrap_or('.'))?;
+                }
+            }
+            writeln!(f, " | {}", rank + 1)?;
+        }
+
+        writeln!(f, "    {}", files_header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::engine::Engine;
+
+    fn board(fen: &str) -> Board {
+        Engine::from_fen(fen).unwrap().get_board().clone()
+    }
+
+    #[test]
+    fn finds_open_and_half_open_files() {
+        // White pawns on a, b, e, g, h and black pawns on a, b, f, g, h
+        let board = board("4k3/pp3ppp/8/8/8/8/PP2P1PP/4K3 w - - 0 1");
+
+        assert_eq!(board.pawn_file_mask(Color::White), 0b1101_0011);
+        assert_eq!(board.pawn_file_mask(Color::Black), 0b1110_0011);
+        assert_eq!(board.open_files(), 0b0000_1100);
+        assert_eq!(board.half_open_files(Color::White), 0b0010_0000);
+        assert_eq!(board.half_open_files(Color::Black), 0b0001_0000);
+    }
+
+    #[test]
+    fn individual_pieces_iter_yields_every_occupied_square() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/8/8/8/8/8/8/K6k w - - 0 1",
+        ] {
+            let board = board(fen);
+            let mut pieces: Vec<_> = board.individual_pieces_iter().collect();
+            pieces.sort_by_key(|&(square, _, _)| square);
+
+            let expected: Vec<_> = (0..64)
+                .filter_map(|index| {
+                    let (color, piece) = board.piece_at_square_index(index)?;
+                    Some((1u64 << index, piece, color))
+                })
+                .collect();
+            assert_eq!(pieces, expected, "{fen}");
+            assert_eq!(board.individual_pieces().len(), expected.len(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn pawn_hash_only_changes_with_the_pawns() {
+        let start = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let knight_move = board("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1");
+        let pawn_move = board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+
+        assert_eq!(start.pawn_hash(), knight_move.pawn_hash());
+        assert_ne!(start.pawn_hash(), pawn_move.pawn_hash());
+
+        // The same push by black gives another structure
+        let mirrored = board("rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_ne!(pawn_move.pawn_hash(), mirrored.pawn_hash());
+    }
+
+    #[test]
+    fn displays_the_start_position_rank_by_rank() {
+        let rendered = format!(
+            "{}",
+            board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+        );
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        // Header, rank 8 down to rank 1, footer
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], lines[9]);
+        assert_eq!(lines[1], "8 |  r  n  b  q  k  b  n  r  | 8");
+        assert_eq!(lines[4], "5 |  .  .  .  .  .  .  .  .  | 5");
+        assert_eq!(lines[8], "1 |  R  N  B  Q  K  B  N  R  | 1");
+
+        // The queens on the d file, the kings on the e file
+        let column = |file: char| lines[0].find(file).unwrap();
+        assert_eq!(lines[1].find('q'), Some(column('d')));
+        assert_eq!(lines[8].find('Q'), Some(column('d')));
+        assert_eq!(lines[1].find('k'), Some(column('e')));
+        assert_eq!(lines[8].find('K'), Some(column('e')));
+    }
+
+    #[test]
+    fn finds_a_single_attacker_of_each_type() {
+        let e4 = 1u64 << 28;
+        let square = |name: &str| {
+            let bytes = name.as_bytes();
+            1u64 << ((bytes[1] - b'1') * 8 + bytes[0] - b'a')
+        };
+
+        for (color, piece, at, attacks) in [
+            (Color::White, Piece::Pawn, "d3", true),
+            (Color::White, Piece::Pawn, "e3", false),
+            (Color::White, Piece::Pawn, "d5", false),
+            (Color::Black, Piece::Pawn, "f5", true),
+            (Color::Black, Piece::Pawn, "f3", false),
+            (Color::White, Piece::Knight, "f2", true),
+            (Color::White, Piece::Knight, "f3", false),
+            (Color::White, Piece::Bishop, "b1", true),
+            (Color::White, Piece::Bishop, "b2", false),
+            (Color::White, Piece::Rook, "e8", true),
+            (Color::White, Piece::Rook, "d8", false),
+            (Color::White, Piece::Queen, "a8", true),
+            (Color::White, Piece::Queen, "h4", true),
+            (Color::White, Piece::Queen, "g3", false),
+            (Color::White, Piece::King, "f5", true),
+            (Color::White, Piece::King, "g5", false),
+        ] {
+            let board = Board::from_piece_list(&[(color, piece, square(at))]);
+            let expected = if attacks { square(at) } else { 0 };
+            let other = match color {
+                Color::White => Color::Black,
+                Color::Black => Color::White,
+            };
+            assert_eq!(board.attackers_to(e4, color), expected, "{piece:?} {at}");
+            assert_eq!(board.is_attacked(e4, color), attacks, "{piece:?} {at}");
+            assert_eq!(board.attackers_to(e4, other), 0, "{piece:?} {at}");
+        }
+
+        // A piece in between blocks the sliders, taking it off the occupancy
+        // reveals them again
+        let blockers = square("c2") | square("e6");
+        let board = Board::from_piece_list(&[
+            (Color::White, Piece::Bishop, square("b1")),
+            (Color::White, Piece::Rook, square("e8")),
+            (Color::Black, Piece::Knight, square("c2")),
+            (Color::Black, Piece::Knight, square("e6")),
+        ]);
+        assert!(!board.is_attacked(e4, Color::White));
+        assert_eq!(
+            board.attackers_to_with_occupancy(e4, Color::White, board.bitboard() & !blockers),
+            square("b1") | square("e8")
+        );
+    }
+
+    #[test]
+    fn builds_boards_from_pieces_and_from_ascii() {
+        let listed = Board::from_piece_list(&[
+            (Color::White, Piece::King, 1 << 4),
+            (Color::White, Piece::Queen, 1 << 3),
+            (Color::Black, Piece::King, 1 << 60),
+            (Color::Black, Piece::Pawn, 1 << 51),
+        ]);
+        assert_eq!(
+            listed.get_piece_at(1 << 3),
+            Some((Color::White, Piece::Queen))
+        );
+        assert_eq!(
+            listed.get_piece_at(1 << 51),
+            Some((Color::Black, Piece::Pawn))
+        );
+        assert_eq!(listed.individual_pieces().len(), 4);
+
+        let ascii = Board::from_ascii(
+            "....k...
+             ...p....
+             ........
+             ........
+             ........
+             ........
+             ........
+             ...QK...",
+        )
+        .unwrap();
+        assert_eq!(ascii.individual_pieces(), listed.individual_pieces());
+
+        // The printed board reads back to the same pieces
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ] {
+            let board = board(fen);
+            let read_back = Board::from_ascii(&format!("{board}")).unwrap();
+            assert_eq!(
+                read_back.individual_pieces(),
+                board.individual_pieces(),
+                "{fen}"
+            );
+        }
+
+        assert!(Board::from_ascii("........\n........").is_none());
+    }
+
+    #[test]
+    fn reads_the_start_position_by_coordinates_and_index() {
+        let board = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+
+        for (rank, file, expected) in [
+            (0, 0, Some((Color::White, Piece::Rook))),
+            (0, 3, Some((Color::White, Piece::Queen))),
+            (0, 4, Some((Color::White, Piece::King))),
+            (1, 6, Some((Color::White, Piece::Pawn))),
+            (7, 1, Some((Color::Black, Piece::Knight))),
+            (7, 4, Some((Color::Black, Piece::King))),
+            (6, 7, Some((Color::Black, Piece::Pawn))),
+            (3, 4, None),
+            (8, 0, None),
+            (0, 8, None),
+        ] {
+            assert_eq!(board.piece_at_coords(rank, file), expected, "{rank} {file}");
+            if rank < 8 && file < 8 {
+                let index = (rank * 8 + file) as u8;
+                assert_eq!(board.piece_at_square_index(index), expected, "{index}");
+            }
+        }
+
+        assert_eq!(
+            board.piece_at_square_index(63),
+            Some((Color::Black, Piece::Rook))
+        );
+        assert_eq!(board.piece_at_square_index(64), None);
+    }
 }
@@ -15,6 +15,36 @@ pub struct ColorBoard {
 }
 
 impl ColorBoard {
+    /// Creates a `ColorBoard` without any piece nor castling right.
+    pub fn empty() -> Self {
+        ColorBoard {
+            pawn: 0,
+            knight: 0,
+            bishop: 0,
+            rook: 0,
+            queen: 0,
+            king: 0,
+            castling_rights: CastlingRights::new_with_rules(false, false),
+            en_passant: 0,
+        }
+    }
+
+    /// Returns the same pieces mirrored vertically (rank 1 becomes rank 8).
+    ///
+    /// Castling rights and the en passant square are kept, files don't change.
+    pub fn flipped(&self) -> Self {
+        ColorBoard {
+            pawn: self.pawn.swap_bytes(),
+            knight: self.knight.swap_bytes(),
+            bishop: self.bishop.swap_bytes(),
+            rook: self.rook.swap_bytes(),
+            queen: self.queen.swap_bytes(),
+            king: self.king.swap_bytes(),
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant.swap_bytes(),
+        }
+    }
+
     /// Returns a combined bitboard of all pieces for the color.
     ///
     /// # Returns
@@ -57,11 +87,26 @@ impl ColorBoard {
         }
     }
 
-    /// Returns a vector of tuples containing the bitboard position and piece type for each piece.
+    /// Returns the files occupied by at least one pawn.
     ///
     /// # Returns
-    /// A `Vec` of tuples where each tuple contains a `u64` bitboard position and a reference to `Pieces`.
-    pub fn individual_pieces(&self) -> Vec<(u64, Piece)> {
+    /// A `u8` where bit `n` is set if file `n` (0 = a-file) holds a pawn.
+    pub fn pawn_file_mask(&self) -> u8 {
+        // Fold all ranks onto the first one
+        let mut files = self.pawn;
+        files |= files >> 32;
+        files |= files >> 16;
+        files |= files >> 8;
+        (files & 0xFF) as u8
+    }
+
+    /// Returns an iterator over the bitboard position and piece type of each piece.
+    ///
+    /// Unlike `individual_pieces`, nothing is allocated.
+    ///
+    /// # Returns
+    /// An iterator of tuples where each tuple contains a `u64` bitboard position and a `Piece`.
+    pub fn individual_pieces_iter(&self) -> impl Iterator<Item = (u64, Piece)> {
         let piece_bitboards: [(u64, Piece); 6] = [
             (self.pawn, Piece::Pawn),
             (self.knight, Piece::Knight),
@@ -71,16 +116,26 @@ impl ColorBoard {
             (self.king, Piece::King),
         ];
 
-        let mut result = Vec::new();
-        for (bitboard, piece) in piece_bitboards.iter() {
-            let mut bitboard_copy = *bitboard;
-            while bitboard_copy != 0 {
-                let mask = bitboard_copy & (!bitboard_copy + 1);
-                result.push((mask, *piece));
-                bitboard_copy &= bitboard_copy - 1;
-            }
-        }
-        result
+        piece_bitboards
+            .into_iter()
+            .flat_map(|(mut bitboard, piece)| {
+                std::iter::from_fn(move || {
+                    if bitboard == 0 {
+                        return None;
+                    }
+                    let mask = bitboard & bitboard.wrapping_neg();
+                    bitboard &= bitboard - 1;
+                    Some((mask, piece))
+                })
+            })
+    }
+
+    /// Returns a vector of tuples containing the bitboard position and piece type for each piece.
+    ///
+    /// # Returns
+    /// A `Vec` of tuples where each tuple contains a `u64` bitboard position and a reference to `Pieces`.
+    pub fn individual_pieces(&self) -> Vec<(u64, Piece)> {
+        self.individual_pieces_iter().collect()
     }
 
     /// Returns the piece at the specified square, if any.
@@ -1,12 +1,17 @@
 use super::{Board, ColorBoard};
+use crate::pieces::moves::pawn_captures;
 use crate::pieces::Color;
 use once_cell::sync::Lazy;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 const NUM_SQUARES: usize = 64;
 const NUM_PIECE_TYPES: usize = 6; // Pawn, Knight, Bishop, Rook, Queen, King
 const NUM_COLORS: usize = 2; // White, Black
 
+// Keys are the same on every run, so hash dependent results (TT hits, bench) are reproducible
+const ZOBRIST_SEED: u64 = 0x5EED_C0DE_CAFE_F00D;
+
 #[derive(Copy, Clone)]
 pub enum PieceType {
     Pawn = 0,
@@ -26,7 +31,7 @@ pub struct Zobrist {
 
 impl Zobrist {
     pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
         Self {
             table: [[[0; NUM_SQUARES]; NUM_COLORS]; NUM_PIECE_TYPES]
                 .map(|c| c.map(|s| s.map(|_| rng.gen::<u64>()))),
@@ -46,16 +51,10 @@ impl Zobrist {
         hash ^= self.hash_color_board(&board.black, Color::Black);
 
         // Add castling rights
-        let castling_index =
-            board.white.castling_rights.as_index() | (board.black.castling_rights.as_index() << 2);
-        hash ^= self.castling_rights[castling_index];
+        hash ^= self.castling_key(board);
 
-        // Add en passant
-        if white_to_play && board.white.en_passant != 0 {
-            hash ^= self.en_passant[board.white.en_passant.trailing_zeros() as usize];
-        } else if !white_to_play && board.black.en_passant != 0 {
-            hash ^= self.en_passant[board.black.en_passant.trailing_zeros() as usize];
-        }
+        // Add en passant, only when it can be captured
+        hash = self.clear_en_passant_hash(hash, self.hashed_en_passant(board, white_to_play));
 
         // Add side to move
         if !white_to_play {
@@ -65,6 +64,110 @@ impl Zobrist {
         hash
     }
 
+    /// Computes a hash of the pawns of both colors only, with the same keys as `compute_hash`
+    pub fn compute_pawn_hash(&self, board: &Board) -> u64 {
+        let mut hash: u64 = 0;
+        for (color_index, pawns) in [board.white.pawn, board.black.pawn].into_iter().enumerate() {
+            let mut bb = pawns;
+            while bb != 0 {
+                let square = bb.trailing_zeros() as usize;
+                hash ^= self.table[PieceType::Pawn as usize][color_index][square];
+                bb &= bb - 1; // Clear the least significant bit
+            }
+        }
+        hash
+    }
+
+    /// Returns the hash of the position after a move, from the hash before it
+    ///
+    /// Only the squares whose piece changed are hashed again, so every kind of
+    /// move (capture, castling, promotion, en passant) is handled the same way.
+    ///
+    /// # Arguments
+    /// * `hash` - Hash of the position before the move
+    /// * `previous_board` - Board before the move
+    /// * `board` - Board after the move, castling rights and en passant updated
+    /// * `color` - Color of the player who made the move
+    pub fn update_hash(
+        &self,
+        hash: u64,
+        previous_board: &Board,
+        board: &Board,
+        color: Color,
+    ) -> u64 {
+        let mut hash = hash;
+        let color_boards = [
+            (&previous_board.white, &board.white),
+            (&previous_board.black, &board.black),
+        ];
+        for (color_index, (previous, current)) in color_boards.into_iter().enumerate() {
+            let pieces = piece_bitboards(previous)
+                .into_iter()
+                .zip(piece_bitboards(current));
+            for ((piece_type, before), (_, after)) in pieces {
+                let mut changed = before ^ after;
+                while changed != 0 {
+                    let square = changed.trailing_zeros() as usize;
+                    hash ^= self.table[piece_type as usize][color_index][square];
+                    changed &= changed - 1;
+                }
+            }
+        }
+
+        hash ^= self.castling_key(previous_board) ^ self.castling_key(board);
+
+        let white_played = color == Color::White;
+        hash =
+            self.clear_en_passant_hash(hash, self.hashed_en_passant(previous_board, white_played));
+        hash = self.clear_en_passant_hash(hash, self.hashed_en_passant(board, !white_played));
+
+        self.toggle_side(hash)
+    }
+
+    /// Returns the hash of the same position with the other side to move
+    pub fn toggle_side(&self, hash: u64) -> u64 {
+        hash ^ self.side_to_move
+    }
+
+    /// Returns the en passant square that is part of the hash, `0` if there is none
+    ///
+    /// The square is kept by the side that just pushed the pawn, and only counts
+    /// when a pawn of the side to move can capture on it (pins are ignored). A
+    /// double push nobody can take gives the same position as a single one.
+    pub fn hashed_en_passant(&self, board: &Board, white_to_play: bool) -> u64 {
+        let (player_board, pusher_board, pusher_color) = if white_to_play {
+            (&board.white, &board.black, Color::Black)
+        } else {
+            (&board.black, &board.white, Color::White)
+        };
+
+        // The capturing pawns stand where a pawn of the pusher on the square would capture
+        if pawn_captures(pusher_board.en_passant, player_board.pawn, pusher_color) != 0 {
+            pusher_board.en_passant
+        } else {
+            0
+        }
+    }
+
+    /// Returns the hash without the key of an en passant square
+    ///
+    /// # Arguments
+    /// * `hash` - Hash including the en passant key
+    /// * `en_passant` - The en passant square (single bit set), `0` leaves the hash unchanged
+    pub fn clear_en_passant_hash(&self, hash: u64, en_passant: u64) -> u64 {
+        if en_passant == 0 {
+            hash
+        } else {
+            hash ^ self.en_passant[en_passant.trailing_zeros() as usize]
+        }
+    }
+
+    fn castling_key(&self, board: &Board) -> u64 {
+        let castling_index =
+            board.white.castling_rights.as_index() | (board.black.castling_rights.as_index() << 2);
+        self.castling_rights[castling_index]
+    }
+
     fn hash_color_board(&self, color_board: &ColorBoard, color: Color) -> u64 {
         let mut hash: u64 = 0;
         let color_index = {
@@ -76,14 +179,7 @@ impl Zobrist {
         };
 
         // Hash each piece type
-        for (piece_type, bitboard) in [
-            (PieceType::Pawn, color_board.pawn),
-            (PieceType::Knight, color_board.knight),
-            (PieceType::Bishop, color_board.bishop),
-            (PieceType::Rook, color_board.rook),
-            (PieceType::Queen, color_board.queen),
-            (PieceType::King, color_board.king),
-        ] {
+        for (piece_type, bitboard) in piece_bitboards(color_board) {
             let mut bb = bitboard;
             while bb != 0 {
                 let square = bb.trailing_zeros() as usize;
@@ -96,4 +192,16 @@ impl Zobrist {
     }
 }
 
+/// Bitboard of each piece type of a color
+fn piece_bitboards(color_board: &ColorBoard) -> [(PieceType, u64); NUM_PIECE_TYPES] {
+    [
+        (PieceType::Pawn, color_board.pawn),
+        (PieceType::Knight, color_board.knight),
+        (PieceType::Bishop, color_board.bishop),
+        (PieceType::Rook, color_board.rook),
+        (PieceType::Queen, color_board.queen),
+        (PieceType::King, color_board.king),
+    ]
+}
+
 pub static HASHER: Lazy<Zobrist> = Lazy::new(|| Zobrist::new());
@@ -1,11 +1,42 @@
 use rusqlite::{params, Connection, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 use super::init::{init_db, get_db_path};
 use super::models::MoveModel;
 
+use crate::game_engine::pgn::{parse_pgn, PgnResult};
 use crate::lichess_api::models::LichessMove;
+use crate::pieces::Color;
+
+/// Which moves of an imported game are recorded in the book, based on the
+/// result of the game for the side that played them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFilter {
+    /// Every move, whatever the result (even unknown)
+    AllMoves,
+    /// Moves of the winner and moves of both sides of a drawn game
+    NotLost,
+    /// Moves of the winner only
+    WonOnly,
+}
+
+impl ImportFilter {
+    /// Whether a move played by `color` in a game ending with `result` is recorded
+    pub fn accepts(&self, result: PgnResult, color: Color) -> bool {
+        let score = match (result.white_score(), color) {
+            (Some(white_score), Color::White) => white_score,
+            (Some(white_score), Color::Black) => 1. - white_score,
+            (None, _) => return *self == ImportFilter::AllMoves,
+        };
+
+        match self {
+            ImportFilter::AllMoves => true,
+            ImportFilter::NotLost => score >= 0.5,
+            ImportFilter::WonOnly => score == 1.,
+        }
+    }
+}
 
 /// Database interface for storing and retrieving chess positions and moves
 /// 
@@ -62,23 +93,7 @@ impl ChessTablesDb {
     /// * `Result<i64>` - Board ID in the database or SQLite error
     pub fn insert_board(&self, fen: &str) -> Result<i64, rusqlite::Error> {
         let conn = self.conn.lock().unwrap();
-        
-        // First try to get existing board
-        if let Ok(board_id) = conn.query_row(
-            "SELECT id FROM boards WHERE fen = ?",
-            params![fen],
-            |row| row.get(0),
-        ) {
-            return Ok(board_id);
-        }
-
-        // If not found, insert new board
-        conn.execute(
-            "INSERT INTO boards (fen) VALUES (?)",
-            params![fen],
-        )?;
-        
-        Ok(conn.last_insert_rowid())
+        insert_board_in(&conn, fen)
     }
 
     /// Stores multiple chess moves with their statistics for a given board position
@@ -101,7 +116,7 @@ impl ChessTablesDb {
             tx.execute(
                 "INSERT INTO moves (board_id, san, win_rate, draw_rate, loose_rate, game_number)
                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                ON CONFLICT(san) DO UPDATE SET 
+                ON CONFLICT(board_id, san) DO UPDATE SET 
                     win_rate = ?3,
                     draw_rate = ?4,
                     loose_rate = ?5,
@@ -135,6 +150,57 @@ impl ChessTablesDb {
         Ok(board_id)
     }
 
+    /// Records the moves of every game of a PGN into the book
+    /// 
+    /// Each recorded move adds one game to the statistics of the move in its position.
+    /// A game with an illegal move is recorded up to that move.
+    /// 
+    /// # Arguments
+    /// * `pgn` - Content of a PGN file, with one or several games
+    /// * `filter` - Which moves are worth recording, given the result of their game
+    /// 
+    /// # Returns
+    /// * `Result<usize>` - Number of moves recorded or SQLite error
+    pub fn import_pgn(&self, pgn: &str, filter: ImportFilter) -> Result<usize, rusqlite::Error> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut recorded = 0;
+
+        for game in parse_pgn(pgn) {
+            let mut engine = match game.starting_position() {
+                Some(engine) => engine,
+                None => continue,
+            };
+
+            for san in game.moves.iter() {
+                let color = if engine.white_to_play() { Color::White } else { Color::Black };
+                let fen = engine.to_string();
+                if engine.play_san(san).is_err() {
+                    break;
+                }
+
+                if filter.accepts(game.result, color) {
+                    let board_id = insert_board_in(&tx, &fen)?;
+                    record_game_move(&tx, board_id, san, game.result)?;
+                    recorded += 1;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(recorded)
+    }
+
+    /// Same as `import_pgn`, reading the games from a file
+    /// 
+    /// # Returns
+    /// * `Result<usize>` - Number of moves recorded, `InvalidPath` if the file can't be read or SQLite error
+    pub fn import_pgn_file(&self, path: &Path, filter: ImportFilter) -> Result<usize, rusqlite::Error> {
+        let pgn = std::fs::read_to_string(path)
+            .map_err(|_| rusqlite::Error::InvalidPath(path.to_path_buf()))?;
+        self.import_pgn(&pgn, filter)
+    }
+
     /// Retrieves all stored moves for a given chess position
     /// 
     /// # Arguments
@@ -146,7 +212,7 @@ impl ChessTablesDb {
         let conn = self.conn.lock().unwrap();
         
         let mut stmt = conn.prepare(
-            "SELECT m.id, m.board_id, m.san, m.win_rate, m.draw_rate, m.loose_rate, m.game_number
+            "SELECT m.id, m.board_id, m.san, m.win_rate, m.draw_rate, m.loose_rate, m.game_number, m.eval_cp
             FROM moves m
             JOIN boards b ON m.board_id = b.id
             WHERE b.fen = ?"
@@ -161,6 +227,7 @@ impl ChessTablesDb {
                 draw_rate: row.get(4)?,
                 loose_rate: row.get(5)?,
                 game_number: row.get(6)?,
+                eval_cp: row.get(7)?,
             })
         })?;
 
@@ -171,4 +238,133 @@ impl ChessTablesDb {
 
         Ok(result)
     }
-}
\ No newline at end of file
+
+    /// Attaches an engine evaluation to a move already stored for a position
+    /// 
+    /// # Arguments
+    /// * `fen` - The FEN string representing the chess position
+    /// * `san` - The move in SAN notation
+    /// * `cp` - The evaluation in centipawns, from white's point of view
+    /// 
+    /// # Returns
+    /// * `Result<()>` - Success, `QueryReturnedNoRows` if the move isn't stored or SQLite error
+    pub fn set_move_eval(&self, fen: &str, san: &str, cp: i32) -> Result<(), rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+
+        let updated = conn.execute(
+            "UPDATE moves SET eval_cp = ?1
+            WHERE san = ?2 AND board_id = (SELECT id FROM boards WHERE fen = ?3)",
+            params![cp, san, fen],
+        )?;
+
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the id of a board, inserting it if it isn't stored yet
+fn insert_board_in(conn: &Connection, fen: &str) -> Result<i64, rusqlite::Error> {
+    // First try to get existing board
+    if let Ok(board_id) = conn.query_row(
+        "SELECT id FROM boards WHERE fen = ?",
+        params![fen],
+        |row| row.get(0),
+    ) {
+        return Ok(board_id);
+    }
+
+    // If not found, insert new board
+    conn.execute(
+        "INSERT INTO boards (fen) VALUES (?)",
+        params![fen],
+    )?;
+    
+    Ok(conn.last_insert_rowid())
+}
+
+/// Adds one game with the given result to the statistics of a move
+fn record_game_move(conn: &Connection, board_id: i64, san: &str, result: PgnResult) -> Result<(), rusqlite::Error> {
+    let (win, draw, loose) = match result {
+        PgnResult::WhiteWins => (1., 0., 0.),
+        PgnResult::Draw => (0., 1., 0.),
+        PgnResult::BlackWins => (0., 0., 1.),
+        PgnResult::Unknown => (0., 0., 0.),
+    };
+
+    // Rates are updated as running averages over the games
+    conn.execute(
+        "INSERT INTO moves (board_id, san, win_rate, draw_rate, loose_rate, game_number)
+        VALUES (?1, ?2, ?3, ?4, ?5, 1)
+        ON CONFLICT(board_id, san) DO UPDATE SET
+            win_rate = (win_rate * game_number + ?3) / (game_number + 1),
+            draw_rate = (draw_rate * game_number + ?4) / (game_number + 1),
+            loose_rate = (loose_rate * game_number + ?5) / (game_number + 1),
+            game_number = game_number + 1",
+        params![board_id, san, win, draw, loose],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn lichess_move(san: &str, games: u32) -> LichessMove {
+        LichessMove { san: san.to_string(), white: games, draws: 0, black: 0, average_rating: 2000 }
+    }
+
+    #[test]
+    fn sorts_evaluated_moves_first_then_by_popularity() {
+        let db = ChessTablesDb::at_path(PathBuf::from(":memory:")).unwrap();
+        let moves = vec![
+            lichess_move("e4", 500),
+            lichess_move("d4", 400),
+            lichess_move("c4", 300),
+            lichess_move("Nf3", 200),
+        ];
+        db.insert_board_with_moves(START_FEN, moves).unwrap();
+        db.set_move_eval(START_FEN, "c4", 30).unwrap();
+        db.set_move_eval(START_FEN, "Nf3", 25).unwrap();
+        assert!(db.set_move_eval(START_FEN, "h4", 0).is_err());
+
+        let order = |maximize: bool| {
+            let mut moves = db.get_moves_by_fen(START_FEN).unwrap();
+            MoveModel::sort_by_preference(&mut moves, maximize);
+            moves.into_iter().map(|db_move| db_move.san).collect::<Vec<_>>()
+        };
+
+        // White wants the highest evaluation, black the lowest
+        assert_eq!(order(true), ["c4", "Nf3", "e4", "d4"]);
+        assert_eq!(order(false), ["Nf3", "c4", "e4", "d4"]);
+    }
+
+    #[test]
+    fn not_lost_filter_excludes_the_moves_of_the_loser() {
+        use crate::game_engine::engine::Engine;
+
+        let pgn = "[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n";
+        let mut after_f3 = Engine::new();
+        after_f3.play_san("f3").unwrap();
+        let sans = |db: &ChessTablesDb, fen: &str| {
+            db.get_moves_by_fen(fen)
+                .unwrap()
+                .into_iter()
+                .map(|db_move| db_move.san)
+                .collect::<Vec<_>>()
+        };
+
+        // Only black's moves of the fool's mate are kept
+        let db = ChessTablesDb::at_path(PathBuf::from(":memory:")).unwrap();
+        assert_eq!(db.import_pgn(pgn, ImportFilter::NotLost).unwrap(), 2);
+        assert!(sans(&db, START_FEN).is_empty());
+        assert_eq!(sans(&db, &after_f3.to_string()), ["e5"]);
+
+        let db = ChessTablesDb::at_path(PathBuf::from(":memory:")).unwrap();
+        assert_eq!(db.import_pgn(pgn, ImportFilter::AllMoves).unwrap(), 4);
+        assert_eq!(sans(&db, START_FEN), ["f3"]);
+    }
+}
@@ -43,15 +43,63 @@ pub fn init_db(db_path: &PathBuf) -> Result<Connection, rusqlite::Error> {
         "CREATE TABLE IF NOT EXISTS moves (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             board_id INTEGER NOT NULL,
-            san TEXT UNIQUE NOT NULL,
+            san TEXT NOT NULL,
             win_rate REAL NOT NULL,
             draw_rate REAL NOT NULL,
             loose_rate REAL NOT NULL,
             game_number INTEGER NOT NULL,
+            eval_cp INTEGER,
+            UNIQUE (board_id, san),
             FOREIGN KEY (board_id) REFERENCES boards (id) ON DELETE CASCADE
         );",
         []
     )?;
 
+    // Databases created before the eval column existed need it added
+    let has_eval_column: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('moves') WHERE name = 'eval_cp'",
+        [],
+        |row| row.get(0)
+    )?;
+    if !has_eval_column {
+        conn.execute("ALTER TABLE moves ADD COLUMN eval_cp INTEGER", [])?;
+    }
+
+    // The same move can be played from many positions, old databases made
+    // the move alone unique and need the table rebuilt
+    let has_unique_san: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master
+        WHERE type = 'table' AND name = 'moves' AND sql LIKE '%san TEXT UNIQUE%'",
+        [],
+        |row| row.get(0)
+    )?;
+    if has_unique_san {
+        migrate_unique_san(&conn)?;
+    }
+
     Ok(conn)
+}
+
+/// Rebuilds the moves table with moves unique per board instead of globally
+fn migrate_unique_san(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "BEGIN;
+        CREATE TABLE moves_migrated (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            board_id INTEGER NOT NULL,
+            san TEXT NOT NULL,
+            win_rate REAL NOT NULL,
+            draw_rate REAL NOT NULL,
+            loose_rate REAL NOT NULL,
+            game_number INTEGER NOT NULL,
+            eval_cp INTEGER,
+            UNIQUE (board_id, san),
+            FOREIGN KEY (board_id) REFERENCES boards (id) ON DELETE CASCADE
+        );
+        INSERT INTO moves_migrated (id, board_id, san, win_rate, draw_rate, loose_rate, game_number, eval_cp)
+            SELECT id, board_id, san, win_rate, draw_rate, loose_rate, game_number, eval_cp FROM moves;
+        DROP TABLE moves;
+        ALTER TABLE moves_migrated RENAME TO moves;
+        COMMIT;"
+    )
 }
\ No newline at end of file
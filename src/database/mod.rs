@@ -1,4 +1,6 @@
 mod init;
 pub mod chess_table;
 pub mod models;
+pub mod polyglot;
+mod polyglot_random;
 pub mod results;
\ No newline at end of file
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
 use crate::lichess_api::models::LichessMove;
 
@@ -17,6 +18,8 @@ pub struct MoveModel {
     pub draw_rate: f64,
     pub loose_rate: f64,
     pub game_number: i64,
+    /// Engine evaluation of the move in centipawns, from white's point of view
+    pub eval_cp: Option<i32>,
 }
 
 impl MoveModel {
@@ -30,6 +33,16 @@ impl MoveModel {
         ((white_score - black_score) * 100.0) as f32
     }
 
+    /// Expected score of the move for the side playing it: 1 for a win,
+    /// 0.5 for a draw and 0 for a loss, weighted by their rates
+    ///
+    /// # Arguments
+    /// * `white_to_play` - `true` if white plays the move
+    pub fn expected_score(&self, white_to_play: bool) -> f64 {
+        let win_rate = if white_to_play { self.win_rate } else { self.loose_rate };
+        win_rate + self.draw_rate * 0.5
+    }
+
     pub fn from_lichess_move(mv: &LichessMove, board_id: i64) -> Self {
         let total_games = mv.white + mv.black + mv.draws;
         MoveModel {
@@ -39,7 +52,75 @@ impl MoveModel {
             draw_rate: mv.draws as f64 / total_games as f64,
             loose_rate: mv.black as f64 / total_games as f64,
             game_number: total_games as i64,
+            eval_cp: None,
             id: None
         }
     }
-}
\ No newline at end of file
+
+    /// Sorts moves from the most to the least preferred one
+    ///
+    /// Moves with an evaluation come first, best evaluation for the side to play first.
+    /// Moves without one follow, best expected score for the side to play first,
+    /// then most played first.
+    ///
+    /// # Arguments
+    /// * `moves` - Moves of a single position
+    /// * `maximize` - `true` if white is to play
+    pub fn sort_by_preference(moves: &mut [MoveModel], maximize: bool) {
+        moves.sort_by(|a, b| match (a.eval_cp, b.eval_cp) {
+            (Some(a_eval), Some(b_eval)) => {
+                if maximize {
+                    b_eval.cmp(&a_eval)
+                } else {
+                    a_eval.cmp(&b_eval)
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => b
+                .expected_score(maximize)
+                .total_cmp(&a.expected_score(maximize))
+                .then(b.game_number.cmp(&a.game_number)),
+        });
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book_move(san: &str, games: i64, win_rate: f64, draw_rate: f64) -> MoveModel {
+        MoveModel {
+            id: None,
+            board_id: 0,
+            san: san.to_string(),
+            win_rate,
+            draw_rate,
+            loose_rate: 1. - win_rate - draw_rate,
+            game_number: games,
+            eval_cp: None,
+        }
+    }
+
+    #[test]
+    fn ranks_by_expected_score_for_the_side_to_play() {
+        let moves = || {
+            vec![
+                book_move("e4", 1000, 0.3, 0.3),
+                book_move("d4", 200, 0.5, 0.3),
+                book_move("c4", 50, 0.4, 0.4),
+            ]
+        };
+        assert!((moves()[1].expected_score(true) - 0.65).abs() < 1e-9);
+        assert!((moves()[1].expected_score(false) - 0.35).abs() < 1e-9);
+
+        let order = |maximize: bool| {
+            let mut sorted = moves();
+            MoveModel::sort_by_preference(&mut sorted, maximize);
+            sorted.into_iter().map(|book_move| book_move.san).collect::<Vec<_>>()
+        };
+
+        // The most played move is the worst one for white, the best for black
+        assert_eq!(order(true), ["d4", "c4", "e4"]);
+        assert_eq!(order(false), ["e4", "c4", "d4"]);
+    }
+}
@@ -0,0 +1,385 @@
+//! Reader of Polyglot opening books (`.bin` files)
+//!
+//! A book is a list of 16 bytes big-endian entries sorted by key: the
+//! Polyglot hash of the position (8 bytes), the move (2 bytes), its weight
+//! (2 bytes) and a learning value (4 bytes).
+//!
+//! The key of a position xors values of the `Random64` table published
+//! with the Polyglot sources (781 numbers), bundled in `polyglot_random`.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use super::polyglot_random::POLYGLOT_RANDOM64;
+use crate::boards::Board;
+use crate::game_engine::engine::Engine;
+use crate::game_engine::player_move::{CastlingMove, NormalMove, PlayerMove, PromotionMove};
+use crate::pieces::moves::pawn_captures;
+use crate::pieces::{Color, Piece};
+
+/// Number of values of the Polyglot `Random64` table
+pub const POLYGLOT_RANDOM_COUNT: usize = 781;
+
+const ENTRY_SIZE: usize = 16;
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+/// A move of the book for a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolyglotEntry {
+    pub key: u64,
+    /// Move encoded as in the book: target file and row, source file and
+    /// row (3 bits each) then the promotion piece
+    pub raw_move: u16,
+    /// Relative weight of the move among the moves of the position
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl PolyglotEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut learn = [0u8; 4];
+        learn.copy_from_slice(&bytes[12..16]);
+        PolyglotEntry {
+            key: entry_key(bytes),
+            raw_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes(learn),
+        }
+    }
+}
+
+/// Key of the entry starting at `bytes`
+fn entry_key(bytes: &[u8]) -> u64 {
+    let mut key = [0u8; 8];
+    key.copy_from_slice(&bytes[0..8]);
+    u64::from_be_bytes(key)
+}
+
+/// Content of a book, entries are read from it when a position is looked up
+enum BookData {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+/// An opening book in the Polyglot format
+pub struct PolyglotBook {
+    data: BookData,
+}
+
+impl PolyglotBook {
+    /// Opens a `.bin` book, the file is mapped in memory rather than read
+    ///
+    /// The book must be sorted by key, as the format asks, and must not be
+    /// modified while it is open.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is only read, and the book file isn't written
+        // while it is open (see above)
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(PolyglotBook {
+            data: BookData::Mapped(map),
+        })
+    }
+
+    /// Reads a book already in memory, a truncated last entry is ignored
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut entries: Vec<&[u8]> = bytes.chunks_exact(ENTRY_SIZE).collect();
+
+        // Books are sorted by key, keep it true for the binary search
+        if !entries
+            .windows(2)
+            .all(|pair| entry_key(pair[0]) <= entry_key(pair[1]))
+        {
+            entries.sort_by_key(|entry| entry_key(entry));
+        }
+
+        PolyglotBook {
+            data: BookData::Owned(entries.concat()),
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match &self.data {
+            BookData::Mapped(map) => map,
+            BookData::Owned(bytes) => bytes,
+        }
+    }
+
+    /// Number of entries of the book
+    pub fn len(&self) -> usize {
+        self.bytes().len() / ENTRY_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry_bytes(&self, index: usize) -> &[u8] {
+        &self.bytes()[index * ENTRY_SIZE..(index + 1) * ENTRY_SIZE]
+    }
+
+    /// Index of the first entry whose key doesn't satisfy `below`, as
+    /// `slice::partition_point`
+    fn partition_point(&self, below: impl Fn(u64) -> bool) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if below(entry_key(self.entry_bytes(middle))) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    /// Returns the entries stored for a key, found by binary search
+    pub fn entries(&self, key: u64) -> Vec<PolyglotEntry> {
+        let start = self.partition_point(|entry_key| entry_key < key);
+        let end = self.partition_point(|entry_key| entry_key <= key);
+        (start..end)
+            .map(|index| PolyglotEntry::from_bytes(self.entry_bytes(index)))
+            .collect()
+    }
+
+    /// Returns the book moves of a position with their weights, most
+    /// weighted first. Moves that aren't legal in the position are skipped.
+    pub fn moves(&self, engine: &Engine) -> Vec<(PlayerMove, u16)> {
+        let legal_moves = engine.legal_moves();
+        let mut moves: Vec<(PlayerMove, u16)> = self
+            .entries(polyglot_key(engine))
+            .iter()
+            .map(|entry| {
+                (
+                    decode_move(entry.raw_move, engine.get_board()),
+                    entry.weight,
+                )
+            })
+            .filter(|(chess_move, _)| legal_moves.contains(chess_move))
+            .collect();
+        moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+        moves
+    }
+}
+
+/// Computes the Polyglot key of a position
+///
+/// The en passant file only counts when a pawn of the side to move
+/// stands next to the pushed pawn, as Polyglot does.
+pub fn polyglot_key(engine: &Engine) -> u64 {
+    let board = engine.get_board();
+    let mut key = 0;
+
+    for (square, piece, color) in board.individual_pieces_iter() {
+        key ^= POLYGLOT_RANDOM64[64 * piece_kind(piece, color) + square.trailing_zeros() as usize];
+    }
+
+    let castling_rights = [
+        board.white.castling_rights.is_short_castling_available(),
+        board.white.castling_rights.is_long_castling_available(),
+        board.black.castling_rights.is_short_castling_available(),
+        board.black.castling_rights.is_long_castling_available(),
+    ];
+    for (index, available) in castling_rights.into_iter().enumerate() {
+        if available {
+            key ^= POLYGLOT_RANDOM64[CASTLING_OFFSET + index];
+        }
+    }
+
+    let en_passant = polyglot_en_passant(board, engine.white_to_play());
+    if en_passant != 0 {
+        key ^= POLYGLOT_RANDOM64[EN_PASSANT_OFFSET + en_passant.trailing_zeros() as usize % 8];
+    }
+
+    if engine.white_to_play() {
+        key ^= POLYGLOT_RANDOM64[TURN_OFFSET];
+    }
+    key
+}
+
+/// Index of a piece in the Polyglot table: black pawn 0, white pawn 1,
+/// black knight 2, ... white king 11
+fn piece_kind(piece: Piece, color: Color) -> usize {
+    let piece_index = match piece {
+        Piece::Pawn => 0,
+        Piece::Knight => 1,
+        Piece::Bishop => 2,
+        Piece::Rook => 3,
+        Piece::Queen => 4,
+        Piece::King => 5,
+    };
+    let color_index = match color {
+        Color::White => 1,
+        Color::Black => 0,
+    };
+    2 * piece_index + color_index
+}
+
+/// Returns the en passant square when a pawn of the side to play can take
+/// on it, `0` otherwise
+fn polyglot_en_passant(board: &Board, white_to_play: bool) -> u64 {
+    let (player_board, pusher_board, pusher_color) = if white_to_play {
+        (&board.white, &board.black, Color::Black)
+    } else {
+        (&board.black, &board.white, Color::White)
+    };
+
+    if pawn_captures(pusher_board.en_passant, player_board.pawn, pusher_color) != 0 {
+        pusher_board.en_passant
+    } else {
+        0
+    }
+}
+
+/// Decodes a book move, castling being written as the king taking its own
+/// rook ("e1h1")
+fn decode_move(raw_move: u16, board: &Board) -> PlayerMove {
+    let target_index = (raw_move & 0x3F) as u32;
+    let current_index = ((raw_move >> 6) & 0x3F) as u32;
+    let (current_square, target_square) = (1u64 << current_index, 1u64 << target_index);
+
+    let promotion_piece = match (raw_move >> 12) & 0x7 {
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => None,
+    };
+    if let Some(piece) = promotion_piece {
+        return PlayerMove::Promotion(PromotionMove::new(current_square, target_square, piece));
+    }
+
+    if let Some((_, Piece::King)) = board.get_piece_at(current_square) {
+        match (current_index, target_index) {
+            (4, 7) | (60, 63) => return PlayerMove::Castling(CastlingMove::Short),
+            (4, 0) | (60, 56) => return PlayerMove::Castling(CastlingMove::Long),
+            _ => {}
+        }
+    }
+
+    PlayerMove::Normal(NormalMove::new(current_square, target_square))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Key reference positions of the Polyglot book format specification,
+    /// as moves from the start position
+    const REFERENCE_KEYS: [(&str, u64); 9] = [
+        ("", 0x463b96181691fc9c),
+        ("e2e4", 0x823c9b50fd114196),
+        ("e2e4 d7d5", 0x0756b94461c50fb0),
+        ("e2e4 d7d5 e4e5", 0x662fafb965db29d4),
+        ("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1),
+        ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637),
+        ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560),
+    ];
+
+    fn play(moves: &str) -> Engine {
+        let mut engine = Engine::new();
+        for uci in moves.split_whitespace() {
+            let chess_move = PlayerMove::from_uci(uci, &engine).unwrap();
+            engine.play(chess_move).unwrap();
+        }
+        engine
+    }
+
+    /// A book entry, with the move written as Polyglot does
+    fn entry(key: u64, from: u16, to: u16, weight: u16) -> Vec<u8> {
+        let raw_move = from << 6 | to;
+        [
+            &key.to_be_bytes()[..],
+            &raw_move.to_be_bytes(),
+            &weight.to_be_bytes(),
+            &[0; 4],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn keys_follow_the_polyglot_layout() {
+        let random = |index: usize| POLYGLOT_RANDOM64[index];
+        let key = |moves: &str| polyglot_key(&play(moves));
+
+        // White pawn (kind 1) from e2 to e4, black to move. e3 doesn't count,
+        // no black pawn can take on it
+        assert_eq!(
+            key("") ^ key("e2e4"),
+            random(64 + 12) ^ random(64 + 28) ^ random(TURN_OFFSET)
+        );
+
+        // f7-f5 next to the e5 pawn: the f file counts for white
+        assert_eq!(
+            key("e2e4 d7d5 e4e5") ^ key("e2e4 d7d5 e4e5 f7f5"),
+            random(53) ^ random(37) ^ random(EN_PASSANT_OFFSET + 5) ^ random(TURN_OFFSET)
+        );
+
+        // The white king (kind 11) loses both castling flags, and the en
+        // passant file is gone
+        assert_eq!(
+            key("e2e4 d7d5 e4e5 f7f5") ^ key("e2e4 d7d5 e4e5 f7f5 e1e2"),
+            random(11 * 64 + 4)
+                ^ random(11 * 64 + 12)
+                ^ random(CASTLING_OFFSET)
+                ^ random(CASTLING_OFFSET + 1)
+                ^ random(EN_PASSANT_OFFSET + 5)
+                ^ random(TURN_OFFSET)
+        );
+    }
+
+    #[test]
+    fn keys_match_the_reference_positions() {
+        for (moves, reference) in REFERENCE_KEYS {
+            assert_eq!(polyglot_key(&play(moves)), reference, "{moves:?}");
+        }
+    }
+
+    #[test]
+    fn reads_the_moves_of_a_book_file() {
+        let start = polyglot_key(&Engine::new());
+        let after_e4 = polyglot_key(&play("e2e4"));
+        // e2e4 and d2d4 from the start, a move that isn't legal there, and
+        // c7c5 after e4. The keys are sorted, as in a real book
+        let mut entries = vec![
+            (start, entry(start, 12, 28, 10)),
+            (start, entry(start, 11, 27, 30)),
+            (start, entry(start, 12, 36, 50)),
+            (after_e4, entry(after_e4, 50, 34, 1)),
+        ];
+        entries.sort_by_key(|(key, _)| *key);
+        let bytes: Vec<u8> = entries.into_iter().flat_map(|(_, bytes)| bytes).collect();
+
+        let path = std::env::temp_dir().join(format!("polyglot_{}.bin", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let book = PolyglotBook::open(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.entries(start).len(), 3);
+        let uci = |engine: &Engine| -> Vec<(String, u16)> {
+            book.moves(engine)
+                .into_iter()
+                .map(|(chess_move, weight)| (chess_move.to_uci(Color::White), weight))
+                .collect()
+        };
+        assert_eq!(
+            uci(&Engine::new()),
+            [("d2d4".to_string(), 30), ("e2e4".to_string(), 10)]
+        );
+        assert_eq!(uci(&play("e2e4")).len(), 1);
+        assert!(uci(&play("d2d4")).is_empty());
+
+        // The same book from memory, in any order
+        let reversed: Vec<u8> = bytes.chunks(ENTRY_SIZE).rev().flatten().copied().collect();
+        let in_memory = PolyglotBook::from_bytes(&reversed);
+        assert_eq!(in_memory.moves(&Engine::new()), book.moves(&Engine::new()));
+    }
+}
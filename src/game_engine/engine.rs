@@ -1,17 +1,27 @@
+use std::collections::HashMap;
+
+use super::epd::{clean_epd_san, parse_operations, split_epd, EpdError, EpdOps};
+use super::game_states::{DrawReason, GameStatus, TerminalReason};
+use super::legality::LegalityMasks;
 use super::move_evaluation_context::MoveEvaluationContext;
+use super::move_info::MoveInfo;
 use super::move_parsing::*;
 use super::move_piece_output::PieceMoveOutput;
 use super::move_results::{CorrectMoveResults, IncorrectMoveResults, MoveResult};
+use super::perft::PerftTable;
+use super::pgn::{parse_pgn, PgnResult};
 use super::player_move::{CastlingMove, NormalMove, PlayerMove, PromotionMove};
+use super::utility::get_piece_attacks;
 use super::utility::{get_color, get_final_castling_positions, get_half_turn_boards};
 use super::utility::{get_en_passant_ranks, get_half_turn_boards_mut};
 use super::utility::{get_initial_castling_positions, get_piece_type, get_possible_move};
 use super::utility::{get_promotion_rank_by_color, get_required_empty_squares, is_king_checked};
-use super::utility::{is_promotion_available, iter_into_u64, move_piece};
+use super::utility::{is_promotion_available, iter_into_u64, move_piece, string_from_move};
 
 use crate::boards::zobrist_hash::HASHER;
 use crate::boards::Board;
 use crate::pieces::piece::PROMOTE_PIECE;
+use crate::pieces::static_positions::{FILE_A, FILE_H};
 use crate::pieces::Color;
 use crate::pieces::Piece;
 
@@ -27,8 +37,17 @@ pub struct Engine {
     // rules
     board: Board,
     white_turn: bool,
+    // Plies since the start of the game
+    ply: u32,
+    // Plies since the last pawn move or capture, for the fifty-move rule
     halfmove_clock: u32,
     current_king_checked: bool,
+
+    // Zobrist hash of the position, updated with each move
+    hash: u64,
+    // Hashes of the positions since the last pawn move or capture,
+    // current position included
+    position_history: Vec<u64>,
 }
 
 impl Engine {
@@ -39,29 +58,49 @@ impl Engine {
     /// A new `Engine` instance with the following initial state:
     /// - `board`: A new `Board` instance representing the initial chessboard setup.
     /// - `white_turn`: A boolean set to `true`, indicating that it is White's turn to move.
+    /// - `ply`: An integer set to `0`, representing the number of half-moves since the start of the game.
     /// - `halfmove_clock`: An integer set to `0`, representing the number of half-moves since the last capture or pawn advance.
     ///
     /// # Example
     ///
     /// ```
+    /// use chess_engine::prelude::Engine;
+    ///
     /// let engine = Engine::new();
     /// ```
     pub fn new() -> Self {
+        let board = Board::new();
+        let hash = HASHER.compute_hash(&board, true);
         Engine {
-            board: Board::new(),
+            board,
             white_turn: true,
+            ply: 0,
             halfmove_clock: 0,
             current_king_checked: false,
+            hash,
+            position_history: vec![hash],
         }
     }
 
     /// Creates a copy of the current engine with an other board
     pub fn clone_with_new_board(&self, board: Board) -> Self {
+        let mut engine = self.with_unfinalized_board(board);
+        engine.hash = HASHER.compute_hash(&engine.board, engine.white_turn);
+        engine
+    }
+
+    /// Creates a copy of the current engine with the board after a move,
+    /// still holding the hash of the current board: `finalize_turn` updates
+    /// it from what the move changed
+    fn with_unfinalized_board(&self, board: Board) -> Self {
         Engine {
-            board: board,
+            board,
             white_turn: self.white_turn,
+            ply: self.ply,
             halfmove_clock: self.halfmove_clock,
             current_king_checked: self.current_king_checked,
+            hash: self.hash,
+            position_history: self.position_history.clone(),
         }
     }
 
@@ -86,34 +125,117 @@ impl Engine {
     /// * The target position is not a valid move for the piece.
     /// * The move leaves the king in check.
     pub fn play(&mut self, chess_move: PlayerMove) -> MoveResult {
-        // else we can play normal
-        self.board = match chess_move {
+        self.play_move_info(chess_move).map(|move_info| move_info.result)
+    }
+
+    /// Checks that a move can be played in this position, without playing it
+    ///
+    /// # Returns
+    /// * `Ok(())` - The move is legal
+    /// * `Err(IncorrectMoveResults)` - See `play`
+    fn validate_move(&self, chess_move: PlayerMove) -> Result<(), IncorrectMoveResults> {
+        let color = get_color(self.white_turn);
+        match chess_move {
             PlayerMove::Normal(normal_move) => {
-                // get squares and color
                 let (current_square, target_square) = normal_move.squares();
                 let new_board = self.perform_move(current_square, target_square)?;
 
-                // here we ensure the piece moved wasn't a pawn on promotion rank
-                // if it was, we return an error
-                if is_promotion_available(&new_board, target_square, get_color(self.white_turn)) {
+                // A pawn reaching the last rank must say what it becomes
+                if is_promotion_available(&new_board, target_square, color) {
                     return Err(IncorrectMoveResults::PromotionExpected);
                 }
-                new_board
             }
             PlayerMove::Castling(castling_side) => {
-                // perform casting
-                self.perform_castling(castling_side)?
+                self.perform_castling(castling_side)?;
             }
             PlayerMove::Promotion(promotion_move) => {
-                // get squares
                 let (current_square, target_square) = promotion_move.squares();
-                self.board = self.perform_move(current_square, target_square)?;
-                self.promote_pawn(promotion_move.promotion_piece(), target_square)?
+                let new_board = self.perform_move(current_square, target_square)?;
+
+                if !is_promotion_available(&new_board, target_square, color) {
+                    return Err(IncorrectMoveResults::IllegalPromotion);
+                }
             }
-        };
+        }
+        Ok(())
+    }
+
+    /// Plays a move already known to be legal in this position, such as a move
+    /// generated for it, without the validation done by `play`
+    ///
+    /// # Returns
+    /// * `Ok(CorrectMoveResults)` - Move executed
+    /// * `Err(NoPieceAtLocation)` - The side to play has no piece on the source square,
+    ///   the position is left untouched
+    pub(crate) fn play_unchecked(&mut self, chess_move: PlayerMove) -> MoveResult {
+        self.apply_move(chess_move).map(|move_info| move_info.result)
+    }
+
+    /// Applies the move on the board with `Board::apply_move` and finalizes the turn
+    fn apply_move(&mut self, chess_move: PlayerMove) -> Result<MoveInfo, IncorrectMoveResults> {
+        let (board, mut move_info) = self
+            .board
+            .apply_move(&chess_move, get_color(self.white_turn))
+            .ok_or(IncorrectMoveResults::NoPieceAtLocation)?;
+        let previous_board = std::mem::replace(&mut self.board, board);
+        move_info.result = self.finalize_turn(&previous_board);
+        Ok(move_info)
+    }
+
+    /// Passes the turn without moving, as used by null move pruning.
+    ///
+    /// The en passant square expires and the hash is updated incrementally from the
+    /// previous one. A null move is an irreversible step, the position history restarts.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The other side is to play
+    /// * `Err(IncorrectMoveResults::KingStillChecked)` - Passing while in check is illegal
+    pub fn make_null_move(&mut self) -> Result<(), IncorrectMoveResults> {
+        if self.is_king_checked() {
+            return Err(IncorrectMoveResults::KingStillChecked);
+        }
 
-        // Finalize the turn
-        Ok(self.finalize_turn())
+        let color = get_color(self.white_turn);
+
+        // The en passant square expires, if it was part of the hash
+        let hash = HASHER.clear_en_passant_hash(
+            self.zobrist_hash(),
+            HASHER.hashed_en_passant(&self.board, self.white_turn),
+        );
+
+        let (player_board, opponent_board) = get_half_turn_boards_mut(&mut self.board, color);
+        player_board.en_passant = 0;
+        opponent_board.en_passant = 0;
+
+        self.white_turn = !self.white_turn;
+        self.ply += 1;
+        self.halfmove_clock += 1;
+        self.compute_king_checked();
+
+        let hash = HASHER.toggle_side(hash);
+        debug_assert_eq!(hash, HASHER.compute_hash(&self.board, self.white_turn));
+
+        self.hash = hash;
+        self.position_history.clear();
+        self.position_history.push(hash);
+        Ok(())
+    }
+
+    /// Same as `play` but describes everything the move changed on the board,
+    /// which is what a UI needs to animate it.
+    ///
+    /// # Arguments
+    /// * `chess_move` - The move to execute
+    ///
+    /// # Returns
+    /// * `Ok(MoveInfo)` - Move executed successfully, `result` holds what `play` returns
+    /// * `Err(IncorrectMoveResults)` - Move failed, see `play`
+    pub fn play_move_info(
+        &mut self,
+        chess_move: PlayerMove,
+    ) -> Result<MoveInfo, IncorrectMoveResults> {
+        self.validate_move(chess_move)?;
+        self.apply_move(chess_move)
     }
 
     /// Same as `play` but the move is parsed from a **SAN** string (Standard Algebraic Notation).
@@ -196,6 +318,11 @@ impl Engine {
 
     /// Simulate and check if the king is in check
     ///
+    /// The check is done on the board after the move, en passant capture included, so
+    /// an en passant that removes both pawns from the rank of the king and uncovers a
+    /// rook or a queen (`8/8/8/KPp4r/8/8/8/7k w - c6 0 1`) is rejected like any other
+    /// discovered check. A faster generator working from pins must handle that case apart.
+    ///
     /// # Arguments
     ///
     /// * `current_square` - The current position of the piece as a bitboard.
@@ -215,16 +342,7 @@ impl Engine {
         color: Color,
     ) -> Result<PieceMoveOutput, IncorrectMoveResults> {
         // Simulate the move
-        let mut mv_piece_result = move_piece(
-            self.board.clone(),
-            current_square,
-            target_square,
-            color,
-            piece,
-        );
-
-        // perform en passant squares check
-        self.handle_en_passant(&mut mv_piece_result.board, current_square, target_square);
+        let mut mv_piece_result = self.apply_piece_move(current_square, target_square, piece, color);
 
         // Get the simulated player's and opponent's boards
         let (player_board, opponent_board) =
@@ -244,16 +362,70 @@ impl Engine {
         Ok(mv_piece_result)
     }
 
+    /// Plays a piece move on a copy of the board, en passant included,
+    /// without checking that it is legal
+    fn apply_piece_move(
+        &self,
+        current_square: u64,
+        target_square: u64,
+        piece: Piece,
+        color: Color,
+    ) -> PieceMoveOutput {
+        let mut mv_piece_result = move_piece(
+            self.board.clone(),
+            current_square,
+            target_square,
+            color,
+            piece,
+        );
+
+        // perform en passant squares check
+        self.handle_en_passant(&mut mv_piece_result.board, current_square, target_square);
+        mv_piece_result
+    }
+
+    /// Splits the pseudo-legal targets of a piece into the ones known to be
+    /// legal from the pins and checks, and the ones that must be simulated
+    /// to know (king moves and en passant)
+    ///
+    /// # Returns
+    /// A tuple `(legal_targets, targets_to_validate)`
+    fn split_targets(
+        &self,
+        masks: &LegalityMasks,
+        current_square: u64,
+        piece: Piece,
+        pseudo_legal_moves: u64,
+    ) -> (u64, u64) {
+        if piece == Piece::King {
+            return (0, pseudo_legal_moves);
+        }
+
+        let en_passant = match piece {
+            Piece::Pawn => {
+                let (_, opponent_board) =
+                    get_half_turn_boards(&self.board, get_color(self.white_turn));
+                pseudo_legal_moves & opponent_board.en_passant
+            }
+            _ => 0,
+        };
+        let legal_targets = masks.legal_targets(current_square, pseudo_legal_moves & !en_passant);
+        (legal_targets, en_passant)
+    }
+
     /// Finalize the turn after a move
     ///
     /// This function updates the turn, halfmove clock, and fullmove number adn castling rights.
     /// It also checks if there isn't running promotions
-    fn finalize_turn(&mut self) -> CorrectMoveResults {
+    ///
+    /// # Arguments
+    /// * `previous_board` - The board before the move, used to keep the position history
+    fn finalize_turn(&mut self, previous_board: &Board) -> CorrectMoveResults {
         // get the color
         let color = get_color(self.white_turn);
 
-        // Update castling rights directly on the player's board
-        self.update_all_castling_rights();
+        // Update castling rights of both players
+        self.board.update_castling_rights();
 
         // get player and opponent board
         let (_, opponent_board) = get_half_turn_boards_mut(&mut self.board, color);
@@ -262,50 +434,33 @@ impl Engine {
         opponent_board.en_passant = 0;
 
         // we get the initial position depending on the color
-        self.halfmove_clock += 1;
+        self.ply += 1;
         self.white_turn = !self.white_turn;
 
         // The turn moves and we update if the current king is checked
         self.compute_king_checked();
 
-        CorrectMoveResults::Ok
-    }
+        // A pawn move or a capture can't be undone, older positions can't repeat anymore
+        let pawns_moved = previous_board.white.pawn != self.board.white.pawn
+            || previous_board.black.pawn != self.board.black.pawn;
+        let piece_captured =
+            previous_board.bitboard().count_ones() != self.board.bitboard().count_ones();
+        if pawns_moved || piece_captured {
+            self.halfmove_clock = 0;
+            self.position_history.clear();
+        } else {
+            self.halfmove_clock += 1;
+        }
 
-    /// Updates castling rights for both players based on current board state
-    ///
-    /// This function checks and updates castling rights for both the player and opponent by:
-    /// 1. Verifying if the kings are still on their initial squares
-    /// 2. Checking if the rooks are still on their initial squares
-    /// 3. Removing castling rights if either piece has moved or been captured
-    ///
-    /// # Note
-    /// It's crucial to update both players' castling rights after every move because:
-    /// - A player capturing an opponent's rook should remove opponent's castling rights for that side
-    /// - Moving or losing a king removes all castling rights for that side
-    /// - Moving or losing a rook removes castling rights for that side
-    fn update_all_castling_rights(&mut self) {
-        let (initial_white_king, initial_white_short_rook, initial_white_long_rook) =
-            get_initial_castling_positions(Color::White);
-        let (initial_black_king, initial_black_short_rook, initial_black_long_rook) =
-            get_initial_castling_positions(Color::Black);
-
-        // Update white's castling rights
-        self.board.white.castling_rights.update_castling_rights(
-            self.board.white.king,
-            self.board.white.rook,
-            initial_white_king,
-            initial_white_short_rook,
-            initial_white_long_rook,
+        // Only what the move changed is hashed again
+        self.hash = HASHER.update_hash(self.hash, previous_board, &self.board, color);
+        debug_assert_eq!(
+            self.hash,
+            HASHER.compute_hash(&self.board, self.white_turn)
         );
+        self.position_history.push(self.hash);
 
-        // Update black's castling rights
-        self.board.black.castling_rights.update_castling_rights(
-            self.board.black.king,
-            self.board.black.rook,
-            initial_black_king,
-            initial_black_short_rook,
-            initial_black_long_rook,
-        );
+        CorrectMoveResults::Ok
     }
 
     /// Handles all en passant-related logic after a move, including both setting up and executing en passant captures.
@@ -378,6 +533,7 @@ impl Engine {
     /// 1. Verifies if castling is available based on:
     ///    - Required squares being empty
     ///    - Castling rights being maintained
+    ///    - None of the squares the king starts from, crosses or lands on being attacked
     /// 2. Moves both the king and rook to their respective positions
     /// 3. Ensures the king is not in check after the move
     fn perform_castling(&self, castling: CastlingMove) -> Result<Board, IncorrectMoveResults> {
@@ -389,8 +545,8 @@ impl Engine {
         // get color
         let color = get_color(self.white_turn);
 
-        // get player and opponent board
-        let (player_board, opponent_board) = get_half_turn_boards(&self.board, color);
+        // get player board
+        let (player_board, _) = get_half_turn_boards(&self.board, color);
 
         // get the full bitboard to ensure castling is available
         let full_bitboard = self.board.bitboard();
@@ -419,10 +575,18 @@ impl Engine {
             }
         };
 
-        if can_castle && !is_king_checked(player_board.king, player_board, opponent_board, color) {
-            // get final positions
-            let (final_king_pos, final_rook_pos) = get_final_castling_positions(castling, color);
+        // get final positions
+        let (final_king_pos, final_rook_pos) = get_final_castling_positions(castling, color);
+
+        // The king can't start from, cross or land on an attacked square
+        let transit_square = match castling {
+            CastlingMove::Long => initial_king_pos >> 1,
+            CastlingMove::Short => initial_king_pos << 1,
+        };
+        let king_path = initial_king_pos | transit_square | final_king_pos;
+        let path_attacked = self.attack_map(get_color(!self.white_turn)) & king_path != 0;
 
+        if can_castle && !path_attacked {
             // match the initial rook pos
             let initial_rook_pos = match castling {
                 CastlingMove::Long => initial_long_rook_pos,
@@ -476,6 +640,17 @@ impl Engine {
     /// * `Ok(u64)` - Bitboard where set bits represent legal destination squares
     /// * `Err(String)` - Error if no piece exists at the square
     pub fn get_moves(&self, current_square: u64) -> Result<u64, String> {
+        let masks = LegalityMasks::new(&self.board, get_color(self.white_turn));
+        self.get_moves_with_masks(&masks, current_square)
+    }
+
+    /// Same as `get_moves`, with the pins and checks of the position
+    /// already computed, to share them between the pieces
+    fn get_moves_with_masks(
+        &self,
+        masks: &LegalityMasks,
+        current_square: u64,
+    ) -> Result<u64, String> {
         let (player_board, opponent_board) =
             get_half_turn_boards(&self.board, get_color(self.white_turn));
         let piece_type = get_piece_type(player_board, current_square);
@@ -502,11 +677,11 @@ impl Engine {
             color,
         );
 
-        // Initialize a bitboard for filtered moves
-        let mut possible_moves = 0u64;
-        let mut moves_to_check = legal_moves;
+        // Pins and checks tell most moves apart, the others are simulated
+        let (mut possible_moves, mut moves_to_check) =
+            self.split_targets(masks, current_square, piece, legal_moves);
 
-        // Iterate through each set bit in legal_moves
+        // Iterate through each set bit in moves_to_check
         while moves_to_check != 0 {
             // Get the least significant set bit
             let target_square = 1u64 << moves_to_check.trailing_zeros();
@@ -535,12 +710,24 @@ impl Engine {
         *self = Self::new();
     }
 
+    /// Returns the number of half-moves played since the start of the game.
+    ///
+    /// A game loaded from a FEN starts at the ply given by its fullmove number.
+    pub fn ply(&self) -> u32 {
+        self.ply
+    }
+
+    /// Returns the fullmove number, starting at 1 and incremented after each black move.
+    pub fn fullmove_number(&self) -> u32 {
+        self.ply / 2 + 1
+    }
+
     /// Returns the number of full moves in the game.
     ///
     /// # Returns
     /// A `u32` representing the number of full moves.
     pub fn get_fullmove_number(&self) -> u32 {
-        self.halfmove_clock / 2 + 1
+        self.fullmove_number()
     }
 
     /// Returns the number of halfmoves since the last pawn move or capture.
@@ -551,12 +738,158 @@ impl Engine {
         self.halfmove_clock
     }
 
+    /// Returns the draw the side to move may claim, if any.
+    ///
+    /// Threefold repetition and the fifty-move rule don't end the game by
+    /// themselves, a player has to claim them.
+    ///
+    /// # Returns
+    /// * `Some(DrawReason::ThreefoldRepetition)` - The position occurred at least three times
+    /// * `Some(DrawReason::FiftyMoveRule)` - 50 moves were played without pawn move or capture
+    /// * `None` - No draw can be claimed
+    pub fn can_claim_draw(&self) -> Option<DrawReason> {
+        if self.is_threefold_repetition() {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.is_fifty_move_draw() {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the reason why the game is drawn, without any claim, if any.
+    ///
+    /// # Returns
+    /// * `Some(DrawReason::InsufficientMaterial)` - Neither side can mate anymore
+    /// * `Some(DrawReason::FivefoldRepetition)` - The position occurred at least five times
+    /// * `Some(DrawReason::SeventyFiveMoveRule)` - 75 moves were played without pawn move or
+    ///   capture (unless the last one mates)
+    /// * `Some(DrawReason::Stalemate)` - The side to move has no legal move and isn't checked
+    /// * `None` - The game isn't drawn
+    pub fn is_forced_draw(&self) -> Option<DrawReason> {
+        if self.has_insufficient_material() {
+            return Some(DrawReason::InsufficientMaterial);
+        }
+        if self.repetition_count() >= 5 {
+            return Some(DrawReason::FivefoldRepetition);
+        }
+
+        let has_legal_moves = !self
+            .generate_moves_with_engine_state()
+            .unwrap_or_default()
+            .is_empty();
+        if self.halfmove_clock >= 150 && (has_legal_moves || !self.is_king_checked()) {
+            Some(DrawReason::SeventyFiveMoveRule)
+        } else if !has_legal_moves && !self.is_king_checked() {
+            Some(DrawReason::Stalemate)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the draws that don't depend on the legal moves: insufficient
+    /// material, and the draws the side to move may claim
+    ///
+    /// This is cheap enough to be called on every node of a search.
+    pub fn draw_by_rule(&self) -> Option<DrawReason> {
+        if self.has_insufficient_material() {
+            Some(DrawReason::InsufficientMaterial)
+        } else {
+            self.can_claim_draw()
+        }
+    }
+
+    /// Returns why the game can't go on from this position, if it can't
+    ///
+    /// Mates and stalemates come first, then the draws of `draw_by_rule`.
+    pub fn terminal_reason(&self) -> Option<TerminalReason> {
+        let has_legal_moves = !self
+            .generate_moves_with_engine_state()
+            .unwrap_or_default()
+            .is_empty();
+
+        match (has_legal_moves, self.is_king_checked()) {
+            (false, true) => Some(TerminalReason::Checkmate {
+                winner: get_color(!self.white_turn),
+            }),
+            (false, false) => Some(TerminalReason::Stalemate),
+            (true, _) => self.draw_by_rule().map(TerminalReason::from),
+        }
+    }
+
+    /// Returns whether the game goes on, and how it ended otherwise
+    ///
+    /// Same priority as `terminal_reason`: checkmate and stalemate first,
+    /// then insufficient material, threefold repetition and the fifty-move
+    /// rule. This is the single call a front-end needs to end a game.
+    pub fn status(&self) -> GameStatus {
+        self.terminal_reason().into()
+    }
+
+    /// Returns the number of times the current position occurred, current one included.
+    ///
+    /// Positions are compared by Zobrist hash, which includes the side to move,
+    /// the castling rights and the en passant square. Only the positions since the
+    /// last pawn move or capture are kept, older ones can't repeat anymore.
+    pub fn repetition_count(&self) -> u8 {
+        // Same side to move only: every other position
+        let current_hash = self.zobrist_hash();
+        let count = self
+            .position_history
+            .iter()
+            .rev()
+            .step_by(2)
+            .filter(|&&hash| hash == current_hash)
+            .count();
+        count.min(u8::MAX as usize) as u8
+    }
+
+    /// Returns `true` if 50 moves (100 plies) were played without pawn move or capture.
+    ///
+    /// The side to move may then claim a draw, see `can_claim_draw`.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
+    /// Returns `true` if the current position occurred at least three times.
+    ///
+    /// The side to move may then claim a draw, see `can_claim_draw`.
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    /// Whether none of the players has enough material left to mate
+    ///
+    /// That is the case with kings only, a single minor piece, or only
+    /// bishops all standing on squares of the same color.
+    fn has_insufficient_material(&self) -> bool {
+        let (white, black) = (&self.board.white, &self.board.black);
+        if white.pawn | black.pawn | white.rook | black.rook | white.queen | black.queen != 0 {
+            return false;
+        }
+
+        let knights = white.knight | black.knight;
+        let bishops = white.bishop | black.bishop;
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        // a1 is a dark square, and so are all squares of the same parity of rank + file
+        const DARK_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
+    }
+
     /// Update the `current_king_checked` flag based on the current board state
     fn compute_king_checked(&mut self) {
         let color = get_color(self.white_turn);
         let (player_board, opponent_board) = get_half_turn_boards(&self.board, color);
-        self.current_king_checked =
-            is_king_checked(player_board.king, &opponent_board, &player_board, color);
+        // The color is the one of the attacker, it gives the direction of its pawns
+        self.current_king_checked = is_king_checked(
+            player_board.king,
+            opponent_board,
+            player_board,
+            get_color(!self.white_turn),
+        );
     }
 
     /// Returns `true` if the king of the current player is checked
@@ -564,6 +897,107 @@ impl Engine {
         self.current_king_checked
     }
 
+    /// Returns `true` if the king of the current player is checked
+    #[deprecated(note = "use `is_king_checked` instead")]
+    pub fn is_current_king_checked(&self) -> bool {
+        self.is_king_checked()
+    }
+
+    /// Returns `true` if the move takes a piece, en passant included.
+    ///
+    /// # Arguments
+    /// * `chess_move` - A move of the current player, legality isn't checked
+    pub fn is_capture(&self, chess_move: PlayerMove) -> bool {
+        let (current_square, target_square) = match chess_move {
+            PlayerMove::Normal(normal_move) => normal_move.squares(),
+            PlayerMove::Promotion(promotion_move) => promotion_move.squares(),
+            PlayerMove::Castling(_) => return false,
+        };
+        let (player_board, opponent_board) =
+            get_half_turn_boards(&self.board, get_color(self.white_turn));
+
+        let is_en_passant = player_board.pawn & current_square != 0
+            && opponent_board.en_passant & target_square != 0;
+        opponent_board.bitboard() & target_square != 0 || is_en_passant
+    }
+
+    /// Returns `true` if the move puts the opponent king in check, `false` if the move is illegal.
+    ///
+    /// # Arguments
+    /// * `chess_move` - A move of the current player
+    pub fn gives_check(&self, chess_move: PlayerMove) -> bool {
+        let mut engine = self.clone();
+        engine.play(chess_move).is_ok() && engine.is_king_checked()
+    }
+
+    /// Returns all the squares attacked by a side.
+    ///
+    /// Squares of friendly pieces are included when they are defended, and pins
+    /// are ignored: a pinned piece still attacks.
+    ///
+    /// # Arguments
+    /// * `color` - The attacking side
+    ///
+    /// # Returns
+    /// A `u64` bitboard of the attacked squares
+    pub fn attack_map(&self, color: Color) -> u64 {
+        let occupancy = self.board.bitboard();
+        let (player_board, _) = get_half_turn_boards(&self.board, color);
+
+        player_board
+            .individual_pieces_iter()
+            .fold(0, |attacks, (square, piece)| {
+                attacks | get_piece_attacks(piece, square, occupancy, color)
+            })
+    }
+
+    /// Returns whether a pawn of `by` attacks `square` now or could attack it after advancing.
+    ///
+    /// Only pawns on the adjacent files and behind the square (from their side) can,
+    /// blockers are ignored. A square no enemy pawn can ever attack is an outpost.
+    ///
+    /// # Arguments
+    /// * `square` - The square to check (single bit set)
+    /// * `by` - The color of the pawns
+    pub fn square_attackable_by_pawn(&self, square: u64, by: Color) -> bool {
+        let index = square.trailing_zeros();
+        let (file, rank) = (index % 8, index / 8);
+
+        let file_mask = 0x0101_0101_0101_0101u64 << file;
+        let adjacent_files = ((file_mask << 1) & !FILE_A) | ((file_mask >> 1) & !FILE_H);
+
+        // White pawns attack upward, so they must stand on a lower rank
+        let ranks_behind = match by {
+            Color::White => (1u64 << (rank * 8)) - 1,
+            Color::Black => u64::MAX.checked_shl((rank + 1) * 8).unwrap_or(0),
+        };
+
+        let (pawns_board, _) = get_half_turn_boards(&self.board, by);
+        pawns_board.pawn & adjacent_files & ranks_behind != 0
+    }
+
+    /// Returns, for each square, how many more white pieces than black pieces attack it.
+    ///
+    /// # Returns
+    /// An array indexed by square (a1 = 0, h8 = 63), positive when white controls the square
+    pub fn control_map(&self) -> [i8; 64] {
+        let occupancy = self.board.bitboard();
+        let mut control = [0i8; 64];
+
+        for (square, piece, color) in self.board.individual_pieces_iter() {
+            let attacks = get_piece_attacks(piece, square, occupancy, color);
+            let count = match color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+            for attacked_square in iter_into_u64(attacks) {
+                control[attacked_square as usize] += count;
+            }
+        }
+
+        control
+    }
+
     /// Promotes a pawn that has reached the opposite end of the board.
     ///
     /// # Arguments
@@ -605,36 +1039,278 @@ impl Engine {
     /// Returns all possible moves for all pieces of the current player.
     ///
     /// This function calculates all legal moves for each piece belonging to the current player
-    /// (determined by `white_turn`). For each piece, it returns its position, type, and a
-    /// bitboard representing all its possible moves.
+    /// (determined by `white_turn`). Each move has a single destination square, and a pawn
+    /// reaching the last rank yields one promotion move per promotion piece.
+    ///
+    /// Castling moves are not included.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing:
-    /// - `Ok(Vec<(u64, Pieces, u64)>)`: A vector of tuples where each tuple contains:
-    ///   - `u64`: The position of the piece as a bitboard (single bit set)
-    ///   - `Piece`: The type of the piece (e.g., Pawn, Knight, etc.)
-    ///   - `u64`: A bitboard representing all possible moves for this piece
-    /// - `Err(String)`: An error message if move generation fails
+    /// A vector of tuples where each tuple contains:
+    ///   - `Piece`: The type of the moving piece (e.g., Pawn, Knight, etc.)
+    ///   - `PlayerMove`: A `Normal` or `Promotion` move to one destination square
     pub fn get_all_moves_by_piece(&self) -> Vec<(Piece, PlayerMove)> {
         // get the correct color board
         let color = get_color(self.white_turn);
         let (player_board, _) = get_half_turn_boards(&self.board, color);
+        let promotion_rank = get_promotion_rank_by_color(color);
 
-        // then get all the pieces
-        let pieces = player_board.individual_pieces();
+        let masks = LegalityMasks::new(&self.board, color);
+
+        let mut pieces_with_moves = Vec::new();
+        for (current_square, piece) in player_board.individual_pieces_iter() {
+            let legal_moves = self
+                .get_moves_with_masks(&masks, current_square)
+                .expect("Move couldn't be unwrapped, shoudln't even happen");
 
-        let pieces_with_moves = pieces
+            for target_index in iter_into_u64(legal_moves) {
+                let target_square = 1u64 << target_index;
+                if piece == Piece::Pawn && target_square & promotion_rank != 0 {
+                    for promotion_piece in PROMOTE_PIECE {
+                        pieces_with_moves.push((
+                            piece,
+                            PlayerMove::Promotion(PromotionMove::new(
+                                current_square,
+                                target_square,
+                                promotion_piece,
+                            )),
+                        ));
+                    }
+                } else {
+                    pieces_with_moves.push((
+                        piece,
+                        PlayerMove::Normal(NormalMove::new(current_square, target_square)),
+                    ));
+                }
+            }
+        }
+
+        pieces_with_moves
+    }
+
+    /// Returns every legal move of the side to play, castling included. A pawn
+    /// reaching the last rank yields one promotion move per promotion piece.
+    ///
+    /// Unlike `generate_moves_with_engine_state`, the position after each move
+    /// isn't built, which makes it much cheaper when only the moves are needed.
+    pub fn legal_moves(&self) -> Vec<PlayerMove> {
+        let mut legal_moves: Vec<PlayerMove> = self
+            .get_all_moves_by_piece()
             .into_iter()
-            .map(|it| {
-                let unw = self
-                    .get_moves(it.0)
-                    .expect("Move couldn't be unwrapped, shoudln't even happen");
-                (it.1, PlayerMove::Normal(NormalMove::new(it.0, unw)))
+            .map(|(_, chess_move)| chess_move)
+            .collect();
+
+        for castling_move in [CastlingMove::Long, CastlingMove::Short] {
+            if self.perform_castling(castling_move).is_ok() {
+                legal_moves.push(PlayerMove::Castling(castling_move));
+            }
+        }
+
+        legal_moves
+    }
+
+    /// Returns the first legal move found, without generating all of them.
+    ///
+    /// Pawns reaching the last rank are promoted to a queen. Castling is never returned,
+    /// when castling is legal the king can also step toward its rook.
+    ///
+    /// # Returns
+    /// `None` only if the game is over (checkmate or stalemate)
+    pub fn first_legal_move(&self) -> Option<PlayerMove> {
+        let color = get_color(self.white_turn);
+        let (player_board, _) = get_half_turn_boards(&self.board, color);
+        let promotion_rank = get_promotion_rank_by_color(color);
+
+        player_board
+            .individual_pieces_iter()
+            .find_map(|(current_square, piece)| {
+                let legal_moves = self.get_moves(current_square).ok()?;
+                let target_square = 1u64 << iter_into_u64(legal_moves).next()?;
+
+                if piece == Piece::Pawn && target_square & promotion_rank != 0 {
+                    Some(PlayerMove::Promotion(PromotionMove::new(
+                        current_square,
+                        target_square,
+                        Piece::Queen,
+                    )))
+                } else {
+                    Some(PlayerMove::Normal(NormalMove::new(
+                        current_square,
+                        target_square,
+                    )))
+                }
             })
-            .collect::<Vec<_>>();
+    }
 
-        pieces_with_moves
+    /// Writes a move in SAN, the inverse of `get_move_by_san`.
+    ///
+    /// The origin of the piece is only given when another piece of the same type can
+    /// reach the same square (file first, then rank, then both), and the move ends
+    /// with `+` or `#` when it checks or mates.
+    ///
+    /// # Arguments
+    /// * `chess_move` - A legal move of the side to play, an illegal one gives a meaningless string
+    pub fn move_to_san(&self, chess_move: PlayerMove) -> String {
+        let mut san = match chess_move {
+            PlayerMove::Castling(_) => string_from_move(&chess_move),
+            PlayerMove::Normal(normal_move) => {
+                let (current_square, target_square) = normal_move.squares();
+                self.piece_move_san(chess_move, current_square, target_square, None)
+            }
+            PlayerMove::Promotion(promotion_move) => {
+                let (current_square, target_square) = promotion_move.squares();
+                let promotion_piece = Some(promotion_move.promotion_piece());
+                self.piece_move_san(chess_move, current_square, target_square, promotion_piece)
+            }
+        };
+
+        let mut next = self.clone();
+        if next.play(chess_move).is_ok() && next.is_king_checked() {
+            san.push(if next.first_legal_move().is_none() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+
+    /// Writes a sequence of moves in SAN, each move being played from the position
+    /// left by the previous one.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<String>)` - The moves in SAN
+    /// * `Err(IncorrectMoveResults)` - A move of the sequence is illegal
+    pub fn san_line(&self, moves: &[PlayerMove]) -> Result<Vec<String>, IncorrectMoveResults> {
+        let mut engine = self.clone();
+        moves
+            .iter()
+            .map(|&chess_move| {
+                let san = engine.move_to_san(chess_move);
+                engine.play(chess_move)?;
+                Ok(san)
+            })
+            .collect()
+    }
+
+    /// Writes moves played from `start` as PGN movetext (`1. e4 e5 2. Nf3`).
+    ///
+    /// A line starting with black to play begins with `n...`. When the last move ends
+    /// the game (mate or forced draw), the result is appended. The movetext stops
+    /// before the first illegal move.
+    pub fn movetext_from(start: &Engine, moves: &[PlayerMove]) -> String {
+        let mut engine = start.clone();
+        let mut tokens = Vec::new();
+
+        for (index, &chess_move) in moves.iter().enumerate() {
+            if engine.white_to_play() {
+                tokens.push(format!("{}.", engine.fullmove_number()));
+            } else if index == 0 {
+                tokens.push(format!("{}...", engine.fullmove_number()));
+            }
+
+            let san = engine.move_to_san(chess_move);
+            if engine.play(chess_move).is_err() {
+                return tokens.join(" ");
+            }
+            tokens.push(san);
+        }
+
+        let result = engine.pgn_result();
+        if result != PgnResult::Unknown {
+            tokens.push(result.as_token().to_string());
+        }
+
+        tokens.join(" ")
+    }
+
+    /// Returns the result of the game if it ended on the board (mate or forced draw).
+    ///
+    /// # Returns
+    /// The `PgnResult` of the game, `PgnResult::Unknown` if the game can go on
+    pub fn pgn_result(&self) -> PgnResult {
+        match self.terminal_reason() {
+            Some(TerminalReason::Checkmate {
+                winner: Color::White,
+            }) => PgnResult::WhiteWins,
+            Some(TerminalReason::Checkmate {
+                winner: Color::Black,
+            }) => PgnResult::BlackWins,
+            _ if self.is_forced_draw().is_some() => PgnResult::Draw,
+            _ => PgnResult::Unknown,
+        }
+    }
+
+    /// SAN of a move that isn't castling, without the check suffix
+    fn piece_move_san(
+        &self,
+        chess_move: PlayerMove,
+        current_square: u64,
+        target_square: u64,
+        promotion_piece: Option<Piece>,
+    ) -> String {
+        let piece = self
+            .board
+            .get_piece_at(current_square)
+            .map_or(Piece::Pawn, |(_, piece)| piece);
+        let mut san = String::new();
+
+        if piece == Piece::Pawn {
+            // Pawn captures are written from the file they start on
+            if self.is_capture(chess_move) {
+                san.push(square_to_file(current_square));
+            }
+        } else {
+            san.push(piece.to_char(Color::White));
+            san.push_str(&self.san_disambiguation(piece, current_square, target_square));
+        }
+
+        if self.is_capture(chess_move) {
+            san.push('x');
+        }
+        san.push(square_to_file(target_square));
+        san.push(square_to_rank(target_square));
+
+        if let Some(promotion_piece) = promotion_piece {
+            san.push('=');
+            san.push(promotion_piece.to_char(Color::White));
+        }
+        san
+    }
+
+    /// Origin of a piece in SAN, only needed when another piece of the same type
+    /// can move to the same square
+    fn san_disambiguation(&self, piece: Piece, current_square: u64, target_square: u64) -> String {
+        let other_origins: Vec<u64> = self
+            .get_all_moves_by_piece()
+            .into_iter()
+            .filter_map(|(other_piece, other_move)| match other_move {
+                PlayerMove::Normal(normal_move) if other_piece == piece => {
+                    let (other_square, other_target) = normal_move.squares();
+                    (other_target == target_square && other_square != current_square)
+                        .then_some(other_square)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let file = square_to_file(current_square);
+        let rank = square_to_rank(current_square);
+        if other_origins.is_empty() {
+            String::new()
+        } else if other_origins
+            .iter()
+            .all(|&square| square_to_file(square) != file)
+        {
+            file.to_string()
+        } else if other_origins
+            .iter()
+            .all(|&square| square_to_rank(square) != rank)
+        {
+            rank.to_string()
+        } else {
+            format!("{}{}", file, rank)
+        }
     }
 
     /// This function takes a move as a **SAN** string and returns a player move
@@ -670,15 +1346,19 @@ impl Engine {
         let (from_file, from_rank) = parse_opt_source_file_and_rank(piece, chars);
 
         let possible_moves = self.get_all_moves_by_piece();
-        let filtered_pieces =
-            filter_possible_moves(possible_moves, piece, target_square, from_file, from_rank);
+        let filtered_pieces = filter_possible_moves(
+            possible_moves,
+            piece,
+            target_square,
+            promotion_piece_opt,
+            from_file,
+            from_rank,
+        );
 
-        if filtered_pieces.len() != 1 {
-            return Err(IncorrectMoveResults::InvalidMove);
+        match filtered_pieces[..] {
+            [(_, player_move)] => Ok(player_move),
+            _ => Err(IncorrectMoveResults::InvalidMove),
         }
-
-        create_final_move(filtered_pieces[0].1, promotion_piece_opt, target_square)
-            .map_err(|_| IncorrectMoveResults::InvalidMove)
     }
 
     /// Generates all possible moves for the current player, considering the current state of the engine.
@@ -694,13 +1374,14 @@ impl Engine {
         let (player_board, opponent_board) = get_half_turn_boards(&self.board, color);
 
         // then get all the pieces
-        let pieces = player_board.individual_pieces();
+        let pieces = player_board.individual_pieces_iter();
+        let masks = LegalityMasks::new(&self.board, color);
 
         // init a vector for result
         let mut result = Vec::new();
 
         // iteratin INTO the pieces
-        for (current_square, piece) in pieces.into_iter() {
+        for (current_square, piece) in pieces {
             // Get the possible moves for the piece
             let pseudo_legal_moves = get_possible_move(
                 piece,
@@ -714,16 +1395,24 @@ impl Engine {
             // get promotion rnak
             let promotion_rank = get_promotion_rank_by_color(color);
 
+            let (legal_targets, targets_to_validate) =
+                self.split_targets(&masks, current_square, piece, pseudo_legal_moves);
+
             // iterate over the legal moves
-            for target_index in iter_into_u64(pseudo_legal_moves) {
+            for target_index in iter_into_u64(legal_targets | targets_to_validate) {
                 // Get the least significant set bit
                 let target_square = 1u64 << target_index;
 
                 // If the move doesn't leave king in check, add it to possible moves
-                match self.validate_move_safety(current_square, target_square, piece, color) {
+                let move_output = if target_square & targets_to_validate != 0 {
+                    self.validate_move_safety(current_square, target_square, piece, color)
+                } else {
+                    Ok(self.apply_piece_move(current_square, target_square, piece, color))
+                };
+                match move_output {
                     Ok(piece_move_output) => {
                         // in the case the move is valid, we just as if we would for a normal move
-                        let mut engine = self.clone_with_new_board(piece_move_output.board);
+                        let mut engine = self.with_unfinalized_board(piece_move_output.board);
 
                         // check if the move is a promotion
                         if piece == Piece::Pawn && target_square & promotion_rank != 0 {
@@ -733,8 +1422,8 @@ impl Engine {
                                 let new_board = promotion_engine
                                     .promote_pawn(promotion_piece, target_square)
                                     .unwrap();
-                                let mut final_engine = engine.clone_with_new_board(new_board);
-                                let move_result = final_engine.finalize_turn();
+                                let mut final_engine = engine.with_unfinalized_board(new_board);
+                                let move_result = final_engine.finalize_turn(&self.board);
 
                                 // add the moverow to the vec
                                 result.push(MoveEvaluationContext {
@@ -752,7 +1441,7 @@ impl Engine {
                             }
                         } else {
                             // get the move result
-                            let move_result = engine.finalize_turn();
+                            let move_result = engine.finalize_turn(&self.board);
 
                             // add the moverow to the vec
                             result.push(MoveEvaluationContext {
@@ -776,8 +1465,8 @@ impl Engine {
         // Add castling if available
         if let Ok(board) = self.perform_castling(CastlingMove::Long) {
             // in the case the move is valid, we just as if we would for a normal move
-            let mut engine = self.clone_with_new_board(board);
-            let move_result = engine.finalize_turn();
+            let mut engine = self.with_unfinalized_board(board);
+            let move_result = engine.finalize_turn(&self.board);
 
             // add the moverow to the vec
             result.push(MoveEvaluationContext {
@@ -793,8 +1482,8 @@ impl Engine {
         // Add castling if available
         if let Ok(board) = self.perform_castling(CastlingMove::Short) {
             // in the case the move is valid, we just as if we would for a normal move
-            let mut engine = self.clone_with_new_board(board);
-            let move_result = engine.finalize_turn();
+            let mut engine = self.with_unfinalized_board(board);
+            let move_result = engine.finalize_turn(&self.board);
 
             // add the moverow to the vec
             result.push(MoveEvaluationContext {
@@ -810,27 +1499,101 @@ impl Engine {
         Ok(result)
     }
 
+    /// Counts the leaves of the legal move tree, `depth` plies deep
+    ///
+    /// Comparing the counts with known values is the standard way to check
+    /// the move generation (castling, en passant, promotions, pins).
+    pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let possible_moves = self.generate_moves_with_engine_state().unwrap_or_default();
+        if depth == 1 {
+            return possible_moves.len() as u64;
+        }
+
+        possible_moves
+            .iter()
+            .map(|possible_move| possible_move.engine.perft(depth - 1))
+            .sum()
+    }
+
+    /// Same as `perft`, but the count is split by root move
+    ///
+    /// When a count is off, comparing the split with a reference engine
+    /// tells which move leads to the wrong subtree.
+    ///
+    /// # Returns
+    /// Every legal move with the leaves below it, `depth - 1` plies deeper.
+    /// Empty when `depth` is 0.
+    pub fn perft_divide(&self, depth: usize) -> Vec<(PlayerMove, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        self.generate_moves_with_engine_state()
+            .unwrap_or_default()
+            .iter()
+            .map(|possible_move| {
+                (
+                    possible_move.player_move,
+                    possible_move.engine.perft(depth - 1),
+                )
+            })
+            .collect()
+    }
+
+    /// Same as `perft`, but the count of every position is cached in `table`,
+    /// so transpositions are only counted once
+    ///
+    /// Both counts only agree if the Zobrist hash tells apart every
+    /// position reached, which also makes this a check of the hashing.
+    pub fn perft_hashed(&self, depth: usize, table: &mut PerftTable) -> u64 {
+        if depth <= 1 {
+            return self.perft(depth);
+        }
+
+        let hash = self.zobrist_hash();
+        if let Some(nodes) = table.get(hash, depth) {
+            return nodes;
+        }
+
+        let nodes = self
+            .generate_moves_with_engine_state()
+            .unwrap_or_default()
+            .iter()
+            .map(|possible_move| possible_move.engine.perft_hashed(depth - 1, table))
+            .sum();
+        table.insert(hash, depth, nodes);
+        nodes
+    }
+
     /// Parses a PGN (Portable Game Notation) string and plays the moves on the current game state.
     ///
     /// # Parameters
     /// - `pgn`: A reference to a `str` containing the PGN notation of the moves to be played.
     ///   The PGN string should follow the standard format, where moves are separated by
-    ///   whitespace and move numbers (e.g., `1.`) are included but ignored.
+    ///   whitespace and move numbers (e.g., `1.`) are included but ignored. Tag pairs,
+    ///   comments and the result are skipped, only the first game is played and its `FEN`
+    ///   tag isn't used, the moves are played from the current state.
     ///
     /// # Returns
     /// - `Ok(())`: If all moves in the PGN string are successfully parsed and played.
     /// - `Err(IncorrectMoveResults::IllegalMove)`: If any move in the PGN string is invalid or illegal.
     pub fn play_pgn_str(&mut self, pgn: &str) -> Result<CorrectMoveResults, IncorrectMoveResults> {
-        // Split the PGN by whitespace and filter out move numbers and empty tokens
-        let moves = pgn
-            .split_whitespace()
-            .filter(|token| !token.ends_with('.') && !token.is_empty());
+        // Keep the main line of the first game
+        let moves = parse_pgn(pgn)
+            .into_iter()
+            .next()
+            .map(|game| game.moves)
+            .unwrap_or_default();
 
         // Clone the current engine
         let mut engine = self.clone();
 
         // play on the cloned engine so it doesn't affect the current one
-        for mv in moves {
+        for mv in moves.iter() {
             engine.play_san(mv)?;
         }
 
@@ -877,23 +1640,809 @@ impl Engine {
     /// # Returns
     /// * `Ok(Engine)` - If the FEN string is successfully parsed and the engine is created.
     /// * `Err(())` - If the FEN string is invalid or cannot be parsed.
+    ///
+    /// # Note
+    /// The position history starts at this position, earlier repetitions are unknown.
     pub fn from_fen(fen: &str) -> Result<Engine, ()> {
         // Split FEN string into its components
         let parts: Vec<&str> = fen.split_whitespace().collect();
         if parts.len() != 6 {
             return Err(());
         }
-        unimplemented!()
+
+        let mut board = parse_fen_board_position(parts[0])?;
+
+        let white_turn = match parts[1] {
+            "w" => true,
+            "b" => false,
+            _ => return Err(()),
+        };
+
+        parse_fen_castling(parts[2], &mut board)?;
+        parse_fen_en_passant(parts[3], white_turn, &mut board)?;
+
+        // halfmove clock and fullmove number
+        let halfmove_clock: u32 = parts[4].parse().map_err(|_| ())?;
+        let fullmove_number: u32 = parts[5].parse().map_err(|_| ())?;
+        if fullmove_number == 0 {
+            return Err(());
+        }
+        // The fullmove number must fit in the ply counter
+        let ply = (fullmove_number - 1)
+            .checked_mul(2)
+            .and_then(|ply| ply.checked_add(if white_turn { 0 } else { 1 }))
+            .ok_or(())?;
+
+        let hash = HASHER.compute_hash(&board, white_turn);
+        let mut engine = Engine {
+            board,
+            white_turn,
+            ply,
+            halfmove_clock,
+            current_king_checked: false,
+            hash,
+            position_history: vec![hash],
+        };
+        engine.compute_king_checked();
+
+        // The side that just moved can't be in check
+        let color = get_color(white_turn);
+        let (player_board, opponent_board) = get_half_turn_boards(&engine.board, color);
+        if is_king_checked(opponent_board.king, player_board, opponent_board, color) {
+            return Err(());
+        }
+
+        Ok(engine)
     }
 
-    /// Computes the hash for a given node based on the board and whose turn it is.
+    /// Build a new Engine from an EPD record, as used by test suites
+    ///
+    /// An EPD record is a FEN without the move counters, followed by
+    /// operations (`bm Qg6; id "WAC.001";`). The counters are read from the
+    /// `hmvc` and `fmvn` operations if any, they're 0 and 1 otherwise.
     ///
     /// # Arguments
-    /// * `node` - A reference to the node whose hash needs to be computed.
+    /// * `epd` - A reference to a `str` containing the EPD record.
     ///
     /// # Returns
-    /// A 64-bit unsigned integer representing the hash of the node.
+    /// * `Ok((Engine, EpdOps))` - The position and its operations, `bm` and `am` parsed from SAN.
+    /// * `Err(EpdError)` - If a position field is missing or invalid, or an operation can't
+    ///   be read.
+    pub fn from_epd(epd: &str) -> Result<(Engine, EpdOps), EpdError> {
+        let (fields, operations) = split_epd(epd).ok_or(EpdError::MissingFields)?;
+        let operations: HashMap<String, String> = parse_operations(operations).into_iter().collect();
+
+        let invalid_operation = |opcode: &str| EpdError::InvalidOperation {
+            opcode: opcode.to_string(),
+            operands: operations.get(opcode).cloned().unwrap_or_default(),
+        };
+        let counter = |opcode: &str, default: u32| -> Result<u32, EpdError> {
+            operations.get(opcode).map_or(Ok(default), |operands| {
+                operands.parse().map_err(|_| invalid_operation(opcode))
+            })
+        };
+        let halfmove_clock = counter("hmvc", 0)?;
+        let fullmove_number = counter("fmvn", 1)?;
+
+        let fen = format!("{} {} {}", fields.join(" "), halfmove_clock, fullmove_number);
+        let engine = Engine::from_fen(&fen).map_err(|_| EpdError::InvalidPosition)?;
+
+        let parse_moves = |opcode: &str| -> Result<Vec<PlayerMove>, EpdError> {
+            operations.get(opcode).map_or(Ok(Vec::new()), |moves| {
+                moves
+                    .split_whitespace()
+                    .map(|san| {
+                        engine
+                            .get_move_by_san(clean_epd_san(san))
+                            .map_err(|_| invalid_operation(opcode))
+                    })
+                    .collect()
+            })
+        };
+        let best_moves = parse_moves("bm")?;
+        let avoid_moves = parse_moves("am")?;
+
+        let ops = EpdOps {
+            operations,
+            best_moves,
+            avoid_moves,
+        };
+        Ok((engine, ops))
+    }
+
+    /// Returns the same position seen from the other side.
+    ///
+    /// The board is mirrored vertically, the colors are swapped and so is the side to
+    /// move, castling rights and en passant follow their pieces. Both positions are
+    /// strategically equivalent, the best move of one is the mirror (`PlayerMove::flipped`)
+    /// of the best move of the other. The move counters are kept, the history of the
+    /// previous positions is not.
+    pub fn flipped(&self) -> Engine {
+        let board = self.board.flipped();
+        let white_turn = !self.white_turn;
+        let hash = HASHER.compute_hash(&board, white_turn);
+
+        Engine {
+            board,
+            white_turn,
+            // Keeps the fullmove number, the parity of the ply gives the side to move
+            ply: self.ply ^ 1,
+            halfmove_clock: self.halfmove_clock,
+            current_king_checked: self.current_king_checked,
+            hash,
+            position_history: vec![hash],
+        }
+    }
+
+    /// Returns the Zobrist hash of the position: the pieces, the side to move, the
+    /// castling rights and the en passant square when it can be taken.
+    ///
+    /// The hash is kept up to date move by move, it's equal to the one computed
+    /// from scratch by `HASHER.compute_hash`.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Same as `hash`
     pub fn zobrist_hash(&self) -> u64 {
-        HASHER.compute_hash(&self.board, self.white_to_play())
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE_AT_MOVE_30: &str =
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 30";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn play_unchecked_rebuilds_the_generated_positions() {
+        for fen in [
+            KIWIPETE,
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let engine = Engine::from_fen(fen).unwrap();
+            for child in engine.generate_moves_with_engine_state().unwrap() {
+                let mut rebuilt = engine.clone();
+                rebuilt.play_unchecked(child.player_move).unwrap();
+                assert_eq!(rebuilt.to_string(), child.engine.to_string());
+                assert_eq!(rebuilt.hash(), child.engine.hash());
+                assert_eq!(rebuilt.is_king_checked(), child.engine.is_king_checked());
+            }
+        }
+    }
+
+    #[test]
+    fn repetitions_become_claimable_then_forced() {
+        let mut engine = Engine::new();
+        let knight_moves = [(6, 21), (62, 45), (21, 6), (45, 62)];
+
+        // Each round of knight moves brings the start position back once more
+        for occurrences in 2..=5 {
+            for (from, to) in knight_moves {
+                engine.play(PlayerMove::Normal(NormalMove::new(1 << from, 1 << to))).unwrap();
+            }
+
+            let (claim, forced) = match occurrences {
+                2 => (None, None),
+                3 | 4 => (Some(DrawReason::ThreefoldRepetition), None),
+                _ => (Some(DrawReason::ThreefoldRepetition), Some(DrawReason::FivefoldRepetition)),
+            };
+            assert_eq!(engine.can_claim_draw(), claim, "{occurrences} occurrences");
+            assert_eq!(engine.is_forced_draw(), forced, "{occurrences} occurrences");
+        }
+    }
+
+    #[test]
+    fn fifty_moves_are_claimable_and_seventy_five_forced() {
+        // Ra1-a2 is a quiet move, it increments the halfmove clock
+        let rook_move = PlayerMove::Normal(NormalMove::new(1 << 0, 1 << 8));
+        let cases = [
+            (98, None, None),
+            (99, Some(DrawReason::FiftyMoveRule), None),
+            (148, Some(DrawReason::FiftyMoveRule), None),
+            (149, Some(DrawReason::FiftyMoveRule), Some(DrawReason::SeventyFiveMoveRule)),
+        ];
+
+        for (halfmove_clock, claim, forced) in cases {
+            let fen = format!("4k3/8/8/8/8/8/8/R3K3 w - - {halfmove_clock} 100");
+            let mut engine = Engine::from_fen(&fen).unwrap();
+            engine.play(rook_move).unwrap();
+            assert_eq!(engine.can_claim_draw(), claim, "{fen}");
+            assert_eq!(engine.is_forced_draw(), forced, "{fen}");
+        }
+    }
+
+    #[test]
+    fn ply_counts_every_move_and_the_clock_resets_on_pawns_and_captures() {
+        let mut engine = Engine::new();
+        // (move, ply, halfmove clock, fullmove number)
+        let moves = [
+            ("Nf3", 1, 1, 1),
+            ("Nc6", 2, 2, 2),
+            ("e4", 3, 0, 2),
+            ("Nd4", 4, 1, 3),
+            ("Nxd4", 5, 0, 3),
+            ("Nf6", 6, 1, 4),
+        ];
+
+        for (san, ply, halfmove_clock, fullmove_number) in moves {
+            engine.play_san(san).unwrap();
+            assert_eq!(engine.ply(), ply, "{san}");
+            assert_eq!(engine.get_halfmove_clock(), halfmove_clock, "{san}");
+            assert_eq!(engine.fullmove_number(), fullmove_number, "{san}");
+        }
+    }
+
+    #[test]
+    fn movetext_numbers_the_moves_and_ends_with_the_result() {
+        // Parses the line while playing it, then writes it back
+        let moves_of = |start: &Engine, sans: &[&str]| {
+            let mut engine = start.clone();
+            sans.iter()
+                .map(|san| {
+                    let chess_move = engine.get_move_by_san(san).unwrap();
+                    engine.play(chess_move).unwrap();
+                    chess_move
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let start = Engine::new();
+        let fools_mate = moves_of(&start, &["f3", "e5", "g4", "Qh4"]);
+        assert_eq!(Engine::movetext_from(&start, &fools_mate), "1. f3 e5 2. g4 Qh4# 0-1");
+
+        let mut after_e4 = Engine::new();
+        after_e4.play_san("e4").unwrap();
+        let line = moves_of(&after_e4, &["e5", "Nf3", "Nc6"]);
+        assert_eq!(Engine::movetext_from(&after_e4, &line), "1... e5 2. Nf3 Nc6");
+    }
+
+    #[test]
+    fn en_passant_exposing_the_king_on_its_rank_is_illegal() {
+        // bxc6 would remove both pawns between the king and the rook
+        let pinned = Engine::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 1").unwrap();
+        assert!(pinned.clone().play_san("bxc6").is_err());
+        // Ka4, Ka6, Kb6 and b6
+        assert_eq!(pinned.perft(1), 4);
+
+        let free = Engine::from_fen("8/8/8/KPp5/8/8/8/7k w - c6 0 1").unwrap();
+        assert!(free.clone().play_san("bxc6").is_ok());
+        assert_eq!(free.perft(1), 5);
+    }
+
+    #[test]
+    fn outposts_are_out_of_reach_of_the_enemy_pawns() {
+        let d5 = 1 << 35;
+        let cases = [
+            // Protected by e4, no black pawn on the c and e files
+            ("4k3/pp3ppp/8/3N4/4P3/8/8/4K3 w - - 0 1", false),
+            // A black pawn that went past d5 can't come back
+            ("4k3/pp3ppp/8/3N4/2p1P3/8/8/4K3 w - - 0 1", false),
+            // c7-c6 would chase the knight
+            ("4k3/ppp2ppp/8/3N4/4P3/8/8/4K3 w - - 0 1", true),
+            // e6 already attacks it
+            ("4k3/pp3ppp/4p3/3N4/4P3/8/8/4K3 w - - 0 1", true),
+        ];
+
+        for (fen, attackable) in cases {
+            let engine = Engine::from_fen(fen).unwrap();
+            assert_eq!(engine.square_attackable_by_pawn(d5, Color::Black), attackable, "{fen}");
+        }
+
+        // Same from the other side, white pawns attack upward
+        let engine = Engine::from_fen("4k3/8/8/8/8/8/3P4/4K3 w - - 0 1").unwrap();
+        assert!(engine.square_attackable_by_pawn(1 << 44, Color::White));
+        assert!(!engine.square_attackable_by_pawn(1 << 2, Color::White));
+    }
+
+    #[test]
+    fn fullmove_number_follows_the_fen_field() {
+        // (start FEN, moves played, expected fullmove number)
+        let cases = [
+            (START_FEN, vec![], 1),
+            (START_FEN, vec!["e4"], 1),
+            (START_FEN, vec!["e4", "e5"], 2),
+            (START_FEN, vec!["e4", "e5", "Nf3", "Nc6", "Bb5"], 3),
+            (START_FEN, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"], 4),
+            // Black to move first
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", vec![], 1),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", vec!["e5"], 2),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", vec!["e5", "Nf3"], 2),
+            // Later in a game
+            (KIWIPETE_AT_MOVE_30, vec![], 30),
+            (KIWIPETE_AT_MOVE_30, vec!["Qxf6"], 30),
+            (KIWIPETE_AT_MOVE_30, vec!["Qxf6", "Kd8"], 31),
+        ];
+
+        for (fen, moves, fullmove_number) in cases {
+            let mut engine = Engine::from_fen(fen).unwrap();
+            for san in &moves {
+                engine.play_san(san).unwrap();
+            }
+            assert_eq!(engine.fullmove_number(), fullmove_number, "{fen} {moves:?}");
+
+            let emitted = engine.to_string();
+            let field = emitted.split_whitespace().last().unwrap();
+            assert_eq!(field, fullmove_number.to_string(), "{emitted}");
+        }
+    }
+
+    #[test]
+    fn knight_moves_cross_the_fifty_move_threshold() {
+        let mut engine = Engine::from_fen("1n2k3/8/8/8/8/8/8/4K1N1 w - - 90 60").unwrap();
+        // Reversible moves that never repeat a position
+        let moves = ["Nf3", "Nc6", "Nh4", "Na5", "Ng2", "Nb3", "Ne3", "Nd2", "Nc2", "Nb1"];
+
+        for (played, san) in (1..).zip(moves) {
+            engine.play_san(san).unwrap();
+            let reached = 90 + played >= 100;
+            assert_eq!(engine.is_fifty_move_draw(), reached, "{san}");
+            assert_eq!(engine.can_claim_draw().is_some(), reached, "{san}");
+        }
+        assert_eq!(engine.can_claim_draw(), Some(DrawReason::FiftyMoveRule));
+
+        // A capture on the 100th ply resets the clock instead
+        let fen = "4k3/8/8/4p3/8/5N2/8/4K3 w - - 99 60";
+        let mut quiet = Engine::from_fen(fen).unwrap();
+        quiet.play_san("Nd4").unwrap();
+        assert!(quiet.is_fifty_move_draw());
+        let mut capture = Engine::from_fen(fen).unwrap();
+        capture.play_san("Nxe5").unwrap();
+        assert!(!capture.is_fifty_move_draw());
+        assert_eq!(capture.get_halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn castling_through_an_attacked_square_is_rejected() {
+        let short = PlayerMove::Castling(CastlingMove::Short);
+        let long = PlayerMove::Castling(CastlingMove::Long);
+        // (FEN, castle, legal)
+        let cases = [
+            ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", short, true),
+            ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", long, true),
+            // The rook attacks the transit square f1
+            ("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1", short, false),
+            // d1, the transit square of the long castle
+            ("3rk3/8/8/8/8/8/8/R3K2R w KQ - 0 1", long, false),
+            // b1 is crossed by the rook only
+            ("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", long, true),
+            // Same for black, a bishop on b4 attacks f8
+            ("r3k2r/8/8/8/1B6/8/8/4K3 b kq - 0 1", short, false),
+            ("r3k2r/8/8/8/1B6/8/8/4K3 b kq - 0 1", long, true),
+        ];
+
+        for (fen, castle, legal) in cases {
+            let engine = Engine::from_fen(fen).unwrap();
+            assert_eq!(engine.clone().play(castle).is_ok(), legal, "{fen} {castle:?}");
+            assert_eq!(engine.legal_moves().contains(&castle), legal, "{fen} {castle:?}");
+        }
+    }
+
+    #[test]
+    fn castling_rights_and_live_en_passant_change_the_hash() {
+        let hash = |fen: &str| Engine::from_fen(fen).unwrap().zobrist_hash();
+
+        let castling: Vec<u64> = ["KQkq", "Qkq", "Kkq", "KQq", "KQk", "-"]
+            .iter()
+            .map(|rights| hash(&format!("r3k2r/8/8/8/8/8/8/R3K2R w {rights} - 0 1")))
+            .collect();
+        for (i, a) in castling.iter().enumerate() {
+            for b in &castling[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        // d4 can take on e3
+        assert_ne!(
+            hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"),
+            hash("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1")
+        );
+        // No black pawn next to e4, the square doesn't count
+        assert_eq!(
+            hash("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"),
+            hash("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1")
+        );
+
+        // The double push gives the same hash as the position set up directly
+        let mut engine = Engine::from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1").unwrap();
+        engine.play_san("e4").unwrap();
+        assert_eq!(engine.zobrist_hash(), hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"));
+    }
+
+    #[test]
+    fn reads_epd_records_and_their_operations() {
+        let wac_001 =
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4BK1 w - - bm Qg6; id \"WAC.001\";";
+        let (engine, ops) = Engine::from_epd(wac_001).unwrap();
+        let queen_sacrifice = engine.get_move_by_san("Qg6").unwrap();
+        assert_eq!(ops.id(), Some("WAC.001"));
+        assert_eq!(ops.best_moves, [queen_sacrifice]);
+        assert!(ops.is_solved_by(queen_sacrifice));
+        assert!(!ops.is_solved_by(engine.get_move_by_san("Qh4").unwrap()));
+        assert!(engine.to_string().ends_with(" - 0 1"));
+
+        // Counters from the operations, a quoted `;` doesn't end the id
+        let epd = "4k3/8/8/8/8/8/4P3/4K3 w - - am Kd1 Kf1; hmvc 12; fmvn 40; id \"a;b\"";
+        let (engine, ops) = Engine::from_epd(epd).unwrap();
+        assert!(engine.to_string().ends_with(" 12 40"));
+        assert_eq!(ops.id(), Some("a;b"));
+        assert_eq!(ops.avoid_moves.len(), 2);
+        assert!(ops.is_solved_by(engine.get_move_by_san("e4").unwrap()));
+        assert!(!ops.is_solved_by(engine.get_move_by_san("Kd1").unwrap()));
+    }
+
+    #[test]
+    fn epd_errors_tell_the_position_from_the_operations() {
+        for (epd, expected) in [
+            ("4k3/8/8/8/8/8/8/4K3 w -", EpdError::MissingFields),
+            ("4k3/8/8/8/8/8/8/4K3 x - - bm Kd1;", EpdError::InvalidPosition),
+            ("4k3/8/8/8/8/8/8/4KK2 w - - bm Kd1;", EpdError::InvalidPosition),
+        ] {
+            assert_eq!(Engine::from_epd(epd).unwrap_err(), expected, "{epd}");
+        }
+
+
+        for (epd, opcode, operands) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - bm Ke3;", "bm", "Ke3"),
+            ("4k3/8/8/8/8/8/8/4K3 w - - bm Kd1; am Kd1 Qd1;", "am", "Kd1 Qd1"),
+            ("4k3/8/8/8/8/8/8/4K3 w - - hmvc many;", "hmvc", "many"),
+        ] {
+            let expected = EpdError::InvalidOperation {
+                opcode: opcode.to_string(),
+                operands: operands.to_string(),
+            };
+            assert_eq!(Engine::from_epd(epd).unwrap_err(), expected, "{epd}");
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_the_hash_from_scratch() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(2051);
+        for start in [START_FEN, KIWIPETE, "4k3/1P6/8/3pP3/8/8/6p1/4K2R w K d6 0 1"] {
+            for _ in 0..10 {
+                let mut engine = Engine::from_fen(start).unwrap();
+                for _ in 0..60 {
+                    let moves = engine.legal_moves();
+                    if moves.is_empty() {
+                        break;
+                    }
+                    // Null moves now and then, as the search plays them
+                    if rng.gen_range(0..10) == 0 && engine.make_null_move().is_ok() {
+                        continue;
+                    }
+                    engine.play(moves[rng.gen_range(0..moves.len())]).unwrap();
+
+                    let fen = engine.to_string();
+                    assert_eq!(
+                        engine.hash(),
+                        HASHER.compute_hash(engine.get_board(), engine.white_to_play()),
+                        "{fen}"
+                    );
+                    assert_eq!(engine.hash(), Engine::from_fen(&fen).unwrap().hash(), "{fen}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_fen_reads_back_the_positions_of_random_games() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(2001);
+        for _ in 0..20 {
+            let mut engine = Engine::from_fen(START_FEN).unwrap();
+            for _ in 0..80 {
+                let moves = engine.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                engine.play(moves[rng.gen_range(0..moves.len())]).unwrap();
+
+                let fen = engine.to_string();
+                let read_back = Engine::from_fen(&fen).unwrap();
+                assert_eq!(read_back.to_string(), fen);
+                assert_eq!(read_back.hash(), engine.hash(), "{fen}");
+                assert_eq!(read_back.is_king_checked(), engine.is_king_checked(), "{fen}");
+            }
+        }
+    }
+
+    #[test]
+    fn from_fen_rejects_malformed_positions() {
+        // A pawn check is seen on load
+        let engine = Engine::from_fen("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1").unwrap();
+        assert!(engine.is_king_checked());
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0",
+            // Too many moves for the ply counter
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 4294967295",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0",
+        ] {
+            assert!(Engine::from_fen(fen).is_err(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn legal_moves_are_the_moves_of_the_generated_positions() {
+        for fen in [
+            START_FEN,
+            KIWIPETE,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let engine = Engine::from_fen(fen).unwrap();
+            let children = engine.generate_moves_with_engine_state().unwrap();
+            // The position and every position one move away, same moves in the same order
+            for position in std::iter::once(&engine).chain(children.iter().map(|c| &c.engine)) {
+                let generated: Vec<PlayerMove> = position
+                    .generate_moves_with_engine_state()
+                    .unwrap()
+                    .iter()
+                    .map(|child| child.player_move)
+                    .collect();
+                assert_eq!(position.legal_moves(), generated, "{}", position.to_string());
+            }
+        }
+
+        // Each promotion piece is a move of its own
+        let engine = Engine::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotions = engine
+            .legal_moves()
+            .into_iter()
+            .filter(|chess_move| matches!(chess_move, PlayerMove::Promotion(_)))
+            .count();
+        assert_eq!(promotions, 4);
+    }
+
+    #[test]
+    fn san_round_trips_for_every_legal_move() {
+        let mut written = Vec::new();
+        for fen in [
+            KIWIPETE,
+            // Both castlings of each side
+            "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+            "r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1",
+            // Rooks told apart by their rank
+            "R7/8/7k/8/8/8/8/R3K3 w - - 0 1",
+            // Queens told apart by their file, their rank or both
+            "7k/8/8/8/Q1Q5/8/Q7/4K3 w - - 0 1",
+            // Promotions, with and without capture
+            "3n3k/4P3/8/8/8/8/8/4K3 w - - 0 1",
+            // Back rank mate
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            for chess_move in position.legal_moves() {
+                let san = position.move_to_san(chess_move);
+                assert_eq!(position.get_move_by_san(&san).ok(), Some(chess_move), "{san} in {fen}");
+                written.push(san);
+            }
+        }
+
+        for san in [
+            "O-O", "O-O-O", "R1a4", "R8a4", "Qab4", "Q2c2", "Qa4b3", "exd8=Q+", "e8=N", "Qg8#",
+            "Ra8#",
+        ] {
+            assert!(written.iter().any(|written| written == san), "{san}");
+        }
+    }
+
+    #[test]
+    fn moves_by_piece_are_individually_legal() {
+        for fen in [
+            KIWIPETE,
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1",
+        ] {
+            let engine = Engine::from_fen(fen).unwrap();
+            let moves = engine.get_all_moves_by_piece();
+
+            for &(piece, chess_move) in &moves {
+                let (from, to) = match chess_move {
+                    PlayerMove::Normal(normal_move) => normal_move.squares(),
+                    PlayerMove::Promotion(promotion_move) => promotion_move.squares(),
+                    PlayerMove::Castling(_) => panic!("castling isn't expected: {fen}"),
+                };
+                assert_eq!(to.count_ones(), 1, "{fen}");
+                let square_index = from.trailing_zeros() as u8;
+                let moved_piece = engine.get_board().piece_at_square_index(square_index);
+                assert_eq!(moved_piece.map(|(_, piece)| piece), Some(piece), "{fen}");
+                engine.clone().play(chess_move).unwrap();
+            }
+
+            // Everything but castling, one entry per move
+            let non_castling = engine
+                .generate_moves_with_engine_state()
+                .unwrap()
+                .iter()
+                .filter(|child| !matches!(child.player_move, PlayerMove::Castling(_)))
+                .count();
+            assert_eq!(moves.len(), non_castling, "{fen}");
+        }
+    }
+
+    #[test]
+    fn moves_by_piece_expand_promotions() {
+        // b7 pushes to b8 or takes on a8 and c8, each with four pieces
+        let engine = Engine::from_fen("n1n5/1P2k3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let pawn_moves: Vec<PlayerMove> = engine
+            .get_all_moves_by_piece()
+            .into_iter()
+            .filter(|&(piece, _)| piece == Piece::Pawn)
+            .map(|(_, chess_move)| chess_move)
+            .collect();
+
+        assert_eq!(pawn_moves.len(), 12);
+        for target_square in [1 << 56, 1 << 57, 1 << 58] {
+            for promotion_piece in PROMOTE_PIECE {
+                let promotion = PromotionMove::new(1 << 49, target_square, promotion_piece);
+                assert!(pawn_moves.contains(&PlayerMove::Promotion(promotion)));
+            }
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn is_king_checked_matches_the_deprecated_name() {
+        for (fen, checked) in [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", false),
+            ("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", true),
+            ("4k3/8/8/8/8/8/8/4K2R b - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1", true),
+        ] {
+            let engine = Engine::from_fen(fen).unwrap();
+            assert_eq!(engine.is_king_checked(), checked, "{fen}");
+            assert_eq!(engine.is_current_king_checked(), checked, "{fen}");
+        }
+
+        // Rh1-h8 checks the black king
+        let mut engine = Engine::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+        engine.play(PlayerMove::Normal(NormalMove::new(1 << 7, 1 << 63))).unwrap();
+        assert!(engine.is_king_checked());
+    }
+
+    #[test]
+    fn move_info_reports_the_en_passant_capture_square() {
+        // exf6 e.p. lands on f6 and takes the pawn on f5
+        let mut engine =
+            Engine::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+                .unwrap();
+        let move_info = engine
+            .play_move_info(PlayerMove::Normal(NormalMove::new(1 << 36, 1 << 45)))
+            .unwrap();
+
+        assert_eq!(move_info.color, Color::White);
+        assert_eq!(move_info.piece, Piece::Pawn);
+        assert_eq!((move_info.from, move_info.to), (1 << 36, 1 << 45));
+        assert_eq!(move_info.captured, Some((Piece::Pawn, 1 << 37)));
+        assert_eq!(move_info.rook_move, None);
+        assert_eq!(move_info.promotion, None);
+    }
+
+    #[test]
+    fn move_info_reports_the_castling_rook_squares() {
+        let mut engine = Engine::from_fen(KIWIPETE).unwrap();
+        let move_info = engine
+            .play_move_info(PlayerMove::Castling(CastlingMove::Short))
+            .unwrap();
+        assert_eq!(move_info.piece, Piece::King);
+        assert_eq!((move_info.from, move_info.to), (1 << 4, 1 << 6));
+        assert_eq!(move_info.rook_move, Some((1 << 7, 1 << 5)));
+        assert_eq!(move_info.captured, None);
+
+        let move_info = engine
+            .play_move_info(PlayerMove::Castling(CastlingMove::Long))
+            .unwrap();
+        assert_eq!(move_info.color, Color::Black);
+        assert_eq!((move_info.from, move_info.to), (1 << 60, 1 << 58));
+        assert_eq!(move_info.rook_move, Some((1 << 56, 1 << 59)));
+    }
+
+    #[test]
+    fn play_matches_board_apply_move_for_every_move_kind() {
+        let normal = |from: u32, to: u32| PlayerMove::Normal(NormalMove::new(1 << from, 1 << to));
+        let promotion = |from: u32, to: u32, piece| {
+            PlayerMove::Promotion(PromotionMove::new(1 << from, 1 << to, piece))
+        };
+        let cases = [
+            // Quiet move, capture and double push
+            (KIWIPETE, normal(12, 19), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2NB1Q1p/PPPB1PPP/R3K2R b KQkq - 1 1"),
+            (KIWIPETE, normal(21, 45), "r3k2r/p1ppqpb1/bn2pQp1/3PN3/1p2P3/2N4p/PPPBBPPP/R3K2R b KQkq - 0 1"),
+            (KIWIPETE, normal(8, 24), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1"),
+            // En passant
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                normal(36, 45),
+                "rnbqkbnr/ppp1p1pp/5P2/3p4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3",
+            ),
+            // Castling on both sides
+            (KIWIPETE, PlayerMove::Castling(CastlingMove::Short), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R4RK1 b kq - 1 1"),
+            (KIWIPETE, PlayerMove::Castling(CastlingMove::Long), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/2KR3R b kq - 1 1"),
+            // Promotion, with and without a capture
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1", promotion(49, 57, Piece::Queen), "nQn5/P1Pk4/8/8/8/8/4Kppp/5N1N b - - 0 1"),
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1", promotion(49, 56, Piece::Knight), "N1n5/P1Pk4/8/8/8/8/4Kppp/5N1N b - - 0 1"),
+        ];
+
+        for (fen, chess_move, expected) in cases {
+            let mut engine = Engine::from_fen(fen).unwrap();
+            let color = get_color(engine.white_to_play());
+            let (board, _) = engine.get_board().apply_move(&chess_move, color).unwrap();
+
+            engine.play(chess_move).unwrap();
+            assert_eq!(engine.to_string(), expected, "{fen}");
+            assert_eq!(board.to_string(), engine.get_board().to_string(), "{fen}");
+            assert_eq!(board.white.en_passant, engine.get_board().white.en_passant, "{fen}");
+        }
+    }
+
+    #[test]
+    fn null_move_changes_the_hash_by_the_side_and_en_passant_keys() {
+        let side_key = HASHER.toggle_side(0);
+
+        let mut engine = Engine::from_fen(KIWIPETE).unwrap();
+        let before = engine.zobrist_hash();
+        engine.make_null_move().unwrap();
+        assert_eq!(before ^ engine.zobrist_hash(), side_key);
+
+        // exf6 e.p. is possible, the en passant key goes away too
+        let mut engine =
+            Engine::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+                .unwrap();
+        let en_passant_key = HASHER.clear_en_passant_hash(0, 1 << 45);
+        let before = engine.zobrist_hash();
+        engine.make_null_move().unwrap();
+        assert_eq!(before ^ engine.zobrist_hash(), side_key ^ en_passant_key);
+        assert_eq!(engine.zobrist_hash(), HASHER.compute_hash(engine.get_board(), false));
+    }
+
+    #[test]
+    fn start_position_attack_maps() {
+        let engine = Engine::new();
+
+        // The first three ranks, but the corners where the rooks stand
+        assert_eq!(engine.attack_map(Color::White), 0x0000_0000_00FF_FF7E);
+        assert_eq!(engine.attack_map(Color::Black), 0x7EFF_FF00_0000_0000);
+
+        // c3 and f3 are attacked by two pawns and a knight, d3 by two pawns
+        let control = engine.control_map();
+        assert_eq!((control[18], control[19], control[21]), (3, 2, 3));
+        assert_eq!((control[42], control[43], control[45]), (-3, -2, -3));
+        assert_eq!(control[28], 0);
+    }
+
+    #[test]
+    fn first_legal_move_is_none_only_at_mate_or_stalemate() {
+        for (fen, has_move) in [
+            // Fool's mate and a stalemate
+            ("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", false),
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", false),
+            // In check with an escape, a castling position and the start position
+            ("rnb1kbnr/pppp1ppp/8/4p3/7q/5P2/PPPPP1PP/RNBQKBNR w KQkq - 1 3", true),
+            ("4k3/8/8/8/8/8/3PPPPP/3QK2R w K - 0 1", true),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", true),
+        ] {
+            let engine = Engine::from_fen(fen).unwrap();
+            let first_move = engine.first_legal_move();
+            assert_eq!(first_move.is_some(), has_move, "{fen}");
+            if let Some(chess_move) = first_move {
+                engine.clone().play(chess_move).unwrap();
+            }
+        }
     }
 }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use super::player_move::PlayerMove;
+
+/// Operations of an EPD record, the part following the position
+///
+/// An EPD record is a FEN without the move counters, followed by operations
+/// such as `bm Qg6; id "WAC.001";`. See `Engine::from_epd`.
+#[derive(Debug, Clone, Default)]
+pub struct EpdOps {
+    /// Operands of every operation by opcode, as written (quotes removed)
+    pub operations: HashMap<String, String>,
+    /// Best moves (`bm`), any of them solves the position
+    pub best_moves: Vec<PlayerMove>,
+    /// Moves to avoid (`am`)
+    pub avoid_moves: Vec<PlayerMove>,
+}
+
+/// Why an EPD record can't be read, see `Engine::from_epd`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EpdError {
+    /// Less than the four position fields
+    MissingFields,
+    /// The position fields don't make a valid position
+    InvalidPosition,
+    /// The operands of an operation can't be read: a `bm` / `am` move that
+    /// isn't legal in the position, or an `hmvc` / `fmvn` that isn't a number
+    InvalidOperation { opcode: String, operands: String },
+}
+
+impl EpdOps {
+    /// Returns the operands of an operation, `None` if the record doesn't have it
+    pub fn get(&self, opcode: &str) -> Option<&str> {
+        self.operations.get(opcode).map(String::as_str)
+    }
+
+    /// Returns the name of the record (`id` operation)
+    pub fn id(&self) -> Option<&str> {
+        self.get("id")
+    }
+
+    /// Whether a move solves the position: one of the best moves when there
+    /// are some, and none of the moves to avoid
+    pub fn is_solved_by(&self, chess_move: PlayerMove) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&chess_move))
+            && !self.avoid_moves.contains(&chess_move)
+    }
+}
+
+/// Splits an EPD record into its four position fields and its operations
+///
+/// # Returns
+/// `None` if there are less than four fields
+pub(crate) fn split_epd(epd: &str) -> Option<([&str; 4], &str)> {
+    let mut fields = [""; 4];
+    let mut rest = epd.trim_start();
+    for field in fields.iter_mut() {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        *field = &rest[..end];
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest))
+}
+
+/// Parses the operations of an EPD record into `(opcode, operands)` pairs
+///
+/// Operations end with a `;`, except the last one where it can be omitted.
+/// A `;` inside a quoted operand doesn't end the operation.
+pub(crate) fn parse_operations(operations: &str) -> Vec<(String, String)> {
+    let mut parsed = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in operations.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ';' if !in_quotes => {
+                parsed.extend(parse_operation(&current));
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    parsed.extend(parse_operation(&current));
+
+    parsed
+}
+
+/// Parses a single operation, `None` if it's empty
+fn parse_operation(operation: &str) -> Option<(String, String)> {
+    let operation = operation.trim();
+    if operation.is_empty() {
+        return None;
+    }
+
+    let (opcode, operands) = match operation.find(char::is_whitespace) {
+        Some(end) => (&operation[..end], operation[end..].trim()),
+        None => (operation, ""),
+    };
+    let operands = operands
+        .strip_prefix('"')
+        .and_then(|operands| operands.strip_suffix('"'))
+        .unwrap_or(operands);
+
+    Some((opcode.to_string(), operands.to_string()))
+}
+
+/// Removes the annotations a test suite can add to a move in SAN (`Qg6!`)
+pub(crate) fn clean_epd_san(san: &str) -> &str {
+    san.trim_end_matches(['!', '?'])
+}
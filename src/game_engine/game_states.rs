@@ -1,3 +1,5 @@
+use crate::pieces::Color;
+
 pub enum RunningState {
     Normal,
     WhiteChecked,
@@ -9,3 +11,97 @@ pub enum GameState {
     Mate,
     Stale,
 }
+
+/// Why a game is (or can be declared) drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    /// Same position for the third time, the draw can be claimed
+    ThreefoldRepetition,
+    /// Same position for the fifth time, the game is drawn
+    FivefoldRepetition,
+    /// 50 moves without pawn move or capture, the draw can be claimed
+    FiftyMoveRule,
+    /// 75 moves without pawn move or capture, the game is drawn
+    SeventyFiveMoveRule,
+    /// No sequence of legal moves can lead to a mate
+    InsufficientMaterial,
+    /// The side to move isn't checked but has no legal move
+    Stalemate,
+}
+
+/// Why a node of the search tree has no continuation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalReason {
+    /// The side to move is mated
+    Checkmate {
+        winner: Color,
+    },
+    Stalemate,
+    InsufficientMaterial,
+    /// 50 moves without pawn move or capture
+    FiftyMove,
+    /// The position occurred three times
+    Repetition,
+}
+
+impl TerminalReason {
+    /// Suffix annotating the last move of a line, `#` for a mate and `=` for a draw
+    pub fn annotation(&self) -> &'static str {
+        match self {
+            TerminalReason::Checkmate { .. } => "#",
+            _ => "=",
+        }
+    }
+
+    pub fn is_draw(&self) -> bool {
+        !matches!(self, TerminalReason::Checkmate { .. })
+    }
+}
+
+impl From<DrawReason> for TerminalReason {
+    fn from(reason: DrawReason) -> Self {
+        match reason {
+            DrawReason::ThreefoldRepetition | DrawReason::FivefoldRepetition => {
+                TerminalReason::Repetition
+            }
+            DrawReason::FiftyMoveRule | DrawReason::SeventyFiveMoveRule => {
+                TerminalReason::FiftyMove
+            }
+            DrawReason::InsufficientMaterial => TerminalReason::InsufficientMaterial,
+            DrawReason::Stalemate => TerminalReason::Stalemate,
+        }
+    }
+}
+
+/// State of a game as a front-end needs it to end the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    /// The side to move is mated, holds the winner
+    Checkmate(Color),
+    Stalemate,
+    /// 50 moves without pawn move or capture
+    FiftyMoveDraw,
+    /// The position occurred three times
+    ThreefoldDraw,
+    InsufficientMaterial,
+}
+
+impl GameStatus {
+    pub fn is_game_over(&self) -> bool {
+        !matches!(self, GameStatus::Ongoing)
+    }
+}
+
+impl From<Option<TerminalReason>> for GameStatus {
+    fn from(reason: Option<TerminalReason>) -> Self {
+        match reason {
+            None => GameStatus::Ongoing,
+            Some(TerminalReason::Checkmate { winner }) => GameStatus::Checkmate(winner),
+            Some(TerminalReason::Stalemate) => GameStatus::Stalemate,
+            Some(TerminalReason::FiftyMove) => GameStatus::FiftyMoveDraw,
+            Some(TerminalReason::Repetition) => GameStatus::ThreefoldDraw,
+            Some(TerminalReason::InsufficientMaterial) => GameStatus::InsufficientMaterial,
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Pins and checks of the side to play, to tell which moves are legal
+//! without playing them
+//!
+//! A piece other than the king can only leave its king in check by leaving
+//! the line of a pin, or by not answering a check. Both are known from the
+//! position alone, so the moves of those pieces are filtered with two masks
+//! instead of being simulated one by one. King moves and en passant (which
+//! removes two pieces from a rank) are still simulated.
+
+use crate::boards::Board;
+use crate::pieces::static_positions::{
+    EAST, NORTH, NORTH_EAST, NORTH_WEST, RAYS, SOUTH, SOUTH_EAST, SOUTH_WEST, WEST,
+};
+use crate::pieces::Color;
+
+const ORTHOGONAL_DIRECTIONS: [usize; 4] = [NORTH, EAST, SOUTH, WEST];
+const DIAGONAL_DIRECTIONS: [usize; 4] = [NORTH_EAST, NORTH_WEST, SOUTH_WEST, SOUTH_EAST];
+
+/// Pins and checks of the king of the side to play
+pub(crate) struct LegalityMasks {
+    /// Squares a piece other than the king can move to: any square out of
+    /// check, the checker and the squares between it and the king in a
+    /// single check, none in a double check
+    check_mask: u64,
+    /// Pieces pinned to their king
+    pinned: u64,
+    /// For each pinned piece, the squares from the king to the pinning
+    /// slider (included) it can't leave
+    pin_lines: [u64; 64],
+}
+
+impl LegalityMasks {
+    /// Computes the pins and checks of the king of `color`
+    pub(crate) fn new(board: &Board, color: Color) -> Self {
+        let (own, opponent, opponent_color) = match color {
+            Color::White => (&board.white, &board.black, Color::Black),
+            Color::Black => (&board.black, &board.white, Color::White),
+        };
+        let king_index = own.king.trailing_zeros() as usize;
+        let occupancy = board.bitboard();
+
+        let mut masks = LegalityMasks {
+            check_mask: !0,
+            pinned: 0,
+            pin_lines: [0; 64],
+        };
+
+        // Without a king (test positions), nothing is pinned nor checked
+        if own.king == 0 {
+            return masks;
+        }
+
+        let checkers = board.attackers_to(own.king, opponent_color);
+        masks.check_mask = match checkers.count_ones() {
+            0 => !0,
+            1 => checkers | between(king_index, checkers),
+            _ => 0,
+        };
+
+        let orthogonal_sliders = opponent.rook | opponent.queen;
+        let diagonal_sliders = opponent.bishop | opponent.queen;
+        let directions = ORTHOGONAL_DIRECTIONS
+            .iter()
+            .map(|&direction| (direction, orthogonal_sliders))
+            .chain(
+                DIAGONAL_DIRECTIONS
+                    .iter()
+                    .map(|&direction| (direction, diagonal_sliders)),
+            );
+
+        for (direction, sliders) in directions {
+            // A pin is an own piece first on the ray, then a slider moving along it
+            let ray = RAYS[direction][king_index];
+            let Some(first) = nearest_blocker(ray & occupancy, direction) else {
+                continue;
+            };
+            if (1u64 << first) & own.bitboard() == 0 {
+                continue;
+            }
+            let behind = RAYS[direction][first] & occupancy;
+            let Some(second) = nearest_blocker(behind, direction) else {
+                continue;
+            };
+            if (1u64 << second) & sliders != 0 {
+                masks.pinned |= 1u64 << first;
+                masks.pin_lines[first] = ray & !RAYS[direction][second];
+            }
+        }
+
+        masks
+    }
+
+    /// Keeps the targets of a piece other than the king that don't leave
+    /// its king in check
+    ///
+    /// # Arguments
+    /// * `current_square` - Square of the piece
+    /// * `targets` - Its pseudo-legal targets, without en passant
+    pub(crate) fn legal_targets(&self, current_square: u64, targets: u64) -> u64 {
+        let mut legal_targets = targets & self.check_mask;
+        if current_square & self.pinned != 0 {
+            legal_targets &= self.pin_lines[current_square.trailing_zeros() as usize];
+        }
+        legal_targets
+    }
+}
+
+/// Index of the blocker closest to the origin of a ray going in `direction`
+fn nearest_blocker(blockers: u64, direction: usize) -> Option<usize> {
+    if blockers == 0 {
+        None
+    } else if direction < SOUTH {
+        // Rays going up meet the lowest square first
+        Some(blockers.trailing_zeros() as usize)
+    } else {
+        Some(63 - blockers.leading_zeros() as usize)
+    }
+}
+
+/// Squares strictly between the king and a checker on one of its rays,
+/// nothing for a knight or a pawn
+fn between(king_index: usize, checker: u64) -> u64 {
+    let checker_index = checker.trailing_zeros() as usize;
+    for direction in ORTHOGONAL_DIRECTIONS.into_iter().chain(DIAGONAL_DIRECTIONS) {
+        let ray = RAYS[direction][king_index];
+        if ray & checker != 0 {
+            return ray & !checker & !RAYS[direction][checker_index];
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::engine::Engine;
+
+    fn square(name: &str) -> u64 {
+        let bytes = name.as_bytes();
+        1u64 << ((bytes[1] - b'1') * 8 + bytes[0] - b'a')
+    }
+
+    fn squares(names: &[&str]) -> u64 {
+        names
+            .iter()
+            .fold(0, |bitboard, name| bitboard | square(name))
+    }
+
+    fn masks(fen: &str) -> LegalityMasks {
+        let engine = Engine::from_fen(fen).unwrap();
+        let color = if engine.white_to_play() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        LegalityMasks::new(engine.get_board(), color)
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        // The rook slides along the pin up to the pinner, the bishop can't move
+        let rook_targets = squares(&["a2", "d2", "h2", "e3", "e5", "e8"]);
+        let masks = self::masks("4r1k1/8/8/8/8/8/4R3/4K3 w - - 0 1");
+        assert_eq!(
+            masks.legal_targets(square("e2"), rook_targets),
+            squares(&["e3", "e5", "e8"])
+        );
+        let bishop_targets = squares(&["d1", "f1", "d3", "a6", "f3", "h5"]);
+        let masks = self::masks("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1");
+        assert_eq!(masks.legal_targets(square("e2"), bishop_targets), 0);
+
+        // Diagonal pin on black, a second piece on the line breaks it
+        let bishop_targets = squares(&["c8", "c6", "b5", "e6", "h3"]);
+        let masks = self::masks("4k3/3b4/8/1B6/8/8/8/4K3 b - - 0 1");
+        assert_eq!(
+            masks.legal_targets(square("d7"), bishop_targets),
+            squares(&["c6", "b5"])
+        );
+        let masks = self::masks("4k3/3b4/2p5/1B6/8/8/8/4K3 b - - 0 1");
+        assert_eq!(
+            masks.legal_targets(square("d7"), bishop_targets),
+            bishop_targets
+        );
+    }
+
+    #[test]
+    fn checks_restrict_the_other_pieces() {
+        // Single check: capture the checker or block
+        let masks = self::masks("4k3/8/8/8/8/8/8/r3K2R w - - 0 1");
+        assert_eq!(
+            masks.legal_targets(square("h1"), !0),
+            squares(&["a1", "b1", "c1", "d1"])
+        );
+
+        // A knight can only be captured
+        let masks = self::masks("4k3/8/8/8/8/3n4/8/4K2R w - - 0 1");
+        assert_eq!(masks.legal_targets(square("h1"), !0), square("d3"));
+
+        // Double check: only the king moves
+        let masks = self::masks("4k3/8/8/8/8/3n4/8/r3K2R w - - 0 1");
+        assert_eq!(masks.legal_targets(square("h1"), !0), 0);
+    }
+}
@@ -1,9 +1,15 @@
 pub mod debug;
 pub mod engine;
+pub mod epd;
 pub mod game_states;
 pub mod move_evaluation_context;
+pub mod move_info;
+mod legality;
 mod move_parsing;
 mod move_piece_output;
+pub mod packed_move;
+pub mod perft;
+pub mod pgn;
 pub mod move_results;
 pub mod player_move;
 pub mod utility;
@@ -0,0 +1,24 @@
+use super::move_results::CorrectMoveResults;
+
+use crate::pieces::{Color, Piece};
+
+/// Everything that changed on the board for a move that was played
+///
+/// All squares are bitboards with a single bit set.
+#[derive(Debug, Clone, Copy)]
+pub struct MoveInfo {
+    /// Color of the player who moved
+    pub color: Color,
+    /// Piece that moved (the king for castling, the pawn for a promotion)
+    pub piece: Piece,
+    pub from: u64,
+    pub to: u64,
+    /// Captured piece and the square it was on, which differs from `to` for en passant
+    pub captured: Option<(Piece, u64)>,
+    /// Rook squares `(from, to)` when the move is castling
+    pub rook_move: Option<(u64, u64)>,
+    /// Piece the pawn was promoted to
+    pub promotion: Option<Piece>,
+    /// What `Engine::play` returned, always `Ok` from `Board::apply_move` that doesn't know the rules
+    pub result: CorrectMoveResults,
+}
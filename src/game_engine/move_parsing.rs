@@ -1,5 +1,5 @@
-use super::player_move::{CastlingMove, NormalMove, PlayerMove, PromotionMove};
-use crate::{boards::Board, pieces::{Color, Piece}};
+use super::player_move::{CastlingMove, PlayerMove};
+use crate::{boards::{Board, CastlingRights}, pieces::{Color, Piece}};
 
 pub fn parse_str_into_square(target_file: char, target_rank: char) -> Result<u64, ()> {
     // Validate the input
@@ -43,12 +43,9 @@ pub fn square_to_rank(pos: u64) -> char {
 }
 
 pub fn match_piece_by_char(c: char) -> Piece {
-    match c {
-        'K' => Piece::King,
-        'Q' => Piece::Queen,
-        'R' => Piece::Rook,
-        'B' => Piece::Bishop,
-        'N' => Piece::Knight,
+    // In SAN only uppercase letters name a piece, anything else is a pawn move
+    match Piece::from_char(c) {
+        Some((Color::White, piece)) => piece,
         _ => Piece::Pawn,
     }
 }
@@ -56,7 +53,7 @@ pub fn match_piece_by_char(c: char) -> Piece {
 pub fn matches_move(
     mv_piece: Piece,
     mv_current_square: u64,
-    mv_target_squares: u64,
+    mv_target_square: u64,
     piece: Piece,
     target_square: u64,
     from_file: Option<char>,
@@ -66,7 +63,7 @@ pub fn matches_move(
         return false;
     }
     // Match target square
-    if target_square & mv_target_squares == 0 {
+    if target_square != mv_target_square {
         return false;
     }
 
@@ -88,7 +85,8 @@ pub fn matches_move(
 }
 
 pub fn parse_castling(input: &str) -> Option<PlayerMove> {
-    match input.to_uppercase().as_str() {
+    // Castling can give check too
+    match input.trim_end_matches(['+', '#']).to_uppercase().as_str() {
         "O-O" => Some(PlayerMove::Castling(CastlingMove::Short)),
         "O-O-O" => Some(PlayerMove::Castling(CastlingMove::Long)),
         _ => None,
@@ -123,6 +121,7 @@ pub fn filter_possible_moves(
     possible_moves: Vec<(Piece, PlayerMove)>,
     piece: Piece,
     target_square: u64,
+    promotion_piece_opt: Option<Piece>,
     from_file: Option<char>,
     from_rank: Option<char>,
 ) -> Vec<(Piece, PlayerMove)> {
@@ -133,70 +132,36 @@ pub fn filter_possible_moves(
             match pm {
                 PlayerMove::Castling(_) => false,
                 PlayerMove::Normal(mv) => {
-                    let (mv_current_square, mv_target_squares) = mv.squares();
-                    matches_move(
-                        *pc,
-                        mv_current_square,
-                        mv_target_squares,
-                        piece,
-                        target_square,
-                        from_file,
-                        from_rank,
-                    )
+                    let (mv_current_square, mv_target_square) = mv.squares();
+                    promotion_piece_opt.is_none()
+                        && matches_move(
+                            *pc,
+                            mv_current_square,
+                            mv_target_square,
+                            piece,
+                            target_square,
+                            from_file,
+                            from_rank,
+                        )
                 }
                 PlayerMove::Promotion(mv) => {
-                    let (mv_current_square, mv_target_squares) = mv.squares();
-                    matches_move(
-                        *pc,
-                        mv_current_square,
-                        mv_target_squares,
-                        piece,
-                        target_square,
-                        from_file,
-                        from_rank,
-                    )
+                    let (mv_current_square, mv_target_square) = mv.squares();
+                    promotion_piece_opt == Some(mv.promotion_piece())
+                        && matches_move(
+                            *pc,
+                            mv_current_square,
+                            mv_target_square,
+                            piece,
+                            target_square,
+                            from_file,
+                            from_rank,
+                        )
                 }
             }
         })
         .collect()
 }
 
-pub fn create_final_move(
-    player_move: PlayerMove,
-    promotion_piece_opt: Option<Piece>,
-    target_square: u64,
-) -> Result<PlayerMove, ()> {
-    let current_square = match player_move {
-        PlayerMove::Promotion(mv) => mv.squares().0,
-        PlayerMove::Normal(mv) => mv.squares().0,
-        _ => { 
-            return Err(());
-        }
-    };
-    match promotion_piece_opt {
-        Some(promotion_piece) => Ok(PlayerMove::Promotion(
-            PromotionMove::new(current_square, target_square, promotion_piece),
-        )),
-        None => Ok(PlayerMove::Normal(NormalMove::new(current_square, target_square)))
-    }
-}
-
-// Helper function to convert a piece to its FEN character
-pub fn piece_to_char(color: Color, piece: Piece) -> char {
-    let piece_char = match piece {
-        Piece::Pawn => 'P',
-        Piece::Knight => 'N',
-        Piece::Bishop => 'B',
-        Piece::Rook => 'R',
-        Piece::Queen => 'Q',
-        Piece::King => 'K'
-    };
-    match color {
-        Color::Black => piece_char.to_ascii_lowercase(),
-        Color::White => piece_char
-    }
-}
-
 pub fn fen_board_position(board: &Board) -> String {
     // init an empty string for the fen
     let mut board_position = String::new();
@@ -217,7 +182,7 @@ pub fn fen_board_position(board: &Board) -> String {
                         board_position.push_str(&empty_squares.to_string());
                         empty_squares = 0;
                     }
-                    board_position.push(piece_to_char(color, piece));
+                    board_position.push(piece.to_char(color));
                 }
                 None => {
                     empty_squares += 1;
@@ -265,3 +230,90 @@ pub fn fen_en_passant(board: &Board) -> String {
 
     en_passant
 }
+
+pub fn parse_fen_board_position(board_position: &str) -> Result<Board, ()> {
+    let mut board = Board::empty();
+    let ranks: Vec<&str> = board_position.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(());
+    }
+
+    // Ranks are written from 8 to 1
+    for (rank_index, rank_str) in ranks.iter().enumerate() {
+        let rank = 7 - rank_index;
+        let mut file = 0;
+
+        for c in rank_str.chars() {
+            if let Some(empty_squares) = c.to_digit(10) {
+                if !(1..=8).contains(&empty_squares) {
+                    return Err(());
+                }
+                file += empty_squares as usize;
+            } else {
+                let (color, piece) = Piece::from_char(c).ok_or(())?;
+                if file >= 8 {
+                    return Err(());
+                }
+                let square = 1u64 << (rank * 8 + file);
+                let bitboard = board.get_bitboard_by_type(piece, color);
+                board.set_bitboard_by_type(piece, color, bitboard | square);
+                file += 1;
+            }
+        }
+
+        if file != 8 {
+            return Err(());
+        }
+    }
+
+    // The engine needs exactly one king per side
+    if board.white.king.count_ones() != 1 || board.black.king.count_ones() != 1 {
+        return Err(());
+    }
+
+    Ok(board)
+}
+
+pub fn parse_fen_castling(castling_rights: &str, board: &mut Board) -> Result<(), ()> {
+    let (mut white_short, mut white_long, mut black_short, mut black_long) =
+        (false, false, false, false);
+
+    if castling_rights != "-" {
+        for c in castling_rights.chars() {
+            match c {
+                'K' => white_short = true,
+                'Q' => white_long = true,
+                'k' => black_short = true,
+                'q' => black_long = true,
+                _ => return Err(()),
+            }
+        }
+    }
+
+    board.white.castling_rights = CastlingRights::new_with_rules(white_short, white_long);
+    board.black.castling_rights = CastlingRights::new_with_rules(black_short, black_long);
+
+    // Drop the rights the position can't have (king or rook not on its square)
+    board.update_castling_rights();
+    Ok(())
+}
+
+pub fn parse_fen_en_passant(en_passant: &str, white_to_play: bool, board: &mut Board) -> Result<(), ()> {
+    if en_passant == "-" {
+        return Ok(());
+    }
+
+    let chars: Vec<char> = en_passant.chars().collect();
+    if chars.len() != 2 {
+        return Err(());
+    }
+    let square = parse_str_into_square(chars[0], chars[1])?;
+
+    // The square belongs to the player who just pushed the pawn
+    match (chars[1], white_to_play) {
+        ('3', false) => board.white.en_passant = square,
+        ('6', true) => board.black.en_passant = square,
+        _ => return Err(()),
+    }
+    Ok(())
+}
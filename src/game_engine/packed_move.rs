@@ -0,0 +1,169 @@
+use super::player_move::{CastlingMove, NormalMove, PlayerMove, PromotionMove};
+use crate::pieces::{Color, Piece};
+
+const SQUARE_MASK: u16 = 0b11_1111;
+const TO_SHIFT: u16 = 6;
+const FLAGS_SHIFT: u16 = 12;
+const SHORT_CASTLING_FLAG: u16 = 5;
+const LONG_CASTLING_FLAG: u16 = 6;
+
+/// Compact `u16` encoding of a `PlayerMove` (2 bytes instead of 24)
+///
+/// Layout:
+/// * bits 0..6   - source square index
+/// * bits 6..12  - target square index
+/// * bits 12..15 - flags: 0 = none, 1 = knight, 2 = bishop, 3 = rook and
+///   4 = queen promotion, 5 = short and 6 = long castling
+///
+/// Castling is stored as the king's two squares move with its flag, so a
+/// move can be decoded without the position it is played on.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    /// Packs a move played by `color`
+    pub fn from_player_move(player_move: PlayerMove, color: Color) -> Self {
+        match player_move {
+            PlayerMove::Normal(normal_move) => {
+                let (from, to) = normal_move.squares();
+                Self::pack(from, to, 0)
+            }
+            PlayerMove::Promotion(promotion_move) => {
+                let (from, to) = promotion_move.squares();
+                Self::pack(from, to, promotion_to_flags(promotion_move.promotion_piece()))
+            }
+            PlayerMove::Castling(castling_move) => {
+                let king_square = match color {
+                    Color::White => 4,
+                    Color::Black => 60,
+                };
+                let (target_square, flags) = match castling_move {
+                    CastlingMove::Short => (king_square + 2, SHORT_CASTLING_FLAG),
+                    CastlingMove::Long => (king_square - 2, LONG_CASTLING_FLAG),
+                };
+                Self::pack(1u64 << king_square, 1u64 << target_square, flags)
+            }
+        }
+    }
+
+    /// Unpacks the move
+    pub fn to_player_move(&self) -> PlayerMove {
+        let from_bit = 1u64 << self.from_square();
+        let to_bit = 1u64 << self.to_square();
+
+        match self.0 >> FLAGS_SHIFT {
+            SHORT_CASTLING_FLAG => PlayerMove::Castling(CastlingMove::Short),
+            LONG_CASTLING_FLAG => PlayerMove::Castling(CastlingMove::Long),
+            flags => match flags_to_promotion(flags) {
+                Some(piece) => PlayerMove::Promotion(PromotionMove::new(from_bit, to_bit, piece)),
+                None => PlayerMove::Normal(NormalMove::new(from_bit, to_bit)),
+            },
+        }
+    }
+
+    /// Index (0..64) of the source square
+    pub fn from_square(&self) -> u8 {
+        (self.0 & SQUARE_MASK) as u8
+    }
+
+    /// Index (0..64) of the target square
+    pub fn to_square(&self) -> u8 {
+        ((self.0 >> TO_SHIFT) & SQUARE_MASK) as u8
+    }
+
+    /// Raw encoded value
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    fn pack(from: u64, to: u64, flags: u16) -> Self {
+        let from = from.trailing_zeros() as u16;
+        let to = to.trailing_zeros() as u16;
+        PackedMove(from | (to << TO_SHIFT) | (flags << FLAGS_SHIFT))
+    }
+}
+
+fn promotion_to_flags(piece: Piece) -> u16 {
+    match piece {
+        Piece::Knight => 1,
+        Piece::Bishop => 2,
+        Piece::Rook => 3,
+        Piece::Queen => 4,
+        _ => 0,
+    }
+}
+
+fn flags_to_promotion(flags: u16) -> Option<Piece> {
+    match flags {
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::engine::Engine;
+    use crate::game_engine::utility::get_color;
+
+    // Quiet moves, captures, en passant, castling on both sides for both
+    // colors and every promotion for both colors
+    const POSITIONS: [&str; 5] = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1",
+    ];
+
+    #[test]
+    fn round_trips_every_move_kind() {
+        let mut castlings = Vec::new();
+        let mut promotions = Vec::new();
+
+        for fen in POSITIONS {
+            let engine = Engine::from_fen(fen).unwrap();
+            let color = get_color(engine.white_to_play());
+            for chess_move in engine.legal_moves() {
+                let packed_move = PackedMove::from_player_move(chess_move, color);
+                assert_eq!(packed_move.to_player_move(), chess_move, "{fen}");
+
+                match chess_move {
+                    PlayerMove::Castling(castling_move) => castlings.push((color, castling_move)),
+                    PlayerMove::Promotion(promotion_move) => {
+                        promotions.push((color, promotion_move.promotion_piece()))
+                    }
+                    PlayerMove::Normal(_) => {}
+                }
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            for castling_move in [CastlingMove::Short, CastlingMove::Long] {
+                assert!(castlings.contains(&(color, castling_move)));
+            }
+            for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+                assert!(promotions.contains(&(color, piece)));
+            }
+        }
+    }
+
+    #[test]
+    fn castling_keeps_the_king_squares() {
+        let packed_move =
+            PackedMove::from_player_move(PlayerMove::Castling(CastlingMove::Long), Color::Black);
+        assert_eq!(
+            (packed_move.from_square(), packed_move.to_square()),
+            (60, 58)
+        );
+    }
+
+    #[test]
+    fn is_smaller_than_player_move() {
+        assert_eq!(std::mem::size_of::<PackedMove>(), 2);
+        assert!(std::mem::size_of::<Option<PackedMove>>() < std::mem::size_of::<PlayerMove>());
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+/// Cache of perft results, indexed by the Zobrist hash of a position and
+/// the depth it was counted to
+///
+/// A table can be reused across calls of `Engine::perft_hashed`, even from
+/// different root positions.
+#[derive(Debug, Default)]
+pub struct PerftTable {
+    table: HashMap<(u64, usize), u64>,
+}
+
+impl PerftTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of leaves below the position `hash` at `depth`, if counted
+    pub fn get(&self, hash: u64, depth: usize) -> Option<u64> {
+        self.table.get(&(hash, depth)).copied()
+    }
+
+    pub fn insert(&mut self, hash: u64, depth: usize, nodes: u64) {
+        self.table.insert((hash, depth), nodes);
+    }
+
+    /// Number of cached positions
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+}
@@ -0,0 +1,514 @@
+use super::engine::Engine;
+use super::move_results::{IncorrectMoveResults, MoveResult};
+use super::player_move::PlayerMove;
+
+/// Lines of the exported movetext are wrapped at this width, as the PGN standard asks
+const PGN_LINE_WIDTH: usize = 80;
+
+/// Start position written in the `FEN` tag, games starting from it don't need one
+const STANDARD_START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Outcome of a game, as written at the end of the movetext
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgnResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// `*`, the game is unfinished or the result unknown
+    Unknown,
+}
+
+impl PgnResult {
+    /// Parses a result token (`1-0`, `0-1`, `1/2-1/2` or `*`)
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(PgnResult::WhiteWins),
+            "0-1" => Some(PgnResult::BlackWins),
+            "1/2-1/2" => Some(PgnResult::Draw),
+            "*" => Some(PgnResult::Unknown),
+            _ => None,
+        }
+    }
+
+    /// Returns the result token as written in a PGN
+    pub fn as_token(&self) -> &'static str {
+        match self {
+            PgnResult::WhiteWins => "1-0",
+            PgnResult::BlackWins => "0-1",
+            PgnResult::Draw => "1/2-1/2",
+            PgnResult::Unknown => "*",
+        }
+    }
+
+    /// Score of white (1 for a win, 0.5 for a draw, 0 for a loss), `None` if unknown
+    pub fn white_score(&self) -> Option<f32> {
+        match self {
+            PgnResult::WhiteWins => Some(1.),
+            PgnResult::BlackWins => Some(0.),
+            PgnResult::Draw => Some(0.5),
+            PgnResult::Unknown => None,
+        }
+    }
+}
+
+/// A single game read from a PGN file
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// Tag pairs (`[Event "..."]`) in the order they were written
+    pub tags: Vec<(String, String)>,
+    /// Moves of the main line in SAN, without move numbers, comments or variations
+    pub moves: Vec<String>,
+    pub result: PgnResult,
+}
+
+impl PgnGame {
+    /// Returns the value of a tag, if present
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the position the game starts from, given by the `FEN` tag if
+    /// present (puzzles, studies) and the standard starting position otherwise
+    ///
+    /// # Returns
+    /// `None` if the `FEN` tag is invalid
+    pub fn starting_position(&self) -> Option<Engine> {
+        match self.tag("FEN") {
+            Some(fen) => Engine::from_fen(fen).ok(),
+            None => Some(Engine::new()),
+        }
+    }
+
+    /// Replays the game from its starting position
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Engine>)` - The position before every move, followed by the final position
+    /// * `Err(IncorrectMoveResults)` - The `FEN` tag is invalid (`InvalidMove`) or a move couldn't be played
+    pub fn positions(&self) -> Result<Vec<Engine>, IncorrectMoveResults> {
+        let mut engine = self
+            .starting_position()
+            .ok_or(IncorrectMoveResults::InvalidMove)?;
+        let mut positions = Vec::with_capacity(self.moves.len() + 1);
+        positions.push(engine.clone());
+
+        for san in self.moves.iter() {
+            engine.play_san(san)?;
+            positions.push(engine.clone());
+        }
+
+        Ok(positions)
+    }
+}
+
+/// Tag pairs written by `GameRecord::to_pgn`, missing ones are written as `?`
+#[derive(Debug, Clone, Default)]
+pub struct PgnTags {
+    pub event: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    /// Result of a game that didn't end on the board (resignation, agreed draw, time).
+    /// A mate or a forced draw on the board takes precedence.
+    pub result: Option<PgnResult>,
+}
+
+/// A game being played, keeping its moves so it can be exported as PGN
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    start: Engine,
+    position: Engine,
+    moves: Vec<PlayerMove>,
+}
+
+impl GameRecord {
+    /// Starts recording a game from the standard starting position
+    pub fn new() -> Self {
+        Self::from_position(Engine::new())
+    }
+
+    /// Starts recording a game from any position
+    pub fn from_position(start: Engine) -> Self {
+        GameRecord {
+            position: start.clone(),
+            start,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Plays a move on the current position, the move is recorded only if it is legal
+    pub fn play(&mut self, chess_move: PlayerMove) -> MoveResult {
+        let result = self.position.play(chess_move)?;
+        self.moves.push(chess_move);
+        Ok(result)
+    }
+
+    /// Position the game started from
+    pub fn start(&self) -> &Engine {
+        &self.start
+    }
+
+    /// Current position
+    pub fn position(&self) -> &Engine {
+        &self.position
+    }
+
+    /// Moves played since the start, in order
+    pub fn moves(&self) -> &[PlayerMove] {
+        &self.moves
+    }
+
+    /// Writes the game as PGN: the seven tag roster, `SetUp` and `FEN` when the game
+    /// doesn't start from the standard position, then the numbered moves in SAN
+    ///
+    /// The output can be read back by `parse_pgn` or `Engine::play_pgn_str`.
+    pub fn to_pgn(&self, tags: &PgnTags) -> String {
+        let board_result = self.position.pgn_result();
+        let result = match board_result {
+            PgnResult::Unknown => tags.result.unwrap_or(PgnResult::Unknown),
+            _ => board_result,
+        };
+
+        let tag_value = |value: &Option<String>| value.clone().unwrap_or_else(|| "?".to_string());
+        let mut tag_pairs = vec![
+            ("Event", tag_value(&tags.event)),
+            ("Site", "?".to_string()),
+            ("Date", "????.??.??".to_string()),
+            ("Round", "?".to_string()),
+            ("White", tag_value(&tags.white)),
+            ("Black", tag_value(&tags.black)),
+            ("Result", result.as_token().to_string()),
+        ];
+        let start_fen = self.start.to_string();
+        if start_fen != STANDARD_START_FEN {
+            tag_pairs.push(("SetUp", "1".to_string()));
+            tag_pairs.push(("FEN", start_fen));
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tag_pairs {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+        }
+        pgn.push('\n');
+
+        // The movetext already ends with the result when the game ended on the board
+        let mut movetext = Engine::movetext_from(&self.start, &self.moves);
+        if board_result == PgnResult::Unknown {
+            if !movetext.is_empty() {
+                movetext.push(' ');
+            }
+            movetext.push_str(result.as_token());
+        }
+        pgn.push_str(&wrap_movetext(&movetext));
+        pgn.push('\n');
+        pgn
+    }
+}
+
+impl Default for GameRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits a movetext into lines of at most `PGN_LINE_WIDTH` characters, between tokens
+fn wrap_movetext(movetext: &str) -> String {
+    let mut wrapped = String::new();
+    let mut line_length = 0;
+
+    for token in movetext.split_whitespace() {
+        if line_length > 0 && line_length + 1 + token.len() > PGN_LINE_WIDTH {
+            wrapped.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            wrapped.push(' ');
+            line_length += 1;
+        }
+        wrapped.push_str(token);
+        line_length += token.len();
+    }
+
+    wrapped
+}
+
+/// Parses every game of a PGN string
+///
+/// Comments (`{...}` and `;`), variations, NAGs (`$1`) and move annotations (`!`, `?`)
+/// are skipped. A game ends with its result token, a game missing one is still
+/// returned with an `Unknown` result if it has moves.
+///
+/// # Arguments
+/// * `pgn` - Content of a PGN file, with one or several games
+pub fn parse_pgn(pgn: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut moves = Vec::new();
+    let mut variation_depth = 0;
+    let mut in_comment = false;
+
+    for line in pgn.lines() {
+        let line = line.trim();
+
+        // Tag pairs are only read between games
+        if !in_comment && variation_depth == 0 && line.starts_with('[') {
+            // The previous game had no result
+            if !moves.is_empty() {
+                games.push(PgnGame {
+                    tags: std::mem::take(&mut tags),
+                    moves: std::mem::take(&mut moves),
+                    result: PgnResult::Unknown,
+                });
+            }
+            if let Some(tag) = parse_tag(line) {
+                tags.push(tag);
+            }
+            continue;
+        }
+
+        for token in tokenize_movetext(line, &mut in_comment) {
+            match token {
+                "(" => variation_depth += 1,
+                ")" => variation_depth = variation_depth.max(1) - 1,
+                _ if variation_depth > 0 => {}
+                _ => {
+                    if let Some(result) = PgnResult::from_token(token) {
+                        games.push(PgnGame {
+                            tags: std::mem::take(&mut tags),
+                            moves: std::mem::take(&mut moves),
+                            result,
+                        });
+                    } else if let Some(san) = clean_san_token(token) {
+                        moves.push(san);
+                    }
+                }
+            }
+        }
+    }
+
+    if !moves.is_empty() {
+        games.push(PgnGame {
+            tags,
+            moves,
+            result: PgnResult::Unknown,
+        });
+    }
+
+    games
+}
+
+/// Parses a `[Name "Value"]` line
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+/// Splits a movetext line into tokens, dropping comments and keeping
+/// parentheses as separate tokens
+///
+/// # Arguments
+/// * `line` - The line to split
+/// * `in_comment` - Whether a `{...}` comment is open, updated for the next line
+fn tokenize_movetext<'a>(line: &'a str, in_comment: &mut bool) -> Vec<&'a str> {
+    let mut tokens = Vec::new();
+    let mut rest = line;
+
+    loop {
+        // Comments can't be nested, a comment ends at the first '}'
+        if *in_comment {
+            match rest.find('}') {
+                Some(end) => {
+                    rest = &rest[end + 1..];
+                    *in_comment = false;
+                }
+                None => break,
+            }
+        }
+
+        let start = match rest.find(|c: char| !c.is_whitespace()) {
+            Some(start) => start,
+            None => break,
+        };
+        rest = &rest[start..];
+
+        if rest.starts_with('{') {
+            *in_comment = true;
+            rest = &rest[1..];
+        } else if rest.starts_with(';') {
+            // A comment until the end of the line
+            break;
+        } else if rest.starts_with('(') || rest.starts_with(')') {
+            tokens.push(&rest[..1]);
+            rest = &rest[1..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '{' | '(' | ')' | ';'))
+                .unwrap_or(rest.len());
+            tokens.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+
+    tokens
+}
+
+/// Removes the move number and annotations of a movetext token
+///
+/// # Returns
+/// The move in SAN, `None` if the token isn't a move (a move number alone or a NAG)
+fn clean_san_token(token: &str) -> Option<String> {
+    if token.starts_with('$') {
+        return None;
+    }
+
+    // "12." or "12..." can be glued to the move ("12.e4")
+    let after_number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let san = if after_number.starts_with('.') {
+        after_number.trim_start_matches('.')
+    } else {
+        token
+    };
+    let san = san.trim_end_matches(['!', '?']);
+
+    if san.is_empty() {
+        None
+    } else {
+        // Castling is sometimes written with zeros
+        Some(san.replace('0', "O"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_the_moves_from_the_fen_tag() {
+        let pgn = "[Event \"Puzzle\"]\n\
+            [SetUp \"1\"]\n\
+            [FEN \"r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3\"]\n\
+            \n\
+            3. Bb5 a6 4. Ba4 *\n";
+        let games = parse_pgn(pgn);
+        assert_eq!(games.len(), 1);
+
+        let positions = games[0].positions().unwrap();
+        assert_eq!(
+            positions[0].to_string(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+        );
+        assert_eq!(
+            positions.last().unwrap().to_string(),
+            "r1bqkbnr/1ppp1ppp/p1n5/4p3/B3P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 1 4"
+        );
+    }
+
+    #[test]
+    fn rejects_an_invalid_fen_tag() {
+        let games = parse_pgn("[FEN \"not a position\"]\n\n1. e4 *\n");
+        assert!(games[0].starting_position().is_none());
+        assert!(games[0].positions().is_err());
+    }
+
+    /// Plays random legal moves until the game ends on the board or `plies` moves
+    fn random_game(start: Engine, plies: usize, rng: &mut impl rand::Rng) -> GameRecord {
+        let mut record = GameRecord::from_position(start);
+        for _ in 0..plies {
+            let moves = record.position().legal_moves();
+            if moves.is_empty() || record.position().pgn_result() != PgnResult::Unknown {
+                break;
+            }
+            record.play(moves[rng.gen_range(0..moves.len())]).unwrap();
+        }
+        record
+    }
+
+    /// Positions of a recorded game, in the same form as `PgnGame::positions`
+    fn recorded_positions(record: &GameRecord) -> Vec<String> {
+        let mut engine = record.start().clone();
+        let mut positions = vec![engine.to_string()];
+        for &chess_move in record.moves() {
+            engine.play(chess_move).unwrap();
+            positions.push(engine.to_string());
+        }
+        positions
+    }
+
+    #[test]
+    fn exported_games_read_back_to_the_same_positions() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(2009);
+        let starts = [
+            STANDARD_START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 3 40",
+        ];
+        for start_fen in starts {
+            for _ in 0..5 {
+                let record = random_game(Engine::from_fen(start_fen).unwrap(), 120, &mut rng);
+                let pgn = record.to_pgn(&PgnTags::default());
+
+                // Long movetexts are split on several lines, none too wide
+                let mut movetext_lines = pgn.lines().skip_while(|line| !line.is_empty()).skip(1);
+                if record.moves().len() > 20 {
+                    assert!(movetext_lines.clone().count() > 1, "{pgn}");
+                }
+                assert!(
+                    movetext_lines.all(|line| line.len() <= PGN_LINE_WIDTH),
+                    "{pgn}"
+                );
+
+                let games = parse_pgn(&pgn);
+                assert_eq!(games.len(), 1, "{pgn}");
+                let game = &games[0];
+                if start_fen == STANDARD_START_FEN {
+                    assert_eq!(game.tag("SetUp"), None);
+                    assert_eq!(game.tag("FEN"), None);
+                } else {
+                    assert_eq!(game.tag("SetUp"), Some("1"));
+                    assert_eq!(game.tag("FEN"), Some(start_fen));
+                }
+
+                let positions: Vec<String> = game
+                    .positions()
+                    .unwrap()
+                    .iter()
+                    .map(|engine| engine.to_string())
+                    .collect();
+                assert_eq!(positions, recorded_positions(&record), "{pgn}");
+                assert_eq!(game.result, record.position().pgn_result(), "{pgn}");
+            }
+        }
+    }
+
+    #[test]
+    fn a_game_ending_on_the_board_keeps_its_result() {
+        let mut record = GameRecord::new();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            let chess_move = record.position().get_move_by_san(san).unwrap();
+            record.play(chess_move).unwrap();
+        }
+        let tags = PgnTags {
+            event: Some("Fool's mate".to_string()),
+            result: Some(PgnResult::WhiteWins),
+            ..PgnTags::default()
+        };
+
+        // The mate on the board wins over the result of the tags
+        let pgn = record.to_pgn(&tags);
+        assert!(pgn.contains("[Event \"Fool's mate\"]\n"), "{pgn}");
+        assert!(pgn.contains("[Result \"0-1\"]\n"), "{pgn}");
+        assert!(pgn.ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n"), "{pgn}");
+
+        let games = parse_pgn(&pgn);
+        assert_eq!(games[0].result, PgnResult::BlackWins);
+        assert_eq!(
+            games[0].positions().unwrap().last().unwrap().to_string(),
+            record.position().to_string()
+        );
+    }
+}
@@ -1,6 +1,12 @@
+use super::engine::Engine;
+use super::move_parsing::parse_str_into_square;
+use super::move_results::IncorrectMoveResults;
+use super::packed_move::PackedMove;
 use super::utility::coordinates_to_u64;
-use crate::{pieces::Piece, prelude::string_from_move};
+use crate::pieces::{Color, Piece};
+use crate::prelude::string_from_move;
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum CastlingMove {
@@ -66,13 +72,230 @@ pub enum PlayerMove {
     Promotion(PromotionMove),
 }
 
+impl PlayerMove {
+    /// Returns the same move on a board mirrored vertically (see `Engine::flipped`)
+    pub fn flipped(&self) -> PlayerMove {
+        match self {
+            PlayerMove::Normal(normal_move) => {
+                let (current_square, target_square) = normal_move.squares();
+                PlayerMove::Normal(NormalMove::new(
+                    current_square.swap_bytes(),
+                    target_square.swap_bytes(),
+                ))
+            }
+            PlayerMove::Promotion(promotion_move) => {
+                let (current_square, target_square) = promotion_move.squares();
+                PlayerMove::Promotion(PromotionMove::new(
+                    current_square.swap_bytes(),
+                    target_square.swap_bytes(),
+                    promotion_move.promotion_piece(),
+                ))
+            }
+            PlayerMove::Castling(castling_move) => PlayerMove::Castling(*castling_move),
+        }
+    }
+
+    /// Parses a move in coordinate notation, as written by `string_from_move`:
+    /// "e2e4", "e7e8q" for a promotion (the piece letter in either case), and
+    /// "O-O" / "O-O-O" for castling
+    ///
+    /// Unlike `create_move_from_str`, an invalid string is an error, not a panic.
+    ///
+    /// # Returns
+    /// `Err(IncorrectMoveResults::InvalidMove)` if the string isn't a move
+    pub fn try_from_str(str_move: &str) -> Result<PlayerMove, IncorrectMoveResults> {
+        match str_move {
+            "O-O" => return Ok(PlayerMove::Castling(CastlingMove::Short)),
+            "O-O-O" => return Ok(PlayerMove::Castling(CastlingMove::Long)),
+            _ => {}
+        }
+
+        let chars: Vec<char> = str_move.chars().collect();
+        if chars.len() != 4 && chars.len() != 5 {
+            return Err(IncorrectMoveResults::InvalidMove);
+        }
+        let current_square = parse_str_into_square(chars[0], chars[1])
+            .map_err(|_| IncorrectMoveResults::InvalidMove)?;
+        let target_square = parse_str_into_square(chars[2], chars[3])
+            .map_err(|_| IncorrectMoveResults::InvalidMove)?;
+
+        match chars.get(4).map(|&piece_char| Piece::from_char(piece_char)) {
+            None => Ok(PlayerMove::Normal(NormalMove::new(
+                current_square,
+                target_square,
+            ))),
+            Some(Some((_, piece)))
+                if matches!(
+                    piece,
+                    Piece::Knight | Piece::Bishop | Piece::Rook | Piece::Queen
+                ) =>
+            {
+                Ok(PlayerMove::Promotion(PromotionMove::new(
+                    current_square,
+                    target_square,
+                    piece,
+                )))
+            }
+            Some(_) => Err(IncorrectMoveResults::IllegalPromotion),
+        }
+    }
+
+    /// Parses a move in long algebraic notation, as used by UCI ("e2e4", "e7e8q")
+    ///
+    /// Castling is written as the two squares move of the king ("e1g1"), which is
+    /// recognized using the position the move is played in. The move isn't
+    /// checked to be legal, `Engine::play` does it.
+    ///
+    /// # Returns
+    /// `Err(IncorrectMoveResults::InvalidMove)` if the string isn't a move in long
+    /// algebraic notation
+    pub fn from_uci(uci: &str, engine: &Engine) -> Result<PlayerMove, IncorrectMoveResults> {
+        let chars: Vec<char> = uci.chars().collect();
+        if chars.len() != 4 && chars.len() != 5 {
+            return Err(IncorrectMoveResults::InvalidMove);
+        }
+        let current_square = parse_str_into_square(chars[0], chars[1])
+            .map_err(|_| IncorrectMoveResults::InvalidMove)?;
+        let target_square = parse_str_into_square(chars[2], chars[3])
+            .map_err(|_| IncorrectMoveResults::InvalidMove)?;
+
+        if let Some(&piece_char) = chars.get(4) {
+            return match Piece::from_char(piece_char) {
+                Some((Color::Black, piece))
+                    if matches!(
+                        piece,
+                        Piece::Knight | Piece::Bishop | Piece::Rook | Piece::Queen
+                    ) =>
+                {
+                    Ok(PlayerMove::Promotion(PromotionMove::new(
+                        current_square,
+                        target_square,
+                        piece,
+                    )))
+                }
+                _ => Err(IncorrectMoveResults::InvalidMove),
+            };
+        }
+
+        // A king moving two files can only be castling
+        if let Some((_, Piece::King)) = engine.get_board().get_piece_at(current_square) {
+            match target_square.trailing_zeros() as i32 - current_square.trailing_zeros() as i32 {
+                2 => return Ok(PlayerMove::Castling(CastlingMove::Short)),
+                -2 => return Ok(PlayerMove::Castling(CastlingMove::Long)),
+                _ => {}
+            }
+        }
+
+        Ok(PlayerMove::Normal(NormalMove::new(
+            current_square,
+            target_square,
+        )))
+    }
+
+    /// Writes the move in long algebraic notation, as used by UCI ("e2e4", "e7e8q")
+    ///
+    /// # Arguments
+    /// * `color` - The side playing the move, castling is written as the move of its king ("e1g1")
+    pub fn to_uci(&self, color: Color) -> String {
+        let packed_move = PackedMove::from_player_move(*self, color);
+        let mut uci = String::new();
+        for square in [packed_move.from_square(), packed_move.to_square()] {
+            uci.push((b'a' + square % 8) as char);
+            uci.push((b'1' + square / 8) as char);
+        }
+        if let PlayerMove::Promotion(promotion_move) = self {
+            uci.push(promotion_move.promotion_piece().to_char(Color::Black));
+        }
+        uci
+    }
+}
+
+/// Writes the move in coordinate notation, see `string_from_move`
 impl fmt::Display for PlayerMove {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let move_type = match self {
-            PlayerMove::Castling(_) => "CastlingMove",
-            PlayerMove::Normal(_) => "NormalMove",
-            PlayerMove::Promotion(_) => "Promotion",
-        };
-        write!(f, "{}({})", move_type, string_from_move(self))
+        write!(f, "{}", string_from_move(self))
+    }
+}
+
+/// Parses a move in coordinate notation, see `PlayerMove::try_from_str`
+impl FromStr for PlayerMove {
+    type Err = IncorrectMoveResults;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PlayerMove::try_from_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::utility::get_color;
+
+    /// Writes every legal move of `engine` and reads it back, `depth` plies deep
+    fn assert_round_trip(engine: &Engine, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+        let color = get_color(engine.white_to_play());
+        for chess_move in engine.legal_moves() {
+            let uci = chess_move.to_uci(color);
+            assert_eq!(
+                PlayerMove::from_uci(&uci, engine).unwrap(),
+                chess_move,
+                "{uci}"
+            );
+
+            let mut child = engine.clone();
+            child.play(chess_move).unwrap();
+            assert_round_trip(&child, depth - 1);
+        }
+    }
+
+    #[test]
+    fn uci_moves_round_trip() {
+        for fen in [
+            // Castling on both sides for both colors
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            // En passant
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            // Promotions, with and without capture
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1",
+        ] {
+            assert_round_trip(&Engine::from_fen(fen).unwrap(), 2);
+        }
+
+        let engine = Engine::from_fen("r3k2r/4P3/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(
+            PlayerMove::from_uci("e1g1", &engine).unwrap(),
+            PlayerMove::Castling(CastlingMove::Short)
+        );
+        assert_eq!(
+            PlayerMove::from_uci("e1c1", &engine).unwrap(),
+            PlayerMove::Castling(CastlingMove::Long)
+        );
+        assert_eq!(
+            PlayerMove::Castling(CastlingMove::Long).to_uci(Color::Black),
+            "e8c8"
+        );
+        assert_eq!(
+            PlayerMove::from_uci("e7d8n", &engine).unwrap(),
+            PlayerMove::Promotion(PromotionMove::new(1 << 52, 1 << 59, Piece::Knight))
+        );
+    }
+
+    #[test]
+    fn rejects_what_isnt_long_algebraic() {
+        let engine = Engine::new();
+        for uci in [
+            "", "e2", "e2e", "e2e4e5", "i2e4", "e0e4", "e7e8k", "e7e8x", "O-O",
+        ] {
+            assert!(
+                matches!(
+                    PlayerMove::from_uci(uci, &engine),
+                    Err(IncorrectMoveResults::InvalidMove)
+                ),
+                "{uci:?}"
+            );
+        }
     }
 }
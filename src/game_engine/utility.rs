@@ -147,6 +147,27 @@ pub fn get_possible_move(
     }
 }
 
+/// Returns the squares attacked by a single piece, whatever occupies them.
+///
+/// # Arguments
+/// * `piece` - The type of the attacking piece.
+/// * `square` - A `u64` with a single bit set, the position of the piece.
+/// * `occupancy` - A `u64` of all the pieces on the board, blocking the sliders.
+/// * `color` - The color of the piece, for the direction of pawns.
+///
+/// # Returns
+/// A `u64` of the attacked squares, friendly pieces (defended) included.
+pub fn get_piece_attacks(piece: Piece, square: u64, occupancy: u64, color: Color) -> u64 {
+    match piece {
+        Piece::King => king_moves(square, 0),
+        Piece::Queen => queen_moves(square, 0, occupancy),
+        Piece::Rook => rooks_moves(square, 0, occupancy),
+        Piece::Knight => knight_moves(square, 0),
+        Piece::Bishop => bishops_moves(square, 0, occupancy),
+        Piece::Pawn => pawn_captures(square, !0, color),
+    }
+}
+
 /// Returns the color based on the turn.
 ///
 /// # Arguments
@@ -310,6 +331,11 @@ pub fn get_half_turn_boards_mut(
     }
 }
 
+/// Returns the squares the king and the rooks start from.
+///
+/// # Returns
+/// A tuple `(king, short_rook, long_rook)`: the short castling rook is on the h-file
+/// (h1 / h8) and the long castling rook on the a-file (a1 / a8).
 pub fn get_initial_castling_positions(color: Color) -> (u64, u64, u64) {
     match color {
         Color::White => (WHITE_KING, WHITE_ROOKS & FILE_H, WHITE_ROOKS & FILE_A),
@@ -385,61 +411,85 @@ pub fn create_normal_move(current_square: u64, target_square: u64) -> PlayerMove
     PlayerMove::Normal(NormalMove::new(current_square, target_square))
 }
 
+/// Parses a move in coordinate notation ("e2e4", "e7e8q", "O-O")
+///
+/// # Panics
+/// If the string isn't a move, `PlayerMove::try_from_str` returns an error instead
 pub fn create_move_from_str(str_move: &str) -> PlayerMove {
-    if str_move == "O-O" {
-        PlayerMove::Castling(CastlingMove::Short)
-    } else if str_move == "O-O-O" {
-        PlayerMove::Castling(CastlingMove::Long)
-    } else if str_move.len() == 4 {
-        // Parse a regular move like "e2e4"
-        let chars: Vec<char> = str_move.chars().collect();
-        let current_file = chars[0];
-        let current_rank = chars[1];
-        let target_file = chars[2];
-        let target_rank = chars[3];
-
-        // Validate the input
-        if !('a'..='h').contains(&current_file)
-            || !('1'..='8').contains(&current_rank)
-            || !('a'..='h').contains(&target_file)
-            || !('1'..='8').contains(&target_rank)
-        {
-            panic!("Invalid chess move notation: {}", str_move);
-        }
-
-        // Convert file and rank to bitboard positions
-        let current_bitboard =
-            1u64 << ((current_rank as u64 - '1' as u64) * 8 + (current_file as u64 - 'a' as u64));
-        let target_bitboard =
-            1u64 << ((target_rank as u64 - '1' as u64) * 8 + (target_file as u64 - 'a' as u64));
+    PlayerMove::try_from_str(str_move)
+        .unwrap_or_else(|_| panic!("Invalid chess move notation: {}", str_move))
+}
 
-        // Create a normal move
-        PlayerMove::Normal(NormalMove::new(current_bitboard, target_bitboard))
-    } else {
-        panic!("Invalid move format: {}", str_move);
+/// Writes a move in coordinate notation ("e2e4"), castling as "O-O" / "O-O-O"
+///
+/// Promotions get the lowercase promotion piece appended ("e7e8q"). For SAN, which
+/// needs the position, see `Engine::move_to_san`.
+pub fn string_from_move(player_move: &PlayerMove) -> String {
+    let (current, target) = match player_move {
+        PlayerMove::Castling(castling_move) => {
+            return match castling_move {
+                CastlingMove::Short => String::from("O-O"),
+                CastlingMove::Long => String::from("O-O-O"),
+            }
+        }
+        PlayerMove::Normal(normal_move) => normal_move.squares(),
+        PlayerMove::Promotion(promotion_move) => promotion_move.squares(),
+    };
+    let (current_rank, current_file) = u64_to_coordinates(current);
+    let (target_rank, target_file) = u64_to_coordinates(target);
+
+    // Convert coordinates to chess notation
+    let mut move_str = format!(
+        "{}{}{}{}",
+        ((b'a' + current_file as u8) as char),
+        ((b'1' + current_rank as u8) as char),
+        ((b'a' + target_file as u8) as char),
+        ((b'1' + target_rank as u8) as char)
+    );
+    if let PlayerMove::Promotion(promotion_move) = player_move {
+        move_str.push(promotion_move.promotion_piece().to_char(Color::Black));
     }
+    move_str
 }
 
-pub fn string_from_move(player_move: &PlayerMove) -> String {
-    match player_move {
-        PlayerMove::Castling(castling_move) => match castling_move {
-            CastlingMove::Short => String::from("O-O"),
-            CastlingMove::Long => String::from("O-O-O"),
-        },
-        PlayerMove::Normal(normal_move) => {
-            let (current, target) = normal_move.squares();
-            let (current_rank, current_file) = u64_to_coordinates(current);
-            let (target_rank, target_file) = u64_to_coordinates(target);
-
-            // Convert coordinates to chess notation
-            format!(
-                "{}{}{}{}",
-                ((b'a' + current_file as u8) as char),
-                ((b'1' + current_rank as u8) as char),
-                ((b'a' + target_file as u8) as char),
-                ((b'1' + target_rank as u8) as char)
-            )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::engine::Engine;
+
+    #[test]
+    fn castling_moves_the_rook_of_its_side() {
+        let cases = [
+            // (side to move, castling, rook before, rook after)
+            ("w", CastlingMove::Short, 1 << 7, 1 << 5),
+            ("w", CastlingMove::Long, 1 << 0, 1 << 3),
+            ("b", CastlingMove::Short, 1 << 63, 1 << 61),
+            ("b", CastlingMove::Long, 1 << 56, 1 << 59),
+        ];
+
+        for (side, castling, rook_from, rook_to) in cases {
+            let color = if side == "w" {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let (_, short_rook, long_rook) = get_initial_castling_positions(color);
+            let initial_rook = match castling {
+                CastlingMove::Short => short_rook,
+                CastlingMove::Long => long_rook,
+            };
+            assert_eq!(initial_rook, rook_from);
+            assert_eq!(get_final_castling_positions(castling, color).1, rook_to);
+
+            let fen = format!("r3k2r/8/8/8/8/8/8/R3K2R {side} KQkq - 0 1");
+            let mut engine = Engine::from_fen(&fen).unwrap();
+            engine.play(PlayerMove::Castling(castling)).unwrap();
+            let (player_board, _) = get_half_turn_boards(engine.get_board(), color);
+            assert_eq!(
+                player_board.rook & (rook_from | rook_to),
+                rook_to,
+                "{fen} {castling:?}"
+            );
         }
-        _ => "PROMOTION".to_string(),
     }
 }
@@ -3,8 +3,14 @@ pub mod database;
 pub mod game_engine;
 pub mod lichess_api;
 pub mod pieces;
+#[cfg(feature = "legacy")]
+pub mod smart_engine;
 pub mod static_evaluation;
+#[cfg(feature = "legacy")]
+pub mod tree_search;
 pub mod tree_search_v2;
+#[cfg(feature = "uci")]
+pub mod uci;
 
 pub mod prelude {
     // Usefull struct
@@ -14,7 +20,7 @@ pub mod prelude {
     pub use crate::game_engine::player_move::PlayerMove;
     pub use crate::pieces::{Color, Piece};
     pub use crate::static_evaluation::evaluators;
-    pub use crate::tree_search_v2::tree::TreeSearch;
+    pub use crate::tree_search_v2::tree::{DynTreeSearch, TreeSearch};
     // pub use crate::smart_engine::config::EngineConfig;
     // pub use crate::smart_engine::engine::SmartEngine;
     // pub use crate::smart_engine::next_move::MoveEvaluation;
@@ -3,4 +3,16 @@ pub enum ApiError {
     HttpError(String), // Error from reqwest
     OpenContentError,  // Error from reqwest
     JsonError(String), // Error from serde_json
-}
\ No newline at end of file
+    Timeout,           // No answer within the time budget
+    RateLimited,       // Still answered 429 after backing off
+    HttpStatus(u16),   // Any other unsuccessful status
+    NoData,            // Valid answer, but no master game reached the position
+}
+
+impl ApiError {
+    /// Whether the request itself failed, as opposed to Lichess answering
+    /// that it knows nothing about the position (`NoData`)
+    pub fn is_network_error(&self) -> bool {
+        !matches!(self, ApiError::NoData)
+    }
+}
@@ -1,12 +1,23 @@
+use std::time::{Duration, Instant};
 use urlencoding;
 
 use reqwest::blocking::Client;
-use reqwest::header::{AUTHORIZATION, ACCEPT};
+use reqwest::header::{HeaderMap, AUTHORIZATION, ACCEPT, RETRY_AFTER};
+use reqwest::StatusCode;
 use serde_json;
 
 use super::models::{LichessMasterDbResponse, LichessMove};
 use super::api_error::ApiError;
 
+/// Time budget of `fetch_lichess_moves`, retries included
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of retries after a 429 before giving up
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// First wait after a 429 without `Retry-After`, doubled at each retry
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
 fn build_request(fen: &str) -> String {
     format!("https://explorer.lichess.ovh/masters?fen={}", urlencoding::encode(fen))
 }
@@ -15,6 +26,7 @@ fn build_request(fen: &str) -> String {
 ///
 /// This function makes an authenticated HTTP request to the Lichess API to retrieve
 /// statistics about moves played in master-level games from a specific position.
+/// It gives up after `DEFAULT_TIMEOUT`, see `fetch_lichess_moves_with_timeout`.
 ///
 /// # Arguments
 ///
@@ -24,25 +36,261 @@ fn build_request(fen: &str) -> String {
 /// # Returns
 ///
 /// Returns a `Result` containing either:
-/// * `Ok(Vec<LichessMove>)` - A vector of move statistics from the position
+/// * `Ok(Vec<LichessMove>)` - A vector of move statistics from the position, never empty
 /// * `Err(ApiError)` - An error that occurred during the API request, which can be:
 ///   * `ApiError::HttpError` - Failed to make the HTTP request
+///   * `ApiError::Timeout` - No answer within the time budget
+///   * `ApiError::RateLimited` - Lichess kept answering 429 after backing off
+///   * `ApiError::HttpStatus` - Lichess answered with another unsuccessful status
 ///   * `ApiError::OpenContentError` - Failed to read the response content
 ///   * `ApiError::JsonError` - Failed to parse the JSON response
+///   * `ApiError::NoData` - No master game reached the position
 pub fn fetch_lichess_moves(fen: &str, api_key: &str) -> Result<Vec<LichessMove>, ApiError> {
+    fetch_lichess_moves_with_timeout(fen, api_key, DEFAULT_TIMEOUT)
+}
+
+/// Same as `fetch_lichess_moves`, giving up after `timeout`
+///
+/// A 429 (rate limited) answer is retried after the delay asked by its
+/// `Retry-After` header, or an exponential backoff, as long as the wait fits
+/// in the remaining time.
+pub fn fetch_lichess_moves_with_timeout(
+    fen: &str,
+    api_key: &str,
+    timeout: Duration,
+) -> Result<Vec<LichessMove>, ApiError> {
+    let deadline = Instant::now() + timeout;
     let client = Client::new();
-    let url = build_request(&fen);
-    let response = client
-        .get(url)
-        .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .header(ACCEPT, "application/json")
-        .send()
-        .map_err(|err| ApiError::HttpError(err.to_string()))?;
-    let content = response
-        .text()
-        .map_err(|_| ApiError::OpenContentError)?;
-    let data: LichessMasterDbResponse = serde_json::from_str(&content)
+    let url = build_request(fen);
+
+    for attempt in 0..=MAX_RATE_LIMIT_RETRIES {
+        let remaining = remaining_time(deadline)?;
+        let response = client
+            .get(&url)
+            .header(AUTHORIZATION, format!("Bearer {}", api_key))
+            .header(ACCEPT, "application/json")
+            .timeout(remaining)
+            .send()
+            .map_err(request_error)?;
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let delay = backoff_delay(response.headers(), attempt, deadline)?;
+            std::thread::sleep(delay);
+            continue;
+        }
+        check_status(response.status())?;
+
+        let content = response
+            .text()
+            .map_err(content_error)?;
+        return parse_moves(&content);
+    }
+
+    Err(ApiError::RateLimited)
+}
+
+/// Non-blocking version of `fetch_lichess_moves_with_timeout`
+///
+/// The future must be run by a tokio runtime, as every `reqwest` future.
+pub async fn fetch_lichess_moves_async(
+    fen: &str,
+    api_key: &str,
+    timeout: Duration,
+) -> Result<Vec<LichessMove>, ApiError> {
+    let deadline = Instant::now() + timeout;
+    let client = reqwest::Client::new();
+    let url = build_request(fen);
+
+    for attempt in 0..=MAX_RATE_LIMIT_RETRIES {
+        let remaining = remaining_time(deadline)?;
+        let response = client
+            .get(&url)
+            .header(AUTHORIZATION, format!("Bearer {}", api_key))
+            .header(ACCEPT, "application/json")
+            .timeout(remaining)
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let delay = backoff_delay(response.headers(), attempt, deadline)?;
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+        check_status(response.status())?;
+
+        let content = response
+            .text()
+            .await
+            .map_err(content_error)?;
+        return parse_moves(&content);
+    }
+
+    Err(ApiError::RateLimited)
+}
+
+fn remaining_time(deadline: Instant) -> Result<Duration, ApiError> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        Err(ApiError::Timeout)
+    } else {
+        Ok(remaining)
+    }
+}
+
+/// Returns how long to wait before retrying a rate limited request
+///
+/// # Returns
+/// `Err(ApiError::RateLimited)` if it was the last retry or the wait
+/// doesn't fit before the deadline
+fn backoff_delay(headers: &HeaderMap, attempt: u32, deadline: Instant) -> Result<Duration, ApiError> {
+    // Only the number of seconds form of `Retry-After` is used by Lichess
+    let delay = headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(BASE_BACKOFF * 2u32.pow(attempt));
+
+    if attempt == MAX_RATE_LIMIT_RETRIES || Instant::now() + delay >= deadline {
+        Err(ApiError::RateLimited)
+    } else {
+        Ok(delay)
+    }
+}
+
+fn request_error(err: reqwest::Error) -> ApiError {
+    if err.is_timeout() {
+        ApiError::Timeout
+    } else {
+        ApiError::HttpError(err.to_string())
+    }
+}
+
+fn content_error(err: reqwest::Error) -> ApiError {
+    // The body can still be downloading when the time is over
+    if err.is_timeout() {
+        ApiError::Timeout
+    } else {
+        ApiError::OpenContentError
+    }
+}
+
+fn check_status(status: StatusCode) -> Result<(), ApiError> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(ApiError::HttpStatus(status.as_u16()))
+    }
+}
+
+fn parse_moves(content: &str) -> Result<Vec<LichessMove>, ApiError> {
+    let data: LichessMasterDbResponse = serde_json::from_str(content)
         .map_err(|err| ApiError::JsonError(err.to_string()))?;
 
-    Ok(data.moves)
-}
\ No newline at end of file
+    if data.moves.is_empty() {
+        Err(ApiError::NoData)
+    } else {
+        Ok(data.moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn far_deadline() -> Instant {
+        Instant::now() + Duration::from_secs(3600)
+    }
+
+    #[test]
+    fn retry_after_gives_the_delay_in_seconds() {
+        for (value, seconds) in [("3", 3), (" 2 ", 2), ("0", 0)] {
+            let delay = backoff_delay(&retry_after(value), 0, far_deadline()).unwrap();
+            assert_eq!(delay, Duration::from_secs(seconds), "{value:?}");
+        }
+
+        // The date form isn't read, the backoff is used instead
+        let headers = retry_after("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(backoff_delay(&headers, 1, far_deadline()).unwrap(), BASE_BACKOFF * 2);
+    }
+
+    #[test]
+    fn backoff_doubles_until_the_last_retry() {
+        let headers = HeaderMap::new();
+        let delays: Vec<Duration> = (0..MAX_RATE_LIMIT_RETRIES)
+            .map(|attempt| backoff_delay(&headers, attempt, far_deadline()).unwrap())
+            .collect();
+        assert_eq!(delays, [BASE_BACKOFF, BASE_BACKOFF * 2, BASE_BACKOFF * 4]);
+
+        // No retry after the last one, even with time left
+        assert!(matches!(
+            backoff_delay(&headers, MAX_RATE_LIMIT_RETRIES, far_deadline()),
+            Err(ApiError::RateLimited)
+        ));
+        assert!(matches!(
+            backoff_delay(&retry_after("1"), MAX_RATE_LIMIT_RETRIES, far_deadline()),
+            Err(ApiError::RateLimited)
+        ));
+    }
+
+    #[test]
+    fn gives_up_when_the_wait_ends_after_the_deadline() {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        assert!(matches!(
+            backoff_delay(&retry_after("5"), 0, deadline),
+            Err(ApiError::RateLimited)
+        ));
+        assert!(backoff_delay(&retry_after("1"), 0, deadline).is_ok());
+
+        // The third wait of the backoff doesn't fit in 2 seconds
+        let headers = HeaderMap::new();
+        assert!(backoff_delay(&headers, 1, deadline).is_ok());
+        assert!(matches!(backoff_delay(&headers, 2, deadline), Err(ApiError::RateLimited)));
+
+        assert!(matches!(remaining_time(Instant::now()), Err(ApiError::Timeout)));
+    }
+
+    #[test]
+    fn only_successful_statuses_are_read() {
+        assert!(check_status(StatusCode::OK).is_ok());
+        assert!(matches!(check_status(StatusCode::NOT_FOUND), Err(ApiError::HttpStatus(404))));
+        assert!(matches!(
+            check_status(StatusCode::INTERNAL_SERVER_ERROR),
+            Err(ApiError::HttpStatus(500))
+        ));
+    }
+
+    #[test]
+    fn parses_the_moves_of_an_answer() {
+        let content = r#"{
+            "white": 10, "draws": 5, "black": 3,
+            "moves": [
+                {"san": "e4", "white": 6, "draws": 3, "black": 1, "averageRating": 2500},
+                {"san": "d4", "white": 4, "draws": 2, "black": 2, "averageRating": 2450}
+            ]
+        }"#;
+        let moves = parse_moves(content).unwrap();
+        let sans: Vec<&str> = moves.iter().map(|chess_move| chess_move.san.as_str()).collect();
+        assert_eq!(sans, ["e4", "d4"]);
+        assert_eq!(moves[1].average_rating, 2450);
+    }
+
+    #[test]
+    fn an_answer_without_moves_is_no_data() {
+        let content = r#"{"white": 0, "draws": 0, "black": 0, "moves": []}"#;
+        let error = parse_moves(content).unwrap_err();
+        assert!(matches!(error, ApiError::NoData));
+        assert!(!error.is_network_error());
+
+        assert!(matches!(parse_moves("{}"), Err(ApiError::JsonError(_))));
+        assert!(matches!(parse_moves("not json"), Err(ApiError::JsonError(_))));
+    }
+}
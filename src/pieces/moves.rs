@@ -1,6 +1,9 @@
 use super::{
     color::Color,
-    static_positions::{FILE_A, FILE_H, KING_MOVES, KNIGHTS_MOVES, RANK1, RANK3, RANK6, RANK8},
+    static_positions::{
+        EAST, FILE_A, FILE_H, KING_MOVES, KNIGHTS_MOVES, NORTH, NORTH_EAST, NORTH_WEST, RANK3,
+        RANK6, RAYS, SOUTH, SOUTH_EAST, SOUTH_WEST, WEST,
+    },
 };
 
 /// Performs a bitwise left shift operation on a 64-bit unsigned integer.
@@ -31,79 +34,64 @@ fn rshift(x: u64, y: u64) -> u64 {
     x >> y
 }
 
-/// Computes legal moves along a ray in a specified direction.
+/// Computes the squares a slider attacks along a ray from a single square.
 ///
-/// This function shifts a piece's position bitboard repeatedly, adding valid moves while stopping
-/// at same-color pieces (blockage) or opponent pieces (capture).
+/// The ray is precomputed up to the edge of the board, it is cut after the first
+/// occupied square, which is included (it can be a capture or a defended piece).
 ///
 /// # Parameters
-/// - `piece_bitboard`: The bitboard of the piece's position.
-/// - `direction`: `true` for left/upward shifts, `false` for right/downward shifts.
-/// - `shift_value`: Number of bits to shift for the ray (e.g., 1 for horizontal, 8 for vertical).
+/// - `square`: Index of the piece's square.
+/// - `direction`: Index of the direction in `RAYS` (`NORTH`, `SOUTH_WEST`...).
+/// - `occupancy`: Bitboard of all the pieces on the board.
+///
+/// # Returns
+/// A `u64` bitboard representing the attacked squares along the ray.
+fn ray_attacks(square: usize, direction: usize, occupancy: u64) -> u64 {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupancy;
+    if blockers == 0 {
+        return ray;
+    }
+
+    // The nearest blocker is the lowest square on rays going up, the highest on rays going down
+    let blocker = if direction < SOUTH {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray & !RAYS[direction][blocker as usize]
+}
+
+/// Computes the moves of every slider of a bitboard along the given directions.
+///
+/// # Parameters
+/// - `sliders_bitboard`: The bitboard of the pieces' positions.
+/// - `directions`: Indexes of the directions in `RAYS`.
 /// - `same_color_bitboard`: Bitboard of same-color pieces.
 /// - `other_color_bitboard`: Bitboard of opponent pieces.
 ///
 /// # Returns
-/// A `u64` bitboard representing valid moves along the ray.
-fn ray_scanning(
-    piece_bitboard: u64,
-    direction: bool,
-    shift_value: u64,
+/// A `u64` bitboard representing valid moves, stopping at same-color pieces
+/// (blockage) and at opponent pieces (capture).
+fn sliders_moves(
+    sliders_bitboard: u64,
+    directions: [usize; 4],
     same_color_bitboard: u64,
     other_color_bitboard: u64,
 ) -> u64 {
+    let occupancy = same_color_bitboard | other_color_bitboard;
     let mut moves = 0u64;
-    let mut ray = piece_bitboard;
-
-    let shift_fn: fn(u64, u64) -> u64 = if direction { lshift } else { rshift };
-
-    // Define masks for movement constraints
-    let movement_mask = match (direction, shift_value) {
-        // Horizontal movement
-        (true, 1) => !FILE_A,  // Moving left
-        (false, 1) => !FILE_H, // Moving right
-
-        // Vertical movement
-        (true, 8) => !RANK1,  // Moving up
-        (false, 8) => !RANK8, // Moving down
-
-        // Diagonal movements - now properly masking both relevant files and ranks
-        (true, 7) => !(FILE_H | RANK1),
-        (false, 7) => !(FILE_A | RANK8),
-        (true, 9) => !(FILE_A | RANK1),
-        (false, 9) => !(FILE_H | RANK8),
-
-        _ => 0xFFFFFFFFFFFFFFFF,
-    };
+    let mut sliders = sliders_bitboard;
 
-    // Do the first shift
-    ray = shift_fn(ray, shift_value);
-
-    while ray != 0 {
-        // Apply the movement mask
-        ray &= movement_mask;
-
-        if ray == 0 {
-            break;
-        }
-
-        // If encounter same color, it can't take, we just break
-        if same_color_bitboard & ray != 0 {
-            ray &= !same_color_bitboard;
+    while sliders != 0 {
+        let square = sliders.trailing_zeros() as usize;
+        for direction in directions {
+            moves |= ray_attacks(square, direction, occupancy);
         }
-
-        // if it is an other color, it can capture
-        // so we add postion to possible moves
-        if other_color_bitboard & ray != 0 {
-            moves |= ray;
-            ray &= !other_color_bitboard;
-        }
-
-        moves |= ray;
-        ray = shift_fn(ray, shift_value);
+        sliders &= sliders - 1;
     }
 
-    moves
+    moves & !same_color_bitboard
 }
 
 /// Computes all possible moves for a rook on the chessboard.
@@ -120,34 +108,11 @@ fn ray_scanning(
 ///
 /// # Notes
 ///
-/// Calculates moves in four directions:
-/// - Vertical downward (false, 8)
-/// - Vertical upward (true, 8)
-/// - Horizontal leftward (false, 1)
-/// - Horizontal rightward (true, 1)
+/// Uses the precomputed rays in four directions (north, south, east, west)
 pub fn rooks_moves(rook_bitboard: u64, same_color_bitboard: u64, other_color_bitboard: u64) -> u64 {
-    ray_scanning(
-        rook_bitboard,
-        false,
-        8,
-        same_color_bitboard,
-        other_color_bitboard,
-    ) | ray_scanning(
-        rook_bitboard,
-        true,
-        8,
-        same_color_bitboard,
-        other_color_bitboard,
-    ) | ray_scanning(
+    sliders_moves(
         rook_bitboard,
-        false,
-        1,
-        same_color_bitboard,
-        other_color_bitboard,
-    ) | ray_scanning(
-        rook_bitboard,
-        true,
-        1,
+        [NORTH, SOUTH, EAST, WEST],
         same_color_bitboard,
         other_color_bitboard,
     )
@@ -169,28 +134,9 @@ pub fn bishops_moves(
     same_color_bitboard: u64,
     other_color_bitboard: u64,
 ) -> u64 {
-    ray_scanning(
-        bishop_bitboard,
-        false,
-        7,
-        same_color_bitboard,
-        other_color_bitboard,
-    ) | ray_scanning(
-        bishop_bitboard,
-        true,
-        7,
-        same_color_bitboard,
-        other_color_bitboard,
-    ) | ray_scanning(
+    sliders_moves(
         bishop_bitboard,
-        false,
-        9,
-        same_color_bitboard,
-        other_color_bitboard,
-    ) | ray_scanning(
-        bishop_bitboard,
-        true,
-        9,
+        [NORTH_EAST, NORTH_WEST, SOUTH_EAST, SOUTH_WEST],
         same_color_bitboard,
         other_color_bitboard,
     )
@@ -316,3 +262,93 @@ pub fn knight_moves(knights_bitboard: u64, same_color_bitboard: u64) -> u64 {
 pub fn king_moves(king_bitboard: u64, same_color_bitboard: u64) -> u64 {
     KING_MOVES[king_bitboard.trailing_zeros() as usize] & !same_color_bitboard
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const ROOK_STEPS: [(i32, i32); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+    const BISHOP_STEPS: [(i32, i32); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+    /// Walks every direction square by square until the edge or a piece
+    fn walk(square: u32, steps: [(i32, i32); 4], same_color: u64, other_color: u64) -> u64 {
+        let mut moves = 0;
+        for (file_step, rank_step) in steps {
+            let (mut file, mut rank) = ((square % 8) as i32, (square / 8) as i32);
+            loop {
+                file += file_step;
+                rank += rank_step;
+                if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+                    break;
+                }
+
+                let target = 1u64 << (rank * 8 + file);
+                if target & same_color != 0 {
+                    break;
+                }
+                moves |= target;
+                if target & other_color != 0 {
+                    break;
+                }
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn sliders_match_a_square_by_square_walk() {
+        let mut rng = StdRng::seed_from_u64(2012);
+        for square in 0..64 {
+            let slider = 1u64 << square;
+            for _ in 0..500 {
+                // Sparse and dense boards
+                let density = rng.gen_range(1..4);
+                let occupancy = (0..density).fold(u64::MAX, |acc, _| acc & rng.gen::<u64>());
+                let same_color = (occupancy & rng.gen::<u64>() & !slider) | slider;
+                let other_color = occupancy & !same_color;
+
+                assert_eq!(
+                    rooks_moves(slider, same_color, other_color),
+                    walk(square, ROOK_STEPS, same_color, other_color),
+                    "rook on {square}, {same_color:#x} / {other_color:#x}"
+                );
+                assert_eq!(
+                    bishops_moves(slider, same_color, other_color),
+                    walk(square, BISHOP_STEPS, same_color, other_color),
+                    "bishop on {square}, {same_color:#x} / {other_color:#x}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn several_sliders_match_the_union_of_their_walks() {
+        let mut rng = StdRng::seed_from_u64(2041);
+        for _ in 0..5000 {
+            let occupancy = rng.gen::<u64>() & rng.gen::<u64>();
+            let same_color = occupancy & rng.gen::<u64>();
+            let other_color = occupancy & !same_color;
+            // A few sliders, sometimes standing in each other's way
+            let sliders = same_color & rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>();
+
+            let squares = (0..64).filter(|square| sliders & (1u64 << square) != 0);
+            let walks = |steps| {
+                squares
+                    .clone()
+                    .fold(0, |acc, square| acc | walk(square, steps, same_color, other_color))
+            };
+            let rooks = rooks_moves(sliders, same_color, other_color);
+            let bishops = bishops_moves(sliders, same_color, other_color);
+
+            assert_eq!(rooks, walks(ROOK_STEPS), "{sliders:#x} / {occupancy:#x}");
+            assert_eq!(bishops, walks(BISHOP_STEPS), "{sliders:#x} / {occupancy:#x}");
+            assert_eq!(
+                queen_moves(sliders, same_color, other_color),
+                rooks | bishops,
+                "{sliders:#x} / {occupancy:#x}"
+            );
+        }
+    }
+}
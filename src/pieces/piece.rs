@@ -1,3 +1,8 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::Color;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Piece {
     King = 6,
@@ -8,6 +13,95 @@ pub enum Piece {
     Pawn = 1,
 }
 
+impl Piece {
+    /// Returns the FEN / SAN letter of the piece, uppercase for white and
+    /// lowercase for black
+    pub fn to_char(self, color: Color) -> char {
+        let piece_char = match self {
+            Piece::King => 'K',
+            Piece::Queen => 'Q',
+            Piece::Rook => 'R',
+            Piece::Bishop => 'B',
+            Piece::Knight => 'N',
+            Piece::Pawn => 'P',
+        };
+        match color {
+            Color::White => piece_char,
+            Color::Black => piece_char.to_ascii_lowercase(),
+        }
+    }
+
+    /// Reads a FEN letter, the case gives the color of the piece
+    ///
+    /// # Returns
+    /// * `Some((Color, Piece))` - The color and the piece of the letter
+    /// * `None` - If the letter isn't a piece
+    pub fn from_char(c: char) -> Option<(Color, Piece)> {
+        let piece = match c.to_ascii_uppercase() {
+            'K' => Piece::King,
+            'Q' => Piece::Queen,
+            'R' => Piece::Rook,
+            'B' => Piece::Bishop,
+            'N' => Piece::Knight,
+            'P' => Piece::Pawn,
+            _ => return None,
+        };
+        let color = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        Some((color, piece))
+    }
+
+    /// Default material value of the piece in centipawns, the king has none
+    pub fn base_value(self) -> i32 {
+        match self {
+            Piece::Pawn => 100,
+            Piece::Knight => 300,
+            Piece::Bishop => 300,
+            Piece::Rook => 500,
+            Piece::Queen => 900,
+            Piece::King => 0,
+        }
+    }
+
+    /// Whether the piece moves along rays that other pieces can block
+    pub fn is_slider(self) -> bool {
+        matches!(self, Piece::Bishop | Piece::Rook | Piece::Queen)
+    }
+
+    /// Knight or bishop
+    pub fn is_minor(self) -> bool {
+        matches!(self, Piece::Knight | Piece::Bishop)
+    }
+
+    /// Rook or queen
+    pub fn is_major(self) -> bool {
+        matches!(self, Piece::Rook | Piece::Queen)
+    }
+}
+
+/// Writes the uppercase SAN letter of the piece
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char(Color::White))
+    }
+}
+
+/// Parses a single piece letter, in either case
+impl FromStr for Piece {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Piece::from_char(c).map(|(_, piece)| piece).ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
 pub const ALL_PIECES: [Piece; 6] = [
     Piece::Pawn,
     Piece::Knight,
@@ -23,3 +117,67 @@ pub const PROMOTE_PIECE: [Piece; 4] = [
     Piece::Rook,
     Piece::Queen,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_piece_letters_in_both_cases() {
+        for (letters, piece) in [
+            ("Kk", Piece::King),
+            ("Qq", Piece::Queen),
+            ("Rr", Piece::Rook),
+            ("Bb", Piece::Bishop),
+            ("Nn", Piece::Knight),
+            ("Pp", Piece::Pawn),
+        ] {
+            for letter in letters.chars() {
+                assert_eq!(letter.to_string().parse::<Piece>(), Ok(piece));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_single_piece_letter() {
+        for input in ["", "x", "1", "KQ", "k ", "♔"] {
+            assert_eq!(input.parse::<Piece>(), Err(()), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn writes_the_letter_in_the_case_of_the_color() {
+        assert_eq!(Piece::Knight.to_char(Color::White), 'N');
+        assert_eq!(Piece::Knight.to_char(Color::Black), 'n');
+        for piece in ALL_PIECES {
+            for color in [Color::White, Color::Black] {
+                assert_eq!(Piece::from_char(piece.to_char(color)), Some((color, piece)));
+            }
+        }
+    }
+
+    #[test]
+    fn classifies_sliders_minors_and_majors() {
+        for (piece, slider, minor, major) in [
+            (Piece::Pawn, false, false, false),
+            (Piece::Knight, false, true, false),
+            (Piece::Bishop, true, true, false),
+            (Piece::Rook, true, false, true),
+            (Piece::Queen, true, false, true),
+            (Piece::King, false, false, false),
+        ] {
+            assert_eq!(piece.is_slider(), slider, "{piece}");
+            assert_eq!(piece.is_minor(), minor, "{piece}");
+            assert_eq!(piece.is_major(), major, "{piece}");
+        }
+    }
+
+    #[test]
+    fn base_values_are_ordered_by_material() {
+        assert_eq!(Piece::Pawn.base_value(), 100);
+        assert_eq!(Piece::Knight.base_value(), Piece::Bishop.base_value());
+        assert!(Piece::Bishop.base_value() < Piece::Rook.base_value());
+        assert!(Piece::Rook.base_value() < Piece::Queen.base_value());
+        assert_eq!(Piece::King.base_value(), 0);
+    }
+}
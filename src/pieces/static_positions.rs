@@ -50,6 +50,58 @@ const fn precompute_king_moves() -> [u64; 64] {
     moves
 }
 
+/// Directions of the sliding pieces, as indexes in `RAYS`. The first four go
+/// towards higher squares, the last four towards lower squares.
+pub const NORTH: usize = 0;
+pub const EAST: usize = 1;
+pub const NORTH_EAST: usize = 2;
+pub const NORTH_WEST: usize = 3;
+pub const SOUTH: usize = 4;
+pub const WEST: usize = 5;
+pub const SOUTH_WEST: usize = 6;
+pub const SOUTH_EAST: usize = 7;
+
+const fn precompute_rays() -> [[u64; 64]; 8] {
+    // (file step, rank step) of each direction, in the order of the indexes above
+    const STEPS: [(i32, i32); 8] = [
+        (0, 1),
+        (1, 0),
+        (1, 1),
+        (-1, 1),
+        (0, -1),
+        (-1, 0),
+        (-1, -1),
+        (1, -1),
+    ];
+
+    let mut rays = [[0u64; 64]; 8];
+    let mut direction = 0;
+
+    while direction < 8 {
+        let (file_step, rank_step) = STEPS[direction];
+        let mut square = 0;
+
+        while square < 64 {
+            let mut file = (square % 8) as i32 + file_step;
+            let mut rank = (square / 8) as i32 + rank_step;
+            let mut ray = 0u64;
+
+            // Every square up to the edge of the board, the square itself excluded
+            while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                ray |= 1u64 << (rank * 8 + file);
+                file += file_step;
+                rank += rank_step;
+            }
+
+            rays[direction][square] = ray;
+            square += 1;
+        }
+        direction += 1;
+    }
+
+    rays
+}
+
 pub const WHITE_PAWNS: u64 = 0x000000000000FF00;
 pub const BLACK_PAWNS: u64 = 0x00FF000000000000;
 
@@ -84,6 +136,7 @@ pub const RANK8: u64 = 0xFF00000000000000;
 
 pub const KNIGHTS_MOVES: [u64; 64] = precompute_knight_moves();
 pub const KING_MOVES: [u64; 64] = precompute_king_moves();
+pub const RAYS: [[u64; 64]; 8] = precompute_rays();
 
 // For castling
 pub const WHITE_SHORT_CASTLING_EMPTY: u64 = 0x0000000000000060;
@@ -1,9 +1,18 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 // smart_engine/config.rs
 pub struct EngineConfig {
+    // moves played in fewer games are ignored, unless the engine evaluated them
     pub min_database_games: u32,
     pub lichess_api_key: Option<String>,
+    // query lichess when the db has no moves, even with a key set it can be turned off
+    // for offline or deterministic play
+    pub use_lichess_fallback: bool,
+    // time budget of the lichess query, retries included, the book is skipped when it runs out
+    pub lichess_timeout: Duration,
     pub db_path: Option<PathBuf>,
+    // polyglot opening book (.bin) probed before the database and the tree search
+    pub polyglot_book: Option<PathBuf>,
     pub max_fullmove_opening: u32,
 }
\ No newline at end of file
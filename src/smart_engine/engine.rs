@@ -1,8 +1,10 @@
+use crate::game_engine::move_results::IncorrectMoveResults;
 use crate::game_engine::player_move::PlayerMove;
 use crate::tree_search::tree::Tree;
 use crate::database::chess_table::ChessTablesDb;
 use crate::database::models::MoveModel;
-use crate::lichess_api::lichess_requests::fetch_lichess_moves;
+use crate::database::polyglot::PolyglotBook;
+use crate::lichess_api::lichess_requests::fetch_lichess_moves_with_timeout;
 use crate::tree_search::tree_node::TreeNodeRef;
 use crate::tree_search::tree_trait::SearchEngine;
 
@@ -13,6 +15,7 @@ pub struct SmartEngine {
     tree: Tree,
     config: EngineConfig,
     db: ChessTablesDb,
+    book: Option<PolyglotBook>,
 }
 
 impl SmartEngine {
@@ -21,30 +24,45 @@ impl SmartEngine {
             Some(db_path) => ChessTablesDb::at_path(db_path.to_path_buf()),
             None => ChessTablesDb::new() 
         }.map_err(|_| ())?;
+        let book = config.polyglot_book
+            .as_ref()
+            .map(PolyglotBook::open)
+            .transpose()
+            .map_err(|_| ())?;
         Ok(SmartEngine {
             tree,
             config, 
-            db
+            db,
+            book
         })
     }
 
+    // the api key to query lichess with, none when live queries are off
+    fn lichess_fallback_key(&self) -> Option<&str> {
+        self.config
+            .lichess_api_key
+            .as_deref()
+            .filter(|_| self.config.use_lichess_fallback)
+    }
+
     fn try_get_db_moves(&self, fen: &str) -> Option<Vec<MoveModel>> {
         // First we check if there is known moves
         let moves = self.db.get_moves_by_fen(fen).ok()?;
 
         // fetch from lichess in same cases
-        match (moves.len(), &self.config.lichess_api_key) {
-            // Case no result and api_key_provided
+        match (moves.len(), self.lichess_fallback_key()) {
+            // Case no result, api_key_provided and live queries allowed
             (0, Some(api_key)) => {
-                // Get the moves
-                let lichess_moves = fetch_lichess_moves(fen, api_key).ok()?;
+                // Get the moves, a slow or rate limited lichess falls back to the tree search
+                let lichess_moves =
+                    fetch_lichess_moves_with_timeout(fen, api_key, self.config.lichess_timeout).ok()?;
 
                 // We now convert lichess mvoes to our move type
                 // Before inserted (or it would be moved)
                 // We use iter not to consume the vec
                 let chess_moves = lichess_moves.iter().map(|mv| {
                     // We don't care about the board id it's for inserting in the db
-                    MoveModel::from_lichess_move(&mv, 0)
+                    MoveModel::from_lichess_move(mv, 0)
                 }).collect::<Vec<_>>();
 
                 // Insert into the db for later usage 
@@ -53,8 +71,8 @@ impl SmartEngine {
                 Some(chess_moves)
             }
 
-            // Case No result but no api key ...
-            (0, None) => None,
+            // Case No result but no api key or fallback disabled ...
+            (0, _) => None,
 
             // Case where there is already results
             _ => Some(moves),
@@ -64,20 +82,18 @@ impl SmartEngine {
     fn try_db_search(&mut self) -> Option<NextMove> {
         let fullmove_num = self.tree.root().borrow().get_engine().get_fullmove_number();
         let fen = self.tree.root().borrow().get_engine().to_string();
-        let _maximize = self.tree.root().borrow().get_engine().white_to_play();
+        let maximize = self.tree.root().borrow().get_engine().white_to_play();
 
         // First, if possible, we try can get anything from the database
         let db_moves = self.try_get_db_moves(&fen);
-        if fullmove_num <= self.config.max_fullmove_opening && db_moves.is_some() {
-            // We can safely unwrap since we checked it wasn't none
-            let mut moves = db_moves.unwrap();
+        if let Some(mut moves) = db_moves.filter(|_| fullmove_num <= self.config.max_fullmove_opening) {
+            // Moves from too few games have a meaningless win rate,
+            // unless the engine evaluated them
+            let min_games = self.config.min_database_games as i64;
+            moves.retain(|mv| mv.eval_cp.is_some() || mv.game_number >= min_games);
 
-            // Sort moves based on evaluation
-            moves.sort_by(|a, b| {
-                // let cmp = a.to_eval().partial_cmp(&b.to_eval()).unwrap();
-                // if maximize { cmp.reverse() } else { cmp }
-                b.game_number.partial_cmp(&a.game_number).unwrap()
-            });
+            // Sort moves based on evaluation, then expected score
+            MoveModel::sort_by_preference(&mut moves, maximize);
 
             // We now return the first result (best move)
             if let Some(best_move) = moves.first() {
@@ -87,7 +103,7 @@ impl SmartEngine {
 
                 // Play on the root node 
                 // (this will reset any tree if there was one already builded)
-                self.tree.root().borrow_mut().play(chess_move.clone()).ok()?;
+                self.tree.root().borrow_mut().play(chess_move).ok()?;
 
                 return Some(NextMove::new_from_db(
                     chess_move, 
@@ -99,17 +115,39 @@ impl SmartEngine {
         None
     }
 
+    fn try_book_search(&mut self) -> Option<NextMove> {
+        // The most weighted move of the book, if the position is in it
+        let (chess_move, weight) = {
+            let book = self.book.as_ref()?;
+            let root = self.tree.root();
+            let moves = book.moves(root.borrow().get_engine());
+            moves.into_iter().next()?
+        };
+
+        // Play on the root node, as for a database move
+        self.tree.root().borrow_mut().play(chess_move).ok()?;
+        Some(NextMove::new_from_book(chess_move, weight))
+    }
+
     fn try_select_branch(&mut self, chess_move: PlayerMove) -> Result<(), ()>{
         // try select branch
-        if self.tree.select_branch(chess_move).is_err() {
-            // We couldn't select the branch
-            // We try to play on the root directly
-            // If it's not working we can't do anything tho
-            self.tree
-                .root()
-                .borrow_mut()
-                .play(chess_move.clone())
-                .map_err(|_| ())?;
+        match self.tree.select_branch(chess_move) {
+            Ok(()) => {}
+
+            // The promotion piece is wrong, playing it on the root would fail as well
+            Err(IncorrectMoveResults::PromotionExpected)
+            | Err(IncorrectMoveResults::IllegalPromotion) => return Err(()),
+
+            Err(_) => {
+                // We couldn't select the branch
+                // We try to play on the root directly
+                // If it's not working we can't do anything tho
+                self.tree
+                    .root()
+                    .borrow_mut()
+                    .play(chess_move)
+                    .map_err(|_| ())?;
+            }
         }
         Ok(())
     }
@@ -119,21 +157,24 @@ impl SmartEngine {
         let best_move = self.tree.search_best_move();
 
         // ?? Shouldn't happen but we never know
-        if best_move.get_move().is_none() {
-            return None;
-        }
+        best_move.get_move()?;
 
         // Else we can use the chess move
-        let chess_move = best_move.get_move().unwrap().clone();
+        let chess_move = best_move.get_move().unwrap();
         self.try_select_branch(chess_move).ok()?;
 
         // Get the mate depth on the new node
         let mate_depth = self.tree.root().borrow().get_plies_to_mate();
-        return Some(NextMove::new_from_tree(chess_move, best_move.get_score(), best_move.get_depth(), mate_depth));
+        Some(NextMove::new_from_tree(chess_move, best_move.get_score(), best_move.get_depth(), mate_depth))
     }
 
     pub fn get_next_move(&mut self) -> Option<NextMove> {
-        // Try database moves first
+        // The opening book is local, no need to query the database for its moves
+        if let Some(book_move) = self.try_book_search() {
+            return Some(book_move);
+        }
+
+        // Try database moves then
         if let Some(db_move) = self.try_db_search() {
             return Some(db_move);
         }
@@ -144,7 +185,7 @@ impl SmartEngine {
         }
         
         // It failed nooooo :'(
-        return None;
+        None
     }
 
     pub fn opponent_move(&mut self, chess_move: PlayerMove) -> Result<(), ()> {
@@ -166,10 +207,90 @@ impl SmartEngine {
     }
 
     pub fn get_tree_root(&self) -> TreeNodeRef {
-        return self.tree.root();
+        self.tree.root()
     }
 
     pub fn tree_size(&self) -> usize {
-        return self.tree.size();
+        self.tree.size()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::engine::Engine;
+    use crate::smart_engine::next_move::MoveEvaluation;
+    use crate::static_evaluation::evaluators::BasicEvaluator;
+    use crate::static_evaluation::values;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn smart_engine(use_lichess_fallback: bool) -> SmartEngine {
+        smart_engine_with_book(use_lichess_fallback, None)
+    }
+
+    fn smart_engine_with_book(
+        use_lichess_fallback: bool,
+        polyglot_book: Option<PathBuf>,
+    ) -> SmartEngine {
+        let tree = Tree::new(
+            Engine::new(),
+            Box::new(BasicEvaluator::default()),
+            1,
+            usize::MAX,
+            0,
+            values::RAZORING_MARGIN_BASE,
+            values::RAZORING_DEPTH,
+        );
+        let config = EngineConfig {
+            min_database_games: 0,
+            lichess_api_key: Some("key".to_string()),
+            use_lichess_fallback,
+            lichess_timeout: Duration::from_secs(1),
+            db_path: Some(PathBuf::from(":memory:")),
+            polyglot_book,
+            max_fullmove_opening: 10,
+        };
+        SmartEngine::new(tree, config).unwrap()
+    }
+
+    #[test]
+    fn no_lichess_query_with_the_fallback_off() {
+        let engine = smart_engine(false);
+        assert_eq!(engine.lichess_fallback_key(), None);
+
+        // The empty book gives nothing, and nothing was fetched into it
+        let fen = Engine::new().to_string();
+        assert!(engine.try_get_db_moves(&fen).is_none());
+        assert!(engine.db.get_moves_by_fen(&fen).unwrap().is_empty());
+
+        assert_eq!(smart_engine(true).lichess_fallback_key(), Some("key"));
+    }
+
+    #[test]
+    fn book_moves_come_before_the_tree_search() {
+        // A book with a single move from the start position: a2a3
+        let key = crate::database::polyglot::polyglot_key(&Engine::new());
+        let raw_move: u16 = 8 << 6 | 16;
+        let entry = [
+            &key.to_be_bytes()[..],
+            &raw_move.to_be_bytes(),
+            &1u16.to_be_bytes(),
+            &[0; 4],
+        ].concat();
+        let path = std::env::temp_dir()
+            .join(format!("smart_engine_book_{}.bin", std::process::id()));
+        std::fs::write(&path, entry).unwrap();
+
+        let mut engine = smart_engine_with_book(false, Some(path.clone()));
+        std::fs::remove_file(&path).ok();
+
+        let next_move = engine.get_next_move().unwrap();
+        assert!(matches!(next_move.eval, MoveEvaluation::BookWeight(1)));
+        assert_eq!(next_move.chess_move.to_uci(crate::pieces::Color::White), "a2a3");
+
+        // Out of the book, the tree search plays
+        let next_move = engine.get_next_move().unwrap();
+        assert!(matches!(next_move.eval, MoveEvaluation::TreeEvaluation(_)));
+    }
+}
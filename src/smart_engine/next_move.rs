@@ -25,7 +25,9 @@ pub struct TreeEval {
 }
 
 pub enum MoveEvaluation {
-    TreeEvaluation(TreeEval), DbRating(DbRatios)
+    TreeEvaluation(TreeEval), DbRating(DbRatios),
+    // weight of the move in the opening book, relative to the other moves of the position
+    BookWeight(u16)
 }
 
 impl MoveEvaluation {
@@ -34,6 +36,8 @@ impl MoveEvaluation {
         match self {
             MoveEvaluation::TreeEvaluation(tree_eval) => tree_eval.score,
             MoveEvaluation::DbRating(ratios) => ratios.to_eval(),
+            // A book move has no score, every book move is as good
+            MoveEvaluation::BookWeight(_) => 0.0,
         }
     }
 }
@@ -57,6 +61,13 @@ impl NextMove {
             eval: MoveEvaluation::DbRating(DbRatios { white_win_rate, draws_rate, black_win_rate }),
         }
     }
+
+    pub fn new_from_book(chess_move: PlayerMove, weight: u16) -> Self {
+        Self {
+            chess_move,
+            eval: MoveEvaluation::BookWeight(weight),
+        }
+    }
 }
 
 impl PartialEq for NextMove {
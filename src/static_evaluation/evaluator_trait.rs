@@ -1,15 +1,121 @@
 use crate::game_engine::engine::Engine;
 use crate::game_engine::player_move::PlayerMove;
 use crate::pieces::Piece;
+use crate::static_evaluation::values::CENTIPAWNS_PER_UNIT;
 
 pub trait Evaluator {
     fn evaluate_engine_state(&self, engine: &Engine, depth: usize) -> f32;
 
+    /// Ordering bonus of a move, `engine` is the position before the move
     fn evaluate_heuristic_move(
         &self,
+        engine: &Engine,
         player_move: PlayerMove,
         moved_piece: Piece,
         captured_piece_opt: Option<Piece>,
         is_king_checked: bool
     ) -> f32;
+
+    /// Material won by the side playing a move, in the unit of
+    /// `evaluate_engine_state`, so a search can keep a running material
+    /// score instead of counting the pieces of every node
+    ///
+    /// # Arguments
+    /// * `moved` - The piece that moves, a pawn for a promotion
+    /// * `captured` - The piece taken, if any
+    /// * `promotion` - The piece the pawn becomes, if any
+    ///
+    /// The default counts the base value of the pieces
+    fn evaluate_material_delta(
+        &self,
+        moved: Piece,
+        captured: Option<Piece>,
+        promotion: Option<Piece>
+    ) -> f32 {
+        let value = |piece: Piece| piece.base_value() as f32 / self.centipawns_per_unit();
+        captured.map_or(0., value)
+            + promotion.map_or(0., |promotion| value(promotion) - value(moved))
+    }
+
+    /// Number of centipawns in one unit of `evaluate_engine_state`, used to
+    /// report scores in centipawns (UCI `score cp`)
+    ///
+    /// The default matches the built-in evaluators, which count a pawn as 10.
+    fn centipawns_per_unit(&self) -> f32 {
+        CENTIPAWNS_PER_UNIT
+    }
+}
+
+/// A boxed evaluator, for a `DynTreeSearch` choosing its evaluator at runtime
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate_engine_state(&self, engine: &Engine, depth: usize) -> f32 {
+        (**self).evaluate_engine_state(engine, depth)
+    }
+
+    fn evaluate_heuristic_move(
+        &self,
+        engine: &Engine,
+        player_move: PlayerMove,
+        moved_piece: Piece,
+        captured_piece_opt: Option<Piece>,
+        is_king_checked: bool
+    ) -> f32 {
+        (**self).evaluate_heuristic_move(
+            engine,
+            player_move,
+            moved_piece,
+            captured_piece_opt,
+            is_king_checked
+        )
+    }
+
+    fn evaluate_material_delta(
+        &self,
+        moved: Piece,
+        captured: Option<Piece>,
+        promotion: Option<Piece>
+    ) -> f32 {
+        (**self).evaluate_material_delta(moved, captured, promotion)
+    }
+
+    fn centipawns_per_unit(&self) -> f32 {
+        (**self).centipawns_per_unit()
+    }
+}
+
+/// A borrowed evaluator, shared by the searches of `TreeSearch::parallel_search`
+impl<E: Evaluator + ?Sized> Evaluator for &E {
+    fn evaluate_engine_state(&self, engine: &Engine, depth: usize) -> f32 {
+        (**self).evaluate_engine_state(engine, depth)
+    }
+
+    fn evaluate_heuristic_move(
+        &self,
+        engine: &Engine,
+        player_move: PlayerMove,
+        moved_piece: Piece,
+        captured_piece_opt: Option<Piece>,
+        is_king_checked: bool
+    ) -> f32 {
+        (**self).evaluate_heuristic_move(
+            engine,
+            player_move,
+            moved_piece,
+            captured_piece_opt,
+            is_king_checked
+        )
+    }
+
+    fn evaluate_material_delta(
+        &self,
+        moved: Piece,
+        captured: Option<Piece>,
+        promotion: Option<Piece>
+    ) -> f32 {
+        (**self).evaluate_material_delta(moved, captured, promotion)
+    }
+
+    fn centipawns_per_unit(&self) -> f32 {
+        (**self).centipawns_per_unit()
+    }
 }
@@ -1,17 +1,18 @@
 use derive_builder::Builder;
 
 use super::super::evaluator_trait::Evaluator;
-use super::utility::{classic_heuristic_move_bonus, get_value_by_piece, get_value_multiplier_by_piece};
+use super::utility::{
+    classic_heuristic_move_bonus, game_phase, get_value_by_piece, material_delta, pst_score,
+};
 
 use crate::pieces::static_positions::*;
 use crate::pieces::moves;
-use crate::boards::ColorBoard;
+use crate::boards::{Board, ColorBoard};
 use crate::game_engine::engine::Engine;
 use crate::pieces::{Color, Piece};
 use crate::game_engine::player_move::PlayerMove;
 
 // const values
-const TOTAL_OPENING_MATERIAL: i32 = 78;
 const INITIAL_MINORS: [u64; 2] = [ WHITE_BISHOPS | WHITE_KNIGHTS, BLACK_BISHOPS | BLACK_KNIGHTS ];
 const CENTER_SQUARES: u64 = (RANK4 | RANK5) & (FILE_D | FILE_E);
 const EXTENDED_CENTER: u64 = (RANK3 | RANK4 | RANK5 | RANK6) & (FILE_C | FILE_D | FILE_E | FILE_F);
@@ -112,6 +113,27 @@ pub struct AdvancedEvaluator {
     /// Default: 1.0
     #[builder(default = "1.0")]
     rank_multiplier: f32,
+
+    /// Bonus for each square knights, bishops, rooks and queens can move to,
+    /// halved in endgame
+    /// Default: 0.5
+    #[builder(default = "0.5")]
+    mobility_weight: f32,
+
+    /// Bonus for having two bishops or more
+    /// Default: 5.0
+    #[builder(default = "5.0")]
+    bishop_pair_bonus: f32,
+
+    /// Bonus for each rook on a file without pawns
+    /// Default: 2.5
+    #[builder(default = "2.5")]
+    rook_open_file_bonus: f32,
+
+    /// Bonus for each rook on a file with opponent pawns only
+    /// Default: 1.0
+    #[builder(default = "1.0")]
+    rook_semi_open_bonus: f32,
 }
 
 impl AdvancedEvaluator {
@@ -119,32 +141,18 @@ impl AdvancedEvaluator {
     /// we should weight every score calculation
     /// 1. represent full opening / mid game score calculation
     /// 0. represent full end game
-    fn evaluate_game_state(&self, pieces: &Vec<(u64, Piece, Color)>) -> f32 {
-        let mut total_material = 0;
-
-        for (_, piece, _) in pieces {
-            total_material += match piece {
-                Piece::Queen => 9,
-                Piece::Rook => 5,
-                Piece::Bishop | Piece::Knight => 3,
-                Piece::Pawn => 1,
-                Piece::King => 0,
-            };
-        }
-
-        // Scale from 1.0 (opening) to 0.0 (endgame)
-        let phase = (total_material as f32 - self.endgame_material_threshold as f32) /
-                   (TOTAL_OPENING_MATERIAL as f32 - self.endgame_material_threshold as f32);
-        phase.max(0.0).min(1.0)
+    fn evaluate_game_state(&self, board: &Board) -> f32 {
+        game_phase(board, self.endgame_material_threshold)
     }
 
-    fn calculate_material_score(&self, pieces: &Vec<(u64, Piece, Color)>) -> f32 {
+    /// Material and piece-square tables, tapered by the game phase
+    fn calculate_material_score(&self, pieces: &Vec<(u64, Piece, Color)>, phase: f32) -> f32 {
         let mut score = 0.0;
         let white = &Color::White;
 
         // Iterate over the piece to get the material score
         for (bitboard, piece, color) in pieces {
-            let piece_score = self.evaluate_material(*piece, *color, *bitboard);
+            let piece_score = self.evaluate_material(*piece, *color, *bitboard, phase);
             if color == white {
                 score += piece_score;
             } else {
@@ -222,7 +230,13 @@ impl AdvancedEvaluator {
         score += self.evaluate_king_safety_side(player_board.king, player_board.pawn);
 
         // Pawn structure
-        score += self.evaluate_pawn_structure_side(player_board.pawn, is_white);
+        score += self.evaluate_pawn_structure_side(player_board, is_white);
+
+        // Piece mobility
+        score += self.evaluate_mobility_side(player_board, opponent_board);
+
+        // Bishop pair and rooks on open files
+        score += self.evaluate_pieces_side(player_board, opponent_board);
 
         score
     }
@@ -246,16 +260,20 @@ impl AdvancedEvaluator {
         score += self.evaluate_king_safety_side(player_board.king, player_board.pawn) * 0.5;
 
         // Pawn structure (less important in endgame)
-        score += self.evaluate_pawn_structure_side(player_board.pawn, is_white) * 0.5;
+        score += self.evaluate_pawn_structure_side(player_board, is_white) * 0.5;
+
+        // Piece mobility (less important in endgame)
+        score += self.evaluate_mobility_side(player_board, opponent_board) * 0.5;
+
+        // Bishop pair and rooks on open files
+        score += self.evaluate_pieces_side(player_board, opponent_board);
 
         score
     }
 
     /// Helper evaluation functions
-    fn evaluate_material(&self, piece: Piece, color: Color, bitboard: u64) -> f32 {
-        let piece_value = get_value_by_piece(piece);
-        let position_multiplier = get_value_multiplier_by_piece(piece, color, bitboard);
-        piece_value * position_multiplier
+    fn evaluate_material(&self, piece: Piece, color: Color, bitboard: u64, phase: f32) -> f32 {
+        get_value_by_piece(piece) + pst_score(piece, color, bitboard, phase)
     }
 
     /// Clip weight values to 0.0 and 1.0
@@ -354,6 +372,55 @@ impl AdvancedEvaluator {
         attacked
     }
 
+    /// Counts the squares knights, bishops, rooks and queens can move to,
+    /// captures included (pseudo-legal moves, pins are ignored)
+    fn evaluate_mobility_side(&self, player_board: &ColorBoard, opponent_board: &ColorBoard) -> f32 {
+        let same_color_bitboard = player_board.bitboard();
+        let other_color_bitboard = opponent_board.bitboard();
+        let mut mobility = 0;
+
+        // Each piece on its own, the moves of pieces reaching the same square all count
+        for (square, piece) in player_board.individual_pieces_iter() {
+            let piece_moves = match piece {
+                Piece::Knight => moves::knight_moves(square, same_color_bitboard),
+                Piece::Bishop => moves::bishops_moves(square, same_color_bitboard, other_color_bitboard),
+                Piece::Rook => moves::rooks_moves(square, same_color_bitboard, other_color_bitboard),
+                Piece::Queen => moves::queen_moves(square, same_color_bitboard, other_color_bitboard),
+                Piece::Pawn | Piece::King => 0,
+            };
+            mobility += piece_moves.count_ones();
+        }
+
+        mobility as f32 * self.mobility_weight
+    }
+
+    /// Bonus for the bishop pair and for rooks on open or semi-open files
+    fn evaluate_pieces_side(&self, player_board: &ColorBoard, opponent_board: &ColorBoard) -> f32 {
+        let mut score = 0.0;
+
+        if player_board.bishop.count_ones() >= 2 {
+            score += self.bishop_pair_bonus;
+        }
+
+        // Files are open without any pawn, semi-open without own pawn
+        let own_pawn_files = player_board.pawn_file_mask();
+        let pawn_files = own_pawn_files | opponent_board.pawn_file_mask();
+        let mut rooks = player_board.rook;
+        while rooks != 0 {
+            let file_bit = 1u8 << (rooks.trailing_zeros() % 8);
+            if pawn_files & file_bit == 0 {
+                score += self.rook_open_file_bonus;
+            } else if own_pawn_files & file_bit == 0 {
+                score += self.rook_semi_open_bonus;
+            }
+
+            // Clear the least significant bit
+            rooks &= rooks - 1;
+        }
+
+        score
+    }
+
     fn evaluate_king_safety_side(
         &self,
         king: u64,
@@ -378,7 +445,8 @@ impl AdvancedEvaluator {
         center_bonus
     }
 
-    fn evaluate_pawn_structure_side(&self, pawn: u64, is_white: bool) -> f32 {
+    fn evaluate_pawn_structure_side(&self, player_board: &ColorBoard, is_white: bool) -> f32 {
+        let pawn = player_board.pawn;
         let mut score = 0.0;
 
         // Check for doubled pawns
@@ -390,24 +458,10 @@ impl AdvancedEvaluator {
             }
         }
 
-        // Check for isolated pawns
-        for file in 0..8 {
-            let file_mask = FILE_A << file;
-            let adjacent_files = if file == 0 {
-                FILE_B
-            } else if file == 7 {
-                FILE_G
-            } else {
-                (FILE_A << (file - 1)) | (FILE_A << (file + 1))
-            };
-
-            let pawns_in_file = pawn & file_mask;
-            let pawns_in_adjacent = pawn & adjacent_files;
-
-            if pawns_in_file != 0 && pawns_in_adjacent == 0 {
-                score += self.isoled_pawn_penalty;
-            }
-        }
+        // Check for isolated pawns (a pawn file without pawns on the neighbouring files)
+        let pawn_files = player_board.pawn_file_mask();
+        let isolated_files = pawn_files & !((pawn_files << 1) | (pawn_files >> 1));
+        score += isolated_files.count_ones() as f32 * self.isoled_pawn_penalty;
 
         // Check for backward pawns and pawn chains
         let pawn_chain = if is_white {
@@ -468,11 +522,12 @@ impl Evaluator for AdvancedEvaluator {
     fn evaluate_engine_state(&self, engine: &Engine, _: usize) -> f32 {
         let board = engine.get_board();
         let pieces: Vec<(u64, Piece, Color)> = board.individual_pieces();
-        let weight = self.evaluate_game_state(&pieces);
+        let weight = self.evaluate_game_state(board);
         let (opening_weight, end_weight) = self.clip_weight_values(weight);
 
-        // Init the score with the materials
-        let mut score = self.calculate_material_score(&pieces);
+        // Init the score with the materials, the tables are interpolated
+        // with the raw weight for a smooth transition to the endgame
+        let mut score = self.calculate_material_score(&pieces, weight);
 
         // Add opening, endgame bonus / malus depending on game state
         if opening_weight != 0.0 {
@@ -489,11 +544,104 @@ impl Evaluator for AdvancedEvaluator {
 
     fn evaluate_heuristic_move(
         &self,
+        engine: &Engine,
         player_move: PlayerMove,
         moved_piece: Piece,
         captured_piece_opt: Option<Piece>,
         is_king_checked: bool
     ) -> f32 {
-        classic_heuristic_move_bonus(player_move, moved_piece, captured_piece_opt, is_king_checked)
+        let mut bonus = classic_heuristic_move_bonus(engine, player_move, moved_piece, captured_piece_opt, is_king_checked);
+
+        // In endgames, the king should walk toward the center first
+        if let (Piece::King, PlayerMove::Normal(normal_move)) = (moved_piece, player_move) {
+            let weight = self.evaluate_game_state(engine.get_board());
+            let (_, end_weight) = self.clip_weight_values(weight);
+            if end_weight != 0.0 {
+                let (current_square, target_square) = normal_move.squares();
+                let centralization = self.calculate_king_center_bonus(target_square.trailing_zeros() as u8)
+                    - self.calculate_king_center_bonus(current_square.trailing_zeros() as u8);
+                bonus += end_weight * centralization;
+            }
+        }
+
+        bonus
+    }
+
+    /// Material without the piece-square tables, they depend on the squares
+    fn evaluate_material_delta(
+        &self,
+        moved: Piece,
+        captured: Option<Piece>,
+        promotion: Option<Piece>
+    ) -> f32 {
+        material_delta(moved, captured, promotion)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::player_move::NormalMove;
+
+    #[test]
+    fn orders_the_centralizing_king_move_first_in_endgames() {
+        let evaluator = AdvancedEvaluatorBuilder::default().build().unwrap();
+        let engine = Engine::from_fen("8/8/8/8/8/4P3/1K6/7k w - - 0 1").unwrap();
+        let bonus = |target_square: u64| {
+            let king_move = PlayerMove::Normal(NormalMove::new(1 << 9, target_square));
+            evaluator.evaluate_heuristic_move(&engine, king_move, Piece::King, None, false)
+        };
+
+        // Kb2-c3 walks to the center, Kb2-a1 to the corner
+        assert!(bonus(1 << 18) > bonus(1 << 0));
+    }
+
+    #[test]
+    fn active_pieces_score_higher_than_boxed_in_ones() {
+        // Same material and pawns, the rook and bishop are free or walled in
+        let open = Engine::from_fen("4k3/8/8/8/3B4/8/PPP5/3RK3 w - - 0 1").unwrap();
+        let cramped = Engine::from_fen("4k3/8/8/8/8/8/PPP5/RB2K3 w - - 0 1").unwrap();
+        let gap = |evaluator: AdvancedEvaluator| {
+            evaluator.evaluate_engine_state(&open, 0) - evaluator.evaluate_engine_state(&cramped, 0)
+        };
+
+        let with_mobility = gap(AdvancedEvaluatorBuilder::default().build().unwrap());
+        let without_mobility = gap(AdvancedEvaluatorBuilder::default().mobility_weight(0.).build().unwrap());
+        assert!(with_mobility > 0.);
+        assert!(with_mobility > without_mobility);
+    }
+
+    #[test]
+    fn bishop_pair_and_rook_file_bonuses() {
+        // Score the term alone: the same position with and without the bonuses
+        let term = |fen: &str| {
+            let engine = Engine::from_fen(fen).unwrap();
+            let with_bonuses = AdvancedEvaluatorBuilder::default()
+                .bishop_pair_bonus(5.)
+                .rook_open_file_bonus(2.5)
+                .rook_semi_open_bonus(1.)
+                .build()
+                .unwrap();
+            let without_bonuses = AdvancedEvaluatorBuilder::default()
+                .bishop_pair_bonus(0.)
+                .rook_open_file_bonus(0.)
+                .rook_semi_open_bonus(0.)
+                .build()
+                .unwrap();
+            with_bonuses.evaluate_engine_state(&engine, 0) - without_bonuses.evaluate_engine_state(&engine, 0)
+        };
+        let assert_close = |fen: &str, expected: f32| {
+            let actual = term(fen);
+            assert!((actual - expected).abs() < 1e-3, "{fen}: {actual}");
+        };
+
+        // Removing a bishop loses the pair
+        assert_close("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", 5.);
+        assert_close("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", 0.);
+
+        // Open, then closed by an opponent pawn, then by an own pawn
+        assert_close("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", 2.5);
+        assert_close("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1", 1.);
+        assert_close("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1", 0.);
+    }
+}
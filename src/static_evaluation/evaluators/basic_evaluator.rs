@@ -4,7 +4,7 @@ use crate::pieces::Piece;
 
 use super::super::evaluator_trait::Evaluator;
 use super::utility::{
-    classic_heuristic_move_bonus, get_value_by_piece, get_value_multiplier_by_piece,
+    classic_heuristic_move_bonus, get_value_by_piece, get_value_multiplier_by_piece, material_delta,
 };
 
 pub struct BasicEvaluator {}
@@ -20,7 +20,7 @@ impl Evaluator for BasicEvaluator {
         let board = engine.get_board();
         let mut score: f32 = 0.;
         let neg = if engine.white_to_play() { 1. } else { -1. };
-        for it in board.individual_pieces() {
+        for it in board.individual_pieces_iter() {
             let position = it.0;
             let piece = it.1;
             let color = it.2;
@@ -33,16 +33,150 @@ impl Evaluator for BasicEvaluator {
 
     fn evaluate_heuristic_move(
         &self,
+        engine: &Engine,
         player_move: PlayerMove,
         moved_piece: Piece,
         captured_piece_opt: Option<Piece>,
         is_king_checked: bool,
     ) -> f32 {
         classic_heuristic_move_bonus(
+            engine,
             player_move,
             moved_piece,
             captured_piece_opt,
             is_king_checked,
         )
     }
+
+    /// Base material only, the square multipliers of the pieces aren't known
+    fn evaluate_material_delta(
+        &self,
+        moved: Piece,
+        captured: Option<Piece>,
+        promotion: Option<Piece>,
+    ) -> f32 {
+        material_delta(moved, captured, promotion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Score from white's point of view, `evaluate_engine_state` is relative
+    /// to the side to play
+    fn white_score(evaluator: &BasicEvaluator, engine: &Engine) -> f32 {
+        let score = evaluator.evaluate_engine_state(engine, 0);
+        if engine.white_to_play() {
+            score
+        } else {
+            -score
+        }
+    }
+
+    #[test]
+    fn the_flipped_position_gets_the_opposite_score() {
+        let evaluator = BasicEvaluator::default();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ] {
+            // The position and every position one move away
+            let engine = Engine::from_fen(fen).unwrap();
+            let children = engine.generate_moves_with_engine_state().unwrap();
+            let positions = std::iter::once(engine).chain(children.into_iter().map(|c| c.engine));
+
+            for position in positions {
+                // The pieces are summed in another order, allow for rounding
+                let flipped = position.flipped();
+                let white = white_score(&evaluator, &position);
+                let flipped_white = white_score(&evaluator, &flipped);
+                assert!(
+                    (white + flipped_white).abs() < 1e-3,
+                    "{}: {white} and {flipped_white}",
+                    position.to_string()
+                );
+            }
+        }
+    }
+
+    /// Material of the pieces from white's point of view
+    fn material(engine: &Engine) -> f32 {
+        engine
+            .get_board()
+            .individual_pieces_iter()
+            .map(|(_, piece, color)| get_value_by_piece(piece) * (color as isize) as f32)
+            .sum()
+    }
+
+    #[test]
+    fn material_deltas_add_up_to_the_material_of_the_game() {
+        use crate::game_engine::player_move::PlayerMove;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let evaluator = BasicEvaluator::default();
+        let mut rng = StdRng::seed_from_u64(2055);
+        for start in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            for _ in 0..10 {
+                let mut engine = Engine::from_fen(start).unwrap();
+                let mut running = material(&engine);
+                for _ in 0..80 {
+                    let moves = engine.legal_moves();
+                    if moves.is_empty() {
+                        break;
+                    }
+                    let chess_move = moves[rng.gen_range(0..moves.len())];
+
+                    let (from, to, promotion) = match chess_move {
+                        PlayerMove::Normal(normal_move) => {
+                            let (from, to) = normal_move.squares();
+                            (from, to, None)
+                        }
+                        PlayerMove::Promotion(promotion_move) => {
+                            let (from, to) = promotion_move.squares();
+                            (from, to, Some(promotion_move.promotion_piece()))
+                        }
+                        PlayerMove::Castling(_) => {
+                            engine.play(chess_move).unwrap();
+                            continue;
+                        }
+                    };
+                    let board = engine.get_board();
+                    let (_, moved) = board.get_piece_at(from).unwrap();
+                    let en_passant = moved == Piece::Pawn
+                        && board.get_piece_at(to).is_none()
+                        && from.trailing_zeros() % 8 != to.trailing_zeros() % 8;
+                    let captured = match board.get_piece_at(to) {
+                        Some((_, captured)) => Some(captured),
+                        None if en_passant => Some(Piece::Pawn),
+                        None => None,
+                    };
+
+                    let delta = evaluator.evaluate_material_delta(moved, captured, promotion);
+                    running += if engine.white_to_play() {
+                        delta
+                    } else {
+                        -delta
+                    };
+                    engine.play(chess_move).unwrap();
+
+                    let expected = material(&engine);
+                    assert!(
+                        (running - expected).abs() < 1e-3,
+                        "{}: {running} and {expected}",
+                        engine.to_string()
+                    );
+                }
+            }
+        }
+    }
 }
@@ -1,8 +1,82 @@
+use crate::boards::Board;
+use crate::game_engine::engine::Engine;
 use crate::game_engine::player_move::PlayerMove;
 use crate::pieces::{Color, Piece};
+use crate::static_evaluation::see::see_move;
 use crate::static_evaluation::values;
 
+/// Material of the starting position, counting a pawn 1, a minor piece 3, a
+/// rook 5 and a queen 9
+pub const TOTAL_OPENING_MATERIAL: i32 = 78;
+
+// Piece-square tables in centipawns, from white's point of view. They are
+// written as the board is seen by white: a8 first, h1 last.
+pub const PAWN_MG_PST: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5,
+    10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20,
+    -20, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+pub const PAWN_EG_PST: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 80, 80, 80, 80, 80, 80, 80, 80, 50, 50, 50, 50, 50, 50, 50, 50, 30, 30,
+    30, 30, 30, 30, 30, 30, 20, 20, 20, 20, 20, 20, 20, 20, 10, 10, 10, 10, 10, 10, 10, 10, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+pub const KNIGHT_MG_PST: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15, 10,
+    0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 10, 15, 15, 10,
+    5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
+];
+pub const KNIGHT_EG_PST: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, -10, -5, -5, -10, -20, -40, -30, -10, 10, 15,
+    15, 10, -10, -30, -30, -5, 15, 20, 20, 15, -5, -30, -30, -5, 15, 20, 20, 15, -5, -30, -30, -10,
+    10, 15, 15, 10, -10, -30, -40, -20, -10, -5, -5, -10, -20, -40, -50, -40, -30, -30, -30, -30,
+    -40, -50,
+];
+pub const BISHOP_MG_PST: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10, 5, 0,
+    -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10, 10, 10, 10, 10,
+    -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+];
+pub const BISHOP_EG_PST: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 10, 10, 10, 10, 0,
+    -10, -10, 0, 10, 15, 15, 10, 0, -10, -10, 0, 10, 15, 15, 10, 0, -10, -10, 0, 10, 10, 10, 10, 0,
+    -10, -10, 0, 0, 0, 0, 0, 0, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+];
+pub const ROOK_MG_PST: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
+    0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0,
+    0, 5, 5, 0, 0, 0,
+];
+pub const ROOK_EG_PST: [i32; 64] = [
+    10, 10, 10, 10, 10, 10, 10, 10, 15, 15, 15, 15, 15, 15, 15, 15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0,
+];
+pub const QUEEN_MG_PST: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0,
+    0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+];
+pub const QUEEN_EG_PST: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 5, 5, 5, 5, 0, -10, -10, 5, 10, 10, 10, 10, 5,
+    -10, -5, 5, 10, 15, 15, 10, 5, -5, -5, 5, 10, 15, 15, 10, 5, -5, -10, 5, 10, 10, 10, 10, 5,
+    -10, -10, 0, 5, 5, 5, 5, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+];
+pub const KING_MG_PST: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40,
+    -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30, -30, -40, -40, -30,
+    -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20, 20, 0, 0, 0, 0, 20, 20, 20, 30, 10, 0, 0,
+    10, 30, 20,
+];
+pub const KING_EG_PST: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50, -30, -20, -10, 0, 0, -10, -20, -30, -30, -10, 20, 30,
+    30, 20, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30,
+    -10, 20, 30, 30, 20, -10, -30, -30, -30, 0, 0, 0, 0, -30, -30, -50, -30, -30, -30, -30, -30,
+    -30, -50,
+];
+
 pub fn classic_heuristic_move_bonus(
+    engine: &Engine,
     player_move: PlayerMove,
     moved_piece: Piece,
     captured_piece_opt: Option<Piece>,
@@ -37,6 +111,13 @@ pub fn classic_heuristic_move_bonus(
         // if more than 0 we are capturing a higher value piece
         if mvv_lva > 0. {
             bonus += mvv_lva * values::CAPTURE_MVV_LVA_FACTOR;
+        } else {
+            // The captured piece is worth less than ours, the exchange
+            // on the square tells if we lose material
+            let see = see_move(engine, player_move);
+            if see < 0. {
+                bonus += see;
+            }
         }
     }
 
@@ -65,12 +146,166 @@ pub fn get_value_multiplier_by_piece(piece: Piece, color: Color, bitboard: u64)
 }
 
 pub fn get_value_by_piece(piece: Piece) -> f32 {
-    match piece {
-        Piece::Pawn => 10.,
-        Piece::Bishop => 30.,
-        Piece::Knight => 30.,
-        Piece::Rook => 50.,
-        Piece::Queen => 90.,
-        Piece::King => 0.,
+    piece.base_value() as f32 / values::CENTIPAWNS_PER_UNIT
+}
+
+/// Material won by the side playing a move, with the values of
+/// `get_value_by_piece`: the captured piece, and what a promotion adds to
+/// the pawn
+pub fn material_delta(moved: Piece, captured: Option<Piece>, promotion: Option<Piece>) -> f32 {
+    captured.map_or(0., get_value_by_piece)
+        + promotion.map_or(0., |promotion| {
+            get_value_by_piece(promotion) - get_value_by_piece(moved)
+        })
+}
+
+/// Returns how far the game is from the endgame, from the material left
+///
+/// # Arguments
+/// * `board` - The position
+/// * `endgame_material_threshold` - Material (pawn 1, minor piece 3, rook 5,
+///   queen 9, both sides) at which the game is a full endgame
+///
+/// # Returns
+/// 1.0 with all the material of the opening, down to 0.0 in the endgame
+pub fn game_phase(board: &Board, endgame_material_threshold: usize) -> f32 {
+    let total_material: u32 = [&board.white, &board.black]
+        .iter()
+        .map(|color_board| {
+            color_board.pawn.count_ones()
+                + (color_board.knight | color_board.bishop).count_ones() * 3
+                + color_board.rook.count_ones() * 5
+                + color_board.queen.count_ones() * 9
+        })
+        .sum();
+
+    // Scale from 1.0 (opening) to 0.0 (endgame)
+    let phase = (total_material as f32 - endgame_material_threshold as f32)
+        / (TOTAL_OPENING_MATERIAL as f32 - endgame_material_threshold as f32);
+    phase.clamp(0.0, 1.0)
+}
+
+/// Positional value of a piece on a square, interpolated between the
+/// middlegame and the endgame piece-square tables
+///
+/// # Arguments
+/// * `piece` - The type of the piece
+/// * `color` - The color of the piece, black uses the mirrored tables
+/// * `square` - A `u64` with a single bit set, the position of the piece
+/// * `phase` - 1.0 for the middlegame table only, 0.0 for the endgame one (see `game_phase`)
+///
+/// # Returns
+/// The value in score units, positive when the square is good for `color`
+pub fn pst_score(piece: Piece, color: Color, square: u64, phase: f32) -> f32 {
+    // The tables start with the 8th rank
+    let index = match color {
+        Color::White => square.trailing_zeros() as usize ^ 56,
+        Color::Black => square.trailing_zeros() as usize,
+    };
+    let (middlegame, endgame) = match piece {
+        Piece::Pawn => (&PAWN_MG_PST, &PAWN_EG_PST),
+        Piece::Knight => (&KNIGHT_MG_PST, &KNIGHT_EG_PST),
+        Piece::Bishop => (&BISHOP_MG_PST, &BISHOP_EG_PST),
+        Piece::Rook => (&ROOK_MG_PST, &ROOK_EG_PST),
+        Piece::Queen => (&QUEEN_MG_PST, &QUEEN_EG_PST),
+        Piece::King => (&KING_MG_PST, &KING_EG_PST),
+    };
+
+    let phase = phase.clamp(0.0, 1.0);
+    let centipawns = middlegame[index] as f32 * phase + endgame[index] as f32 * (1.0 - phase);
+    centipawns / values::CENTIPAWNS_PER_UNIT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::player_move::NormalMove;
+
+    #[test]
+    fn orders_a_losing_capture_below_a_quiet_developing_move() {
+        // Qd3xd5 is met by exd5, Nb1-c3 develops a piece
+        let engine =
+            Engine::from_fen("rnbqkbnr/ppp2ppp/4p3/3p4/8/3Q4/PPP1PPPP/RNB1KBNR w KQkq - 0 1")
+                .unwrap();
+        let losing_capture = PlayerMove::Normal(NormalMove::new(1 << 19, 1 << 35));
+        let developing_move = PlayerMove::Normal(NormalMove::new(1 << 1, 1 << 18));
+
+        let capture_bonus = classic_heuristic_move_bonus(
+            &engine,
+            losing_capture,
+            Piece::Queen,
+            Some(Piece::Pawn),
+            false,
+        );
+        let quiet_bonus =
+            classic_heuristic_move_bonus(&engine, developing_move, Piece::Knight, None, false);
+        assert!(capture_bonus < quiet_bonus);
+    }
+
+    #[test]
+    fn game_phase_goes_from_the_opening_to_the_endgame_threshold() {
+        let phase = |fen: &str, threshold: usize| {
+            game_phase(Engine::from_fen(fen).unwrap().get_board(), threshold)
+        };
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(phase(start, 20), 1.0);
+        assert_eq!(phase(start, 0), 1.0);
+
+        // Two rooks and two pawns: 12 points of material
+        let ending = "4k3/p6r/8/8/8/8/P6R/4K3 w - - 0 1";
+        assert_eq!(phase(ending, 12), 0.0);
+        assert_eq!(phase(ending, 20), 0.0);
+        assert!(phase(ending, 10) > 0.0 && phase(ending, 10) < 1.0);
+    }
+
+    #[test]
+    fn full_phases_read_a_single_table() {
+        let tables: [(Piece, &[i32; 64], &[i32; 64]); 6] = [
+            (Piece::Pawn, &PAWN_MG_PST, &PAWN_EG_PST),
+            (Piece::Knight, &KNIGHT_MG_PST, &KNIGHT_EG_PST),
+            (Piece::Bishop, &BISHOP_MG_PST, &BISHOP_EG_PST),
+            (Piece::Rook, &ROOK_MG_PST, &ROOK_EG_PST),
+            (Piece::Queen, &QUEEN_MG_PST, &QUEEN_EG_PST),
+            (Piece::King, &KING_MG_PST, &KING_EG_PST),
+        ];
+        for (piece, middlegame, endgame) in tables {
+            for square in 0..64 {
+                // The tables are written from a8, white reads them upside down
+                let index = square ^ 56;
+                let value = |table: &[i32; 64]| table[index] as f32 / values::CENTIPAWNS_PER_UNIT;
+                assert_eq!(
+                    pst_score(piece, Color::White, 1 << square, 1.0),
+                    value(middlegame),
+                    "{piece:?} on {square}"
+                );
+                assert_eq!(
+                    pst_score(piece, Color::White, 1 << square, 0.0),
+                    value(endgame),
+                    "{piece:?} on {square}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn black_reads_the_mirrored_tables() {
+        for piece in [
+            Piece::Pawn,
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Rook,
+            Piece::Queen,
+            Piece::King,
+        ] {
+            for square in 0..64 {
+                for phase in [0.0, 0.3, 1.0] {
+                    assert_eq!(
+                        pst_score(piece, Color::White, 1 << square, phase),
+                        pst_score(piece, Color::Black, 1 << (square ^ 56), phase),
+                        "{piece:?} on {square} at phase {phase}"
+                    );
+                }
+            }
+        }
     }
-}
\ No newline at end of file
+}
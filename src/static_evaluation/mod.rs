@@ -1,3 +1,5 @@
 pub mod values;
 pub mod evaluator_trait;
-pub mod evaluators;
\ No newline at end of file
+pub mod evaluators;
+pub mod see;
+pub mod tuning;
\ No newline at end of file
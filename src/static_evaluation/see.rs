@@ -0,0 +1,169 @@
+//! Static exchange evaluation (SEE)
+//!
+//! Plays out the whole sequence of captures on the target square of a move,
+//! each side always recapturing with its least valuable piece and being free
+//! to stop whenever continuing would lose material.
+
+use crate::boards::Board;
+use crate::game_engine::engine::Engine;
+use crate::game_engine::player_move::PlayerMove;
+use crate::pieces::{Color, Piece, ALL_PIECES};
+
+use super::evaluators::utility::get_value_by_piece;
+
+/// Value given to the king in the exchange, so capturing with the king on a
+/// defended square is never worth it
+const SEE_KING_VALUE: f32 = 1000.;
+
+/// Returns the material balance of the exchange started by `player_move`
+///
+/// # Arguments
+/// * `engine` - Position before the move
+/// * `player_move` - The move to evaluate, usually a capture
+///
+/// # Returns
+/// The material won (positive) or lost (negative) by the side playing the
+/// move, in the same unit as `get_value_by_piece`. Castling is always 0.
+pub fn see_move(engine: &Engine, player_move: PlayerMove) -> f32 {
+    let (current_square, target_square) = match player_move {
+        PlayerMove::Normal(normal_move) => normal_move.squares(),
+        PlayerMove::Promotion(promotion_move) => promotion_move.squares(),
+        PlayerMove::Castling(_) => return 0.,
+    };
+
+    let board = engine.get_board();
+    let (color, moved_piece) = match board.get_piece_at(current_square) {
+        Some(color_and_piece) => color_and_piece,
+        None => return 0.,
+    };
+
+    // The moving piece leaves its square
+    let mut occupancy = board.bitboard() & !current_square;
+
+    let captured_value = match board.get_piece_at(target_square) {
+        Some((_, captured_piece)) => see_value(captured_piece),
+        None if moved_piece == Piece::Pawn
+            && current_square.trailing_zeros() % 8 != target_square.trailing_zeros() % 8 =>
+        {
+            // En passant, the captured pawn is next to the pawn
+            let captured_square = match color {
+                Color::White => target_square >> 8,
+                Color::Black => target_square << 8,
+            };
+            occupancy &= !captured_square;
+            see_value(Piece::Pawn)
+        }
+        None => 0.,
+    };
+
+    // Speculative gains of each capture in the sequence
+    let mut gains = vec![captured_value];
+    let mut piece_on_square = moved_piece;
+    let mut side = opponent(color);
+
+    while let Some((attacker_square, attacker)) =
+        least_valuable_attacker(board, target_square, side, occupancy)
+    {
+        let previous_gain = gains[gains.len() - 1];
+        gains.push(see_value(piece_on_square) - previous_gain);
+
+        // Removing the attacker also reveals the sliders behind it
+        occupancy &= !attacker_square;
+        piece_on_square = attacker;
+        side = opponent(side);
+    }
+
+    // Each side stops the exchange as soon as going on is worse
+    while gains.len() > 1 {
+        let gain = gains.pop().unwrap_or_default();
+        let last = gains.len() - 1;
+        gains[last] = -(-gains[last]).max(gain);
+    }
+    gains[0]
+}
+
+/// Finds the least valuable piece of `color` attacking `square`
+///
+/// # Returns
+/// The square and the type of the attacker, `None` if the square isn't attacked
+fn least_valuable_attacker(
+    board: &Board,
+    square: u64,
+    color: Color,
+    occupancy: u64,
+) -> Option<(u64, Piece)> {
+    let attackers = board.attackers_to_with_occupancy(square, color, occupancy);
+    if attackers == 0 {
+        return None;
+    }
+
+    for piece in ALL_PIECES {
+        let pieces = board.get_bitboard_by_type(piece, color) & attackers;
+        if pieces != 0 {
+            return Some((pieces & pieces.wrapping_neg(), piece));
+        }
+    }
+    None
+}
+
+fn see_value(piece: Piece) -> f32 {
+    match piece {
+        Piece::King => SEE_KING_VALUE,
+        _ => get_value_by_piece(piece),
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see_san(fen: &str, san: &str) -> f32 {
+        let engine = Engine::from_fen(fen).unwrap();
+        see_move(&engine, engine.get_move_by_san(san).unwrap())
+    }
+
+    #[test]
+    fn queen_taking_a_defended_pawn_loses_material() {
+        let see = see_san("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1", "Qxd5");
+        assert!(see < 0.);
+        assert_eq!(
+            see,
+            get_value_by_piece(Piece::Pawn) - get_value_by_piece(Piece::Queen)
+        );
+    }
+
+    #[test]
+    fn rook_taking_a_hanging_bishop_wins_it() {
+        let see = see_san("4k3/8/8/3b4/8/8/8/3RK3 w - - 0 1", "Rxd5");
+        assert_eq!(see, get_value_by_piece(Piece::Bishop));
+    }
+
+    #[test]
+    fn exchanges_count_the_sliders_behind_the_attackers() {
+        // The second rook recaptures through the first one
+        let see = see_san("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "Rxd5");
+        assert_eq!(see, get_value_by_piece(Piece::Pawn));
+
+        let see = see_san("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "Rxd5");
+        assert_eq!(
+            see,
+            get_value_by_piece(Piece::Pawn) - get_value_by_piece(Piece::Rook)
+        );
+    }
+
+    #[test]
+    fn even_trades_and_en_passant() {
+        let see = see_san("4k3/2p5/3n4/8/4N3/8/8/4K3 w - - 0 1", "Nxd6+");
+        assert_eq!(see, 0.);
+
+        let see = see_san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6");
+        assert_eq!(see, get_value_by_piece(Piece::Pawn));
+    }
+}
@@ -0,0 +1,117 @@
+//! Extraction of labeled positions for evaluation tuning
+//!
+//! A tuner (e.g. Texel's method) fits the evaluation on positions labeled with
+//! the result of the game they come from. Only quiet positions are kept, the
+//! static evaluation of a tactical position says little about its outcome.
+
+use std::io::{self, Write};
+
+use crate::game_engine::engine::Engine;
+use crate::game_engine::pgn::{parse_pgn, PgnGame};
+
+/// Returns `true` if the side to play isn't in check and has no capture available
+pub fn is_quiet_position(engine: &Engine) -> bool {
+    !engine.is_king_checked()
+        && !engine
+            .get_all_moves_by_piece()
+            .into_iter()
+            .any(|(_, player_move)| engine.is_capture(player_move))
+}
+
+/// Extracts the quiet positions of a game, labeled with its result
+///
+/// # Arguments
+/// * `game` - The game to extract the positions from
+/// * `skip_plies` - Number of opening plies ignored
+///
+/// # Returns
+/// `FEN;score` lines where score is the score of white (1, 0.5 or 0). Nothing
+/// for a game with an unknown result or a move that couldn't be played.
+pub fn extract_game_positions(game: &PgnGame, skip_plies: usize) -> Vec<String> {
+    let white_score = match game.result.white_score() {
+        Some(score) => score,
+        None => return Vec::new(),
+    };
+    let positions = match game.positions() {
+        Ok(positions) => positions,
+        Err(_) => return Vec::new(),
+    };
+
+    positions
+        .iter()
+        .skip(skip_plies)
+        .filter(|engine| is_quiet_position(engine))
+        .map(|engine| format!("{};{:.1}", engine.to_string(), white_score))
+        .collect()
+}
+
+/// Extracts the quiet positions of every game of a PGN string
+///
+/// # Arguments
+/// * `pgn` - Content of a PGN file
+/// * `skip_plies` - Number of opening plies ignored in each game
+///
+/// # Returns
+/// `FEN;score` lines, see `extract_game_positions`
+pub fn extract_quiet_positions(pgn: &str, skip_plies: usize) -> Vec<String> {
+    parse_pgn(pgn)
+        .iter()
+        .flat_map(|game| extract_game_positions(game, skip_plies))
+        .collect()
+}
+
+/// Writes the quiet positions of every game of a PGN string, one per line
+///
+/// # Returns
+/// The number of positions written
+pub fn write_tuning_positions<W: Write>(
+    pgn: &str,
+    skip_plies: usize,
+    writer: &mut W,
+) -> io::Result<usize> {
+    let lines = extract_quiet_positions(pgn, skip_plies);
+    for line in lines.iter() {
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = "[Result \"1-0\"]\n\n1. e4 d5 2. exd5 Qxd5 3. Nc3 Qa5 4. Bb5+ c6 1-0\n";
+
+    #[test]
+    fn keeps_the_quiet_positions_labeled_with_the_result() {
+        // After 1...d5 and 2.exd5 a capture is available, after 3.Nc3 the
+        // queen can take on a2 or g2, 4.Bb5+ checks and 4...c6 attacks the bishop
+        assert_eq!(
+            extract_quiet_positions(GAME, 0),
+            [
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1;1.0",
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1;1.0",
+                "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3;1.0",
+                "rnb1kbnr/ppp1pppp/8/q7/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 2 4;1.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_opening_plies_and_unfinished_games() {
+        assert_eq!(extract_quiet_positions(GAME, 4).len(), 2);
+        assert!(extract_quiet_positions(&GAME.replace("1-0", "*"), 0).is_empty());
+    }
+
+    #[test]
+    fn writes_one_line_per_position() {
+        let mut output = Vec::new();
+        let written = write_tuning_positions(GAME, 4, &mut output).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3;1.0\n\
+             rnb1kbnr/ppp1pppp/8/q7/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 2 4;1.0\n"
+        );
+    }
+}
@@ -5,6 +5,8 @@ pub const CAPTURE_MVV_LVA_FACTOR: f32 = 1.;
 pub const CHECK_BONUS: f32 = 5.;
 pub const CHECK_MATE: f32 = 1e5 as f32;
 pub const MATE_THRESHOLD: f32 = 9e4 as f32;
+// Internal scores are in tenths of a pawn (a pawn is worth 10), this converts them to centipawns
+pub const CENTIPAWNS_PER_UNIT: f32 = 10.;
 pub const VALUE_TB_WIN_IN_MAX_PLY: f32 = (CHECK_MATE as f32) / 2.;
 pub const WHITE_PAWNS_VALUE: [f32; 64] = [
     0.83, 0.83, 0.83, 0.83, 0.83, 0.83, 0.83, 0.83, 0.87, 0.87, 0.87, 0.9, 0.9, 0.87, 0.87, 0.87,
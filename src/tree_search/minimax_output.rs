@@ -14,7 +14,7 @@ pub enum SearchOutput {
 impl SearchOutput {
     pub fn new(best_node: Option<TreeNodeRef>, score: f32) -> Self {
         SearchOutput::Valid {
-            best_node: best_node,
+            best_node,
             score,
         }
     }
@@ -27,7 +27,7 @@ impl SearchOutput {
         match &self {
             SearchOutput::Invalid => None,
             SearchOutput::Valid { best_node,  .. } => match best_node {
-                Some(node) => node.borrow().get_move().clone(),
+                Some(node) => *node.borrow().get_move(),
                 None => None,
             }
         }
@@ -63,10 +63,7 @@ impl SearchOutput {
     pub fn node(&self) -> Option<TreeNodeRef> {
         match &self {
             SearchOutput::Invalid => None,
-            SearchOutput::Valid { best_node,  .. } => match best_node {
-                Some(node) => Some(node.clone()),
-                None => None
-            }
+            SearchOutput::Valid { best_node,  .. } => best_node.as_ref().map(|node| node.clone())
         }
     }
 }
\ No newline at end of file
@@ -22,6 +22,12 @@ pub struct TranspositionTable {
     current_generation: RwLock<u8>,
 }
 
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TranspositionTable {
     pub fn new() -> Self {
         TranspositionTable {
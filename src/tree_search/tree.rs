@@ -13,8 +13,9 @@
 //! a specified depth or size limit.
 
 use std::rc::Rc;
-use std::usize;
 
+use crate::game_engine::game_states::TerminalReason;
+use crate::game_engine::move_results::IncorrectMoveResults;
 use crate::game_engine::player_move::PlayerMove;
 use crate::game_engine::utility::get_color;
 use crate::game_engine::engine::Engine;
@@ -39,6 +40,7 @@ pub struct Tree {
     // In new function
     root: TreeNodeRef,
     evaluator: Box<dyn Evaluator>,
+    /// Number of plies searched from the root by the last iteration
     max_depth: usize,
     max_size: usize,
     max_q_depth: usize,
@@ -46,6 +48,8 @@ pub struct Tree {
     razoring_depth: usize,
 
     // auto initialized
+    /// Depth (in plies from the root) of the running iteration, leaves are the
+    /// nodes at exactly this depth
     current_depth: usize,
     transpose_table: TranspositionTable,
     node_count: usize
@@ -79,15 +83,10 @@ impl SearchEngine for Tree{
         }
 
         // get the hash to see if this node exist somewhere in the tt
-        let hash = node.borrow().get_engine().compute_board_hash();
-
-        // End tree building if reaching max depth
-        if depth == self.current_depth {
-            // Use static evaluation for very early stage of iterative deepening
-            if self.current_depth <= 2 {
-                return SearchOutput::new(Some(node.clone()), node.borrow().get_score());
-            }
+        let hash = node.borrow().get_engine().zobrist_hash();
 
+        // End tree building if reaching the depth of this iteration
+        if depth >= self.current_depth {
             // Chose the depth of qsearch
             let max_qdepth = if self.current_depth < 5 {
                 self.current_depth.min(self.max_q_depth)
@@ -181,7 +180,7 @@ impl SearchEngine for Tree{
         &mut self,
         node: TreeNodeRef,
         mut alpha: f32,
-        beta: f32,
+        mut beta: f32,
         qdepth: usize,
         max_qdepth: usize
     ) -> SearchOutput {
@@ -209,14 +208,25 @@ impl SearchEngine for Tree{
         // evaluate the current position
         let raw_score = node.borrow().get_score();
 
-        // beta cutoff: opponent is already too good
-        if raw_score >= beta {
-            return SearchOutput::new(None, raw_score);
-        }
+        // Scores are from white's perspective, so the stand pat
+        // is a lower bound for white and an upper bound for black
+        let is_maximizing = node.borrow().get_engine().white_to_play();
+        if is_maximizing {
+            // beta cutoff: opponent is already too good
+            if raw_score >= beta {
+                return SearchOutput::new(None, raw_score);
+            }
 
-        // // update alpha
-        if raw_score > alpha {
-            alpha = raw_score;
+            // update alpha
+            alpha = alpha.max(raw_score);
+        } else {
+            // alpha cutoff: opponent is already too good
+            if raw_score <= alpha {
+                return SearchOutput::new(None, raw_score);
+            }
+
+            // update beta
+            beta = beta.min(raw_score);
         }
 
         // we continue for all the nodes that are unstable
@@ -254,7 +264,7 @@ impl Tree {
     /// # Parameters
     /// * `engine` - Initial game state
     /// * `evaluator` - Strategy for evaluating board positions
-    /// * `max_depth` - Maximum depth to explore in the tree
+    /// * `max_depth` - Number of plies searched from the root, leaves are exactly this deep
     /// * `max_size` - Maximum number of nodes allowed in the tree
     ///
     /// # Returns
@@ -290,6 +300,47 @@ impl Tree {
         self.root.clone()
     }
 
+    /// Returns the principal variation of the last search
+    ///
+    /// Walks down from the root, following at each level the child that gave the
+    /// best score when the node was last searched (`TreeNode::get_best_move`).
+    /// The line stops at the first node without a searched child, and after
+    /// `max_depth` moves so that quiescence captures aren't included.
+    ///
+    /// # Returns
+    /// Moves of the line in order, empty if the root wasn't searched
+    pub fn get_principal_variation(&self) -> Vec<PlayerMove> {
+        let mut principal_variation = Vec::new();
+        let mut node = self.root.clone();
+
+        while principal_variation.len() < self.max_depth {
+            let best_child = {
+                let node_ref = node.borrow();
+                let best_move = match node_ref.get_best_move() {
+                    Some(best_move) if node_ref.has_children_computed() => best_move,
+                    _ => break,
+                };
+                node_ref
+                    .get_children()
+                    .iter()
+                    .find(|child| *child.borrow().get_move() == Some(best_move))
+                    .cloned()
+            };
+
+            let child = match best_child {
+                Some(child) => child,
+                None => break,
+            };
+            match *child.borrow().get_move() {
+                Some(chess_move) => principal_variation.push(chess_move),
+                None => break,
+            }
+            node = child;
+        }
+
+        principal_variation
+    }
+
     /// Evaluates the current game state using the minimax algorithm.
     ///
     /// # Parameters
@@ -351,7 +402,9 @@ impl Tree {
             }
         }
 
+        let best_move = best_node.as_ref().and_then(|child| *child.borrow().get_move());
         node.borrow_mut().set_best_score(best_score);
+        node.borrow_mut().set_best_move(best_move);
         SearchOutput::new(best_node, best_score)
     }
 
@@ -363,7 +416,8 @@ impl Tree {
         // When starting iterative deepening, we remove previous results
         self.transpose_table.maintenance();
 
-        // We start from depth = 1 (because last was select branch)
+        // Every search starts over from depth 1, the root being at depth 0,
+        // so the last iteration searches exactly `max_depth` plies
         self.current_depth = 1;
         let mut output= SearchOutput::new(None, 0.);
 
@@ -372,8 +426,8 @@ impl Tree {
             // Mark all entries as 'old'
             self.transpose_table.new_search();
 
-            // break condition (either too deep or size of the tree to big)
-            if self.max_depth < self.current_depth || exceed_size_limit_prob(self.size(), self.max_size) {
+            // break condition (either max_depth plies were searched or size of the tree to big)
+            if self.current_depth > self.max_depth || exceed_size_limit_prob(self.size(), self.max_size) {
                 println!("ouput 1, size : {}", self.size());
                 return output;
             }
@@ -422,7 +476,7 @@ impl Tree {
             .unwrap_or_default();
 
         // If not possible move this is an end leaf
-        if possible_moves.len() == 0 {
+        if possible_moves.is_empty() {
             self.evaluate_terminal_node(node.clone());
             return;
         }
@@ -475,7 +529,7 @@ impl Tree {
             .map(|child| {
                 // Calculate some the hash to know if we already have a score for this node
                 // Old score aren't perfect but for sufficient for move ordering
-                let hash = child.borrow().get_engine().compute_board_hash();
+                let hash = child.borrow().get_engine().zobrist_hash();
                 let base_score = self.transpose_table.get_old_entry_score(hash)
                     .unwrap_or_else(|| {
                         child.borrow().get_score()
@@ -486,8 +540,9 @@ impl Tree {
                 let player_move = child_ref.get_move().unwrap();
                 let moved_piece = child_ref.get_moved_piece();
                 let captured_piece_opt = child_ref.get_captured_piece();
-                let is_king_checked = child_ref.get_engine().is_current_king_checked();
-                let bonus = self.evaluator.evaluate_heuristic_move(player_move,
+                let is_king_checked = child_ref.get_engine().is_king_checked();
+                let bonus = self.evaluator.evaluate_heuristic_move(node.borrow().get_engine(),
+                    player_move,
                     moved_piece,
                     captured_piece_opt,
                     is_king_checked) * values::HEURISTIC_WEIGHT;
@@ -510,7 +565,7 @@ impl Tree {
         scored_children
     }
 
-    /// Evaluates a terminal node (checkmate or stalemate)
+    /// Evaluates a terminal node (checkmate or stalemate) and records why it is terminal
     ///
     /// # Parameters
     /// * `node` - Terminal node to evaluate
@@ -522,7 +577,7 @@ impl Tree {
 
         // if it's terminal node (number of moves == 0)
         // it means it's either check mate or stale mate
-        if node.borrow().get_engine().is_current_king_checked() {
+        if node.borrow().get_engine().is_king_checked() {
             // get who's check mated
             let color_checkmate = get_color(!white_to_play);
 
@@ -531,10 +586,14 @@ impl Tree {
             let score = values::CHECK_MATE * multiplier;
             node.borrow_mut().set_score(score);
             node.borrow_mut().set_best_score(score);
+            node.borrow_mut().set_terminal_reason(TerminalReason::Checkmate {
+                winner: get_color(!white_to_play),
+            });
             score
         } else {
             node.borrow_mut().set_score(0.);
             node.borrow_mut().set_best_score(0.);
+            node.borrow_mut().set_terminal_reason(TerminalReason::Stalemate);
             0.
         }
     }
@@ -670,10 +729,10 @@ impl Tree {
         None
     }
 
-    /// Calculates the total number of nodes in the tree
+    /// Returns the total number of nodes in the tree
     ///
     /// # Returns
-    /// Total count of nodes in the tree, avoiding double counting in cycles
+    /// Node count maintained by `compute_new_children` and `select_branch`
     pub fn size(&self) -> usize {
         self.node_count
     }
@@ -684,15 +743,40 @@ impl Tree {
     /// * `chess_move` - Move to follow in the tree
     ///
     /// # Returns
-    /// Ok(()) if move was found and selected, Err(()) if move wasn't found
-    pub fn select_branch(&mut self, chess_move: PlayerMove) -> Result<(), ()> {
+    /// * `Ok(())` - The move was found and selected
+    /// * `Err(PromotionExpected)` - A normal move reaching a promotion square
+    /// * `Err(IllegalPromotion)` - A promotion on squares where no promotion is possible
+    /// * `Err(IllegalMove)` - The move isn't a child of the current root
+    pub fn select_branch(&mut self, chess_move: PlayerMove) -> Result<(), IncorrectMoveResults> {
         let mut kept_node: Option<Rc<std::cell::RefCell<TreeNode>>> = None;
+        let mut error = IncorrectMoveResults::IllegalMove;
 
         for child in self.root.borrow().get_children() {
-            if child.borrow().get_move() == &Some(chess_move) {
+            let child_move = match child.borrow().get_move() {
+                Some(child_move) => *child_move,
+                None => continue,
+            };
+
+            if child_move == chess_move {
                 kept_node = Some(child.clone());
                 break;
             }
+
+            // Same squares but a different kind of move: the promotion
+            // piece is either missing or not expected at all
+            error = match (chess_move, child_move) {
+                (PlayerMove::Normal(normal_move), PlayerMove::Promotion(promotion_move))
+                    if normal_move.squares() == promotion_move.squares() =>
+                {
+                    IncorrectMoveResults::PromotionExpected
+                }
+                (PlayerMove::Promotion(promotion_move), PlayerMove::Normal(normal_move))
+                    if normal_move.squares() == promotion_move.squares() =>
+                {
+                    IncorrectMoveResults::IllegalPromotion
+                }
+                _ => error,
+            };
         }
 
         // Reassign root outside the borrowing scope
@@ -700,16 +784,149 @@ impl Tree {
             // We reassigne the root of the tree
             self.root = node;
 
-            // We virtual reduce the size of the tree
-            self.current_depth -= 1;
-
-            // Recalculate the size of the Tree
+            // Recount only the kept subtree, the rest is dropped
             self.node_count = get_tree_size(self.root.clone());
 
             // Everything went well
             Ok(())
         } else {
-            Err(())
+            Err(error)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::player_move::{NormalMove, PromotionMove};
+    use crate::pieces::Piece;
+    use crate::static_evaluation::evaluators::BasicEvaluator;
+
+    fn tree(fen: &str, max_depth: usize) -> Tree {
+        Tree::new(
+            Engine::from_fen(fen).unwrap(),
+            Box::new(BasicEvaluator::default()),
+            max_depth,
+            usize::MAX,
+            2,
+            values::RAZORING_MARGIN_BASE,
+            values::RAZORING_DEPTH,
+        )
+    }
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn maintained_size_matches_full_walk() {
+        let mut tree = tree(START_FEN, 3);
+        let output = tree.search_best_move();
+        assert_eq!(tree.size(), get_tree_size(tree.root()));
+
+        tree.select_branch(output.get_move().unwrap()).unwrap();
+        assert_eq!(tree.size(), get_tree_size(tree.root()));
+    }
+
+    #[test]
+    fn selects_the_promotion_branch_and_rejects_mismatches() {
+        let mut tree = tree("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", 1);
+        tree.search_best_move();
+
+        // b7-b8 without a piece, and a "promotion" of the king on e1-e2
+        assert!(matches!(
+            tree.select_branch(PlayerMove::Normal(NormalMove::new(1 << 49, 1 << 57))),
+            Err(IncorrectMoveResults::PromotionExpected)
+        ));
+        assert!(matches!(
+            tree.select_branch(PlayerMove::Promotion(PromotionMove::new(1 << 4, 1 << 12, Piece::Queen))),
+            Err(IncorrectMoveResults::IllegalPromotion)
+        ));
+
+        let knight_promotion = PlayerMove::Promotion(PromotionMove::new(1 << 49, 1 << 57, Piece::Knight));
+        tree.select_branch(knight_promotion).unwrap();
+        assert_eq!(tree.root().borrow().get_move(), &Some(knight_promotion));
+    }
+
+    fn deepest_node(node: TreeNodeRef) -> usize {
+        node.borrow()
+            .get_children()
+            .iter()
+            .map(|child| 1 + deepest_node(child.clone()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn max_depth_leaves_are_exactly_n_plies_deep() {
+        for max_depth in 1..=3 {
+            // no quiescence, so the leaves are never expanded
+            let mut tree = Tree::new(
+                Engine::from_fen(START_FEN).unwrap(),
+                Box::new(BasicEvaluator::default()),
+                max_depth,
+                usize::MAX,
+                0,
+                values::RAZORING_MARGIN_BASE,
+                values::RAZORING_DEPTH,
+            );
+            tree.search_best_move();
+            assert_eq!(deepest_node(tree.root()), max_depth);
+        }
+    }
+
+    #[test]
+    fn principal_variation_starts_with_the_best_move() {
+        let mut start_tree = tree(START_FEN, 3);
+        let output = start_tree.search_best_move();
+        let pv = start_tree.get_principal_variation();
+        assert_eq!(pv.len(), 3);
+        assert_eq!(Some(pv[0]), output.get_move());
+
+        let mut engine = Engine::from_fen(START_FEN).unwrap();
+        for chess_move in pv {
+            engine.play(chess_move).unwrap();
+        }
+
+        // The line stops at the mate
+        let mut mate_tree = tree("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 3);
+        mate_tree.search_best_move();
+        let pv = mate_tree.get_principal_variation();
+        let mate = Engine::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1")
+            .unwrap()
+            .get_move_by_san("Ra8")
+            .unwrap();
+        assert_eq!(pv, [mate]);
+    }
+
+    #[test]
+    fn black_stand_pat_cuts_below_alpha() {
+        // Black can win the queen, but standing pat is already below alpha
+        let fen = "4k3/8/8/3r4/8/3Q4/8/7K b - - 0 1";
+        let stand_pat = -5.;
+
+        let mut quiet_tree = tree(fen, 2);
+        let root = quiet_tree.root();
+        root.borrow_mut().set_score(stand_pat);
+        let output = quiet_tree.quiescence_search(root, 0., f32::INFINITY, 0, 2);
+        assert_eq!(output.get_score(), stand_pat);
+        assert_eq!(output.get_move(), None);
+
+        // Within the window, black looks for the capture
+        let mut capture_tree = tree(fen, 2);
+        let root = capture_tree.root();
+        root.borrow_mut().set_score(stand_pat);
+        let output = capture_tree.quiescence_search(root, f32::NEG_INFINITY, f32::INFINITY, 0, 2);
+        let capture = Engine::from_fen(fen).unwrap().get_move_by_san("Rxd3").unwrap();
+        assert_eq!(output.get_move(), Some(capture));
+        assert!(output.get_score() < stand_pat);
+    }
+
+    #[test]
+    fn quiet_leaf_returns_the_stand_pat() {
+        let mut quiet_tree = tree("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", 2);
+        let root = quiet_tree.root();
+        root.borrow_mut().set_score(1.5);
+        let output = quiet_tree.quiescence_search(root, f32::NEG_INFINITY, f32::INFINITY, 0, 2);
+        assert_eq!(output.get_score(), 1.5);
+        assert_eq!(output.get_move(), None);
+    }
+}
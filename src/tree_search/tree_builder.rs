@@ -19,6 +19,12 @@ pub struct TreeBuilder {
     evaluator: Option<Box<dyn Evaluator>>
 }
 
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TreeBuilder {
     /// Creates a new TreeBuilder with default settings (all parameters unset)
     pub fn new() -> Self {
@@ -108,17 +114,16 @@ impl TreeBuilder {
     /// # Panics
     /// * If no evaluator was set
     pub fn build(self) -> Result<Tree, ()> {
-        match (self.max_depth, self.max_size) {
-            // Cannot start a tree with both size and max depth unset
-            // It would result in an infinit tree that would never be able to compute
-            // Any result
-            (None, None) => return Err(()),
-            _ => {}
-        };
+        // Cannot start a tree with both size and max depth unset
+        // It would result in an infinit tree that would never be able to compute
+        // Any result
+        if let (None, None) = (self.max_depth, self.max_size) {
+            return Err(());
+        }
 
         let tree = Tree::new(
             self.engine.unwrap_or(Engine::new()),
-            self.evaluator.unwrap_or(Box::new(BasicEvaluator::new())),
+            self.evaluator.unwrap_or(Box::new(BasicEvaluator::default())),
             self.max_depth.unwrap_or(usize::MAX),
             self.max_size.unwrap_or(usize::MAX),
             self.max_q_depth.unwrap_or(usize::MAX),
@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::game_engine::game_states::TerminalReason;
 use crate::game_engine::move_results::MoveResult;
 use crate::game_engine::player_move::PlayerMove;
 use crate::pieces::Piece;
@@ -15,6 +16,7 @@ pub struct TreeNode {
     children: Vec<TreeNodeRef>,
     score: f32,
     best_score: f32,
+    best_move: Option<PlayerMove>,
     computed: bool,
 
     // About the game
@@ -22,6 +24,7 @@ pub struct TreeNode {
     chess_move: Option<PlayerMove>,
     moved_piece: Option<Piece>,
     captured_piece: Option<Piece>,
+    terminal_reason: Option<TerminalReason>,
 }
 
 impl TreeNode {
@@ -60,9 +63,11 @@ impl TreeNode {
             score,
             chess_move,
             best_score: 0.,
+            best_move: None,
             computed: false,
             moved_piece,
             captured_piece,
+            terminal_reason: None,
         }
     }
 
@@ -96,7 +101,7 @@ impl TreeNode {
         if result.is_ok() {
             self.children.clear();
         }
-        return result;
+        result
     }
 
     /// Returns the raw evaluation score of this position
@@ -136,6 +141,17 @@ impl TreeNode {
         self.captured_piece
     }
 
+    /// Returns the move of the child that gave the best score in the last search
+    /// of this node, `None` if no child was searched
+    pub fn get_best_move(&self) -> Option<PlayerMove> {
+        self.best_move
+    }
+
+    /// Returns why the game ends at this position, `None` if it goes on
+    pub fn get_terminal_reason(&self) -> Option<TerminalReason> {
+        self.terminal_reason
+    }
+
     // SETTER
 
     /// Set the best score
@@ -143,6 +159,11 @@ impl TreeNode {
         self.best_score = score;
     }
 
+    /// Set the move of the best child
+    pub fn set_best_move(&mut self, best_move: Option<PlayerMove>) {
+        self.best_move = best_move;
+    }
+
     /// Sets whether this node's children have been computed
     pub fn set_computed(&mut self, is_computed: bool) {
         self.computed = is_computed;
@@ -153,6 +174,11 @@ impl TreeNode {
         self.score = score;
     }
 
+    /// Marks the position as the end of the game
+    pub fn set_terminal_reason(&mut self, reason: TerminalReason) {
+        self.terminal_reason = Some(reason);
+    }
+
     /// Adds a child node to this position's children
     pub fn add_child(&mut self, child: TreeNodeRef) {
         self.children.push(child);
@@ -161,6 +187,7 @@ impl TreeNode {
     /// Copies children, computed status, and best score from another node
     pub fn copy_entry(&mut self, node: TreeNodeRef) {
         self.children = node.borrow().children.clone();
+        self.best_move = node.borrow().best_move;
         self.computed = true;
     }
 
@@ -173,7 +200,7 @@ impl TreeNode {
         if self.best_score >= values::VALUE_TB_WIN_IN_MAX_PLY {
             // We need to solve: score = MATE_SCORE - (n * (n + 1) / 2)
             // Or: (n * (n + 1) / 2) = MATE_SCORE - score
-            let diff = (values::CHECK_MATE - self.best_score.abs()) as f32;
+            let diff = values::CHECK_MATE - self.best_score.abs();
             
             // Quadratic formula: n^2 + n - 2*diff = 0
             // (-1 ± sqrt(1 + 8*diff)) / 2
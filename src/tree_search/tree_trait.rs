@@ -1,4 +1,3 @@
-use std::usize;
 use super::minimax_output::SearchOutput;
 use super::node_with_score::NodeWithScore;
 use super::tree_node::TreeNodeRef;
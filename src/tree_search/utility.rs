@@ -1,6 +1,4 @@
-use std::{collections::HashSet, rc::Rc};
-
-use super::tree_node::{TreeNode, TreeNodeRef};
+use super::tree_node::TreeNodeRef;
 use crate::static_evaluation::values;
 
 pub fn is_unstable_position(node: TreeNodeRef) -> bool {
@@ -8,12 +6,12 @@ pub fn is_unstable_position(node: TreeNodeRef) -> bool {
     let engine = borrowed.get_engine();
     
     // Check if the move is a capture or gives check
-    if engine.is_current_king_checked() {
+    if engine.is_king_checked() {
         return true;
     }
     
     // Look for captures
-    if let Some(_) = borrowed.get_captured_piece() {
+    if borrowed.get_captured_piece().is_some() {
         return true;
     }
     
@@ -42,44 +40,24 @@ pub fn adjust_score_for_depth(score: f32, depth: usize) -> f32 {
 
 /// Calculates the total size of a tree starting from given root
 ///
+/// The tree keeps its own node counter up to date while searching, so this
+/// full walk is only needed when a subtree is kept (see `Tree::select_branch`).
+/// Children are owned by a single parent, so no visited set is required.
+///
 /// # Parameters
 /// * `root_node` - Starting node for size calculation
 ///
 /// # Returns
-/// Total number of unique nodes in the tree
+/// Total number of nodes in the tree
 pub fn get_tree_size(root_node: TreeNodeRef) -> usize {
-    let mut visited = HashSet::new();
-    get_tree_size_recursive(root_node, &mut visited)
-}
-
-/// Helper function for tree size calculation that handles cycles
-///
-/// # Parameters
-/// * `root_node` - Current node being processed
-/// * `visited` - Set of already visited nodes
-///
-/// # Returns
-/// Number of unique nodes in this subtree
-fn get_tree_size_recursive(
-    root_node: TreeNodeRef,
-    visited: &mut HashSet<*const std::cell::RefCell<TreeNode>>,
-) -> usize {
-    let raw_ptr = Rc::as_ptr(&root_node);
-
-    // If this node has already been visited, return 0 to prevent double counting
-    if !visited.insert(raw_ptr) {
-        panic!("CYCLIC REFERENCES !!??");
-    }
-
     let node = root_node.borrow();
-    let mut size = 1; // Count current node
 
-    // Recursively count children
-    for child in node.get_children().iter() {
-        size += get_tree_size_recursive(child.clone(), visited);
-    }
-
-    size
+    // Count current node and recursively count children
+    1 + node
+        .get_children()
+        .iter()
+        .map(|child| get_tree_size(child.clone()))
+        .sum::<usize>()
 }
 
 /// Returns initial score based on whether the player is maximizing
@@ -98,9 +76,5 @@ pub fn init_best_score(is_maximizing: bool) -> f32 {
 }
 
 pub fn exceed_size_limit_prob(current_size: usize, max_size: usize) -> bool {
-    if current_size * 10 > max_size {
-        true
-    } else {
-        false
-    }
+    current_size * 10 > max_size
 }
\ No newline at end of file
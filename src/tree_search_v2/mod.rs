@@ -1,4 +1,8 @@
+pub mod search_info;
 pub mod search_result;
+#[cfg(feature = "tablebase")]
+pub mod tablebase;
+pub mod time_management;
 pub mod transposition_table;
 pub mod tree;
 pub mod tree_node;
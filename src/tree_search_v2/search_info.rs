@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use crate::prelude::PlayerMove;
+use crate::static_evaluation::values;
+
+use super::search_result::{score_to_cp, score_to_mate};
+use super::transposition_table::BoundType;
+
+/// Progress of an iterative search, reported after each completed depth
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    depth: usize,
+    score: f32,
+    bound: BoundType,
+    node_count: usize,
+    elapsed: Duration,
+    pv: Vec<PlayerMove>,
+    centipawns_per_unit: f32,
+}
+
+impl SearchInfo {
+    pub fn new(
+        depth: usize,
+        score: f32,
+        bound: BoundType,
+        node_count: usize,
+        elapsed: Duration,
+        pv: Vec<PlayerMove>,
+    ) -> Self {
+        Self {
+            depth,
+            score,
+            bound,
+            node_count,
+            elapsed,
+            pv,
+            centipawns_per_unit: values::CENTIPAWNS_PER_UNIT,
+        }
+    }
+
+    /// Same info, with `score` counting `centipawns_per_unit` centipawns per
+    /// unit, see `Evaluator::centipawns_per_unit`
+    pub(crate) fn with_centipawns_per_unit(self, centipawns_per_unit: f32) -> Self {
+        Self {
+            centipawns_per_unit,
+            ..self
+        }
+    }
+
+    /// The depth that was just completed
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    /// Score in centipawns, as in the UCI `score cp`
+    pub fn score_cp(&self) -> i32 {
+        score_to_cp(self.score, self.centipawns_per_unit)
+    }
+
+    /// Moves until mate for a mate score, as in the UCI `score mate`
+    pub fn score_mate(&self) -> Option<i32> {
+        score_to_mate(self.score)
+    }
+
+    pub fn bound(&self) -> BoundType {
+        self.bound
+    }
+
+    /// Number of nodes visited since the start of the search, re-searches included
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Time spent since the start of the search
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Nodes visited per second, 0 before any measurable time elapsed
+    pub fn nodes_per_second(&self) -> u64 {
+        match self.elapsed.as_micros() {
+            0 => 0,
+            micros => (self.node_count as u128 * 1_000_000 / micros) as u64,
+        }
+    }
+
+    /// Principal variation, the best line found for both sides
+    pub fn pv(&self) -> &[PlayerMove] {
+        &self.pv
+    }
+}
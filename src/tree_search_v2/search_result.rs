@@ -1,30 +1,51 @@
 use std::fmt;
 
-use crate::prelude::PlayerMove;
+use crate::game_engine::move_results::IncorrectMoveResults;
+use crate::prelude::{Engine, PlayerMove};
+use crate::static_evaluation::values;
+
+use super::transposition_table::BoundType;
 
 #[derive(Debug)]
 pub struct SearchResult {
     best_move: PlayerMove,
     score: f32,
+    bound: BoundType,
     depth: usize,
     tree_max_depth: usize,
     node_count: usize,
+    pv: Vec<PlayerMove>,
+    centipawns_per_unit: f32,
 }
 
 impl SearchResult {
     pub fn new(
         best_move: PlayerMove,
         score: f32,
+        bound: BoundType,
         depth: usize,
         tree_max_depth: usize,
         node_count: usize,
+        pv: Vec<PlayerMove>,
     ) -> Self {
         Self {
             best_move,
             score,
+            bound,
             depth,
             tree_max_depth,
             node_count,
+            pv,
+            centipawns_per_unit: values::CENTIPAWNS_PER_UNIT,
+        }
+    }
+
+    /// Same result, with `score` counting `centipawns_per_unit` centipawns per
+    /// unit, see `Evaluator::centipawns_per_unit`
+    pub(crate) fn with_centipawns_per_unit(self, centipawns_per_unit: f32) -> Self {
+        Self {
+            centipawns_per_unit,
+            ..self
         }
     }
 
@@ -36,17 +57,112 @@ impl SearchResult {
         self.score
     }
 
+    /// Whether `score` is exact or only a bound, which happens when the root
+    /// score falls outside of the aspiration window
+    pub fn bound(&self) -> BoundType {
+        self.bound
+    }
+
+    /// Score in centipawns (a pawn is worth 100), from the point of view of
+    /// the side to play, as in the UCI `score cp`
+    pub fn score_cp(&self) -> i32 {
+        score_to_cp(self.score, self.centipawns_per_unit)
+    }
+
+    /// Number of moves until mate when the score is a mate score, as in the
+    /// UCI `score mate`. Positive if the side to play mates, negative if it
+    /// gets mated.
+    pub fn score_mate(&self) -> Option<i32> {
+        score_to_mate(self.score)
+    }
+
     pub fn depth(&self) -> usize {
         self.depth
     }
+
+    /// Number of nodes visited up to the last completed depth, re-searches
+    /// of failed aspiration windows included
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Same result, counting `node_count` nodes, for the searches run in
+    /// parallel to find it
+    #[cfg(feature = "parallel")]
+    pub(crate) fn with_node_count(self, node_count: usize) -> Self {
+        Self { node_count, ..self }
+    }
+
+    /// Principal variation of the last completed depth, starting with `best_move`
+    pub fn pv(&self) -> &[PlayerMove] {
+        &self.pv
+    }
+
+    /// Plays the principal variation from `start`, which must be the searched position
+    ///
+    /// # Returns
+    /// * `Ok(Engine)` - The position at the end of the principal variation
+    /// * `Err(IncorrectMoveResults)` - A move of the line is illegal
+    pub fn apply_pv(&self, start: &Engine) -> Result<Engine, IncorrectMoveResults> {
+        let mut engine = start.clone();
+        for chess_move in self.pv.iter() {
+            engine.play(*chess_move)?;
+        }
+        Ok(engine)
+    }
+}
+
+/// Converts a search score to centipawns, see `SearchResult::score_cp`
+pub(crate) fn score_to_cp(score: f32, centipawns_per_unit: f32) -> i32 {
+    (score * centipawns_per_unit).round() as i32
+}
+
+/// Converts a mate score to a number of moves, see `SearchResult::score_mate`
+pub(crate) fn score_to_mate(score: f32) -> Option<i32> {
+    if score.abs() <= values::MATE_THRESHOLD {
+        return None;
+    }
+
+    // Mate scores lose one point per ply between the root and the mate
+    let plies = (values::CHECK_MATE - score.abs()).round() as i32;
+    let moves = (plies + 1) / 2;
+    Some(if score > 0. { moves } else { -moves })
 }
 
 impl fmt::Display for SearchResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Search Result:\n  Score: {:.2}\n  Depth/MaxDepth: {}/{}\n  Nodes: {}\n  Best Move: {}",
-            self.score, self.depth, self.tree_max_depth, self.node_count, self.best_move
+            "Search Result:\n  Score: {:.2} ({:?})\n  Depth/MaxDepth: {}/{}\n  Nodes: {}\n  Best Move: {}",
+            self.score, self.bound, self.depth, self.tree_max_depth, self.node_count, self.best_move
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::player_move::NormalMove;
+
+    fn result(score: f32) -> SearchResult {
+        let e2e4 = PlayerMove::Normal(NormalMove::new(1 << 12, 1 << 28));
+        SearchResult::new(e2e4, score, BoundType::Exact, 1, 1, 1, vec![e2e4])
+    }
+
+    #[test]
+    fn converts_a_material_edge_to_centipawns() {
+        // A pawn up, with the default scale and with an evaluator in centipawns
+        assert_eq!(result(10.).score_cp(), 100);
+        assert_eq!(result(-10.).score_cp(), -100);
+        assert_eq!(result(100.).with_centipawns_per_unit(1.).score_cp(), 100);
+        assert_eq!(result(10.).score_mate(), None);
+    }
+
+    #[test]
+    fn converts_mate_scores_to_moves() {
+        // A mate three plies from the root is a mate in 2 moves
+        assert_eq!(result(values::CHECK_MATE - 3.).score_mate(), Some(2));
+        assert_eq!(result(-(values::CHECK_MATE - 4.)).score_mate(), Some(-2));
+        assert_eq!(result(values::CHECK_MATE - 1.).score_mate(), Some(1));
+    }
+}
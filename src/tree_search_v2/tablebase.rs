@@ -0,0 +1,207 @@
+//! Endgame tablebase probing, built with the `tablebase` feature
+//!
+//! A `Tablebase` gives the exact result of positions with few pieces. When
+//! one is given to the tree (`TreeSearchBuilder::tablebase`), the search
+//! probes it at the root and at every node with few enough pieces, and
+//! uses the exact result instead of the static evaluation. Without one,
+//! nothing is probed.
+//!
+//! `SyzygyTablebase` reads the Syzygy files of a directory (`.rtbw` for the
+//! results, `.rtbz` for the moves of the root).
+
+use std::io;
+use std::path::Path;
+
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess};
+use shakmaty_syzygy::Wdl as SyzygyWdl;
+
+use crate::prelude::{Engine, PlayerMove};
+use crate::static_evaluation::values;
+
+/// Result of a position for the side to play, with perfect play on both sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wdl {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Wdl {
+    /// Score of the result found `ply` plies from the root. Wins stay below
+    /// mate scores, the closer to the root the better.
+    pub fn score(self, ply: usize) -> f32 {
+        match self {
+            Wdl::Win => values::VALUE_TB_WIN_IN_MAX_PLY - ply as f32,
+            Wdl::Draw => 0.,
+            Wdl::Loss => ply as f32 - values::VALUE_TB_WIN_IN_MAX_PLY,
+        }
+    }
+}
+
+/// A source of exact endgame results, such as Syzygy WDL / DTZ files
+pub trait Tablebase {
+    /// Largest number of pieces, kings included, of the positions it knows
+    fn max_pieces(&self) -> u32;
+
+    /// Result of the position, `None` if it isn't in the tablebase
+    fn probe_wdl(&self, engine: &Engine) -> Option<Wdl>;
+
+    /// Best move of the position with its result, `None` if the position
+    /// isn't in the tablebase or is over
+    fn probe_root(&self, engine: &Engine) -> Option<(PlayerMove, Wdl)>;
+}
+
+/// Separator of the directories of a Syzygy path, as in the UCI
+/// `SyzygyPath` option
+#[cfg(windows)]
+const PATH_SEPARATOR: char = ';';
+#[cfg(not(windows))]
+const PATH_SEPARATOR: char = ':';
+
+/// Syzygy tables read from the disk. A tablebase without any directory
+/// knows no position, so probing it never changes the search.
+pub struct SyzygyTablebase {
+    tables: shakmaty_syzygy::Tablebase<Chess>,
+}
+
+impl SyzygyTablebase {
+    /// Tablebase without any table
+    pub fn new() -> SyzygyTablebase {
+        SyzygyTablebase {
+            tables: shakmaty_syzygy::Tablebase::new(),
+        }
+    }
+
+    /// Reads the tables of every directory of `path`, separated by `:`
+    /// (`;` on Windows). An empty path gives a tablebase without any table.
+    ///
+    /// # Returns
+    /// `Err` if one of the directories can't be read
+    pub fn open(path: &str) -> io::Result<SyzygyTablebase> {
+        let mut tablebase = SyzygyTablebase::new();
+        for directory in path.split(PATH_SEPARATOR).filter(|dir| !dir.is_empty()) {
+            tablebase.add_directory(directory)?;
+        }
+        Ok(tablebase)
+    }
+
+    /// Adds the tables found in `directory`
+    ///
+    /// # Returns
+    /// The number of tables added, `Err` if the directory can't be read
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> io::Result<usize> {
+        self.tables.add_directory(directory)
+    }
+}
+
+impl Default for SyzygyTablebase {
+    fn default() -> Self {
+        SyzygyTablebase::new()
+    }
+}
+
+impl Tablebase for SyzygyTablebase {
+    fn max_pieces(&self) -> u32 {
+        self.tables.max_pieces() as u32
+    }
+
+    fn probe_wdl(&self, engine: &Engine) -> Option<Wdl> {
+        let position = to_chess(engine)?;
+        // Only the WDL files are needed when the position is taken as
+        // reached by a capture or a pawn move, as the search does
+        let wdl = self.tables.probe_wdl_after_zeroing(&position).ok()?;
+        Some(Wdl::from(wdl))
+    }
+
+    fn probe_root(&self, engine: &Engine) -> Option<(PlayerMove, Wdl)> {
+        let position = to_chess(engine)?;
+        let wdl = self.tables.probe_wdl(&position).ok()?.after_zeroing();
+        let (best_move, _) = self.tables.best_move(&position).ok()??;
+        let uci = best_move.to_uci(CastlingMode::Standard).to_string();
+        let best_move = PlayerMove::from_uci(&uci, engine).ok()?;
+        Some((best_move, Wdl::from(wdl)))
+    }
+}
+
+/// A win or a loss that the 50-move rule turns into a draw is a draw
+impl From<SyzygyWdl> for Wdl {
+    fn from(wdl: SyzygyWdl) -> Self {
+        match wdl {
+            SyzygyWdl::Win => Wdl::Win,
+            SyzygyWdl::Loss => Wdl::Loss,
+            SyzygyWdl::CursedWin | SyzygyWdl::Draw | SyzygyWdl::BlessedLoss => Wdl::Draw,
+        }
+    }
+}
+
+/// Converts the position through its FEN, `None` if shakmaty refuses it
+fn to_chess(engine: &Engine) -> Option<Chess> {
+    let fen: Fen = engine.to_string().parse().ok()?;
+    fen.into_position(CastlingMode::Standard).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOK_ENDING: &str = "8/8/8/4k3/8/8/8/R3K3 w - - 0 1";
+
+    #[test]
+    fn a_tablebase_without_path_knows_no_position() {
+        let position = Engine::from_fen(ROOK_ENDING).unwrap();
+        for tablebase in [SyzygyTablebase::new(), SyzygyTablebase::open("").unwrap()] {
+            assert_eq!(tablebase.max_pieces(), 0);
+            assert_eq!(tablebase.probe_wdl(&position), None);
+            assert!(tablebase.probe_root(&position).is_none());
+        }
+    }
+
+    #[test]
+    fn an_unreadable_directory_is_an_error() {
+        assert!(SyzygyTablebase::open("/nonexistent/syzygy").is_err());
+    }
+
+    #[test]
+    fn wins_are_scored_below_mates_and_closer_is_better() {
+        assert!(Wdl::Win.score(0) < values::CHECK_MATE);
+        assert!(Wdl::Win.score(0) > Wdl::Win.score(3));
+        assert_eq!(Wdl::Loss.score(3), -Wdl::Win.score(3));
+        assert_eq!(Wdl::Draw.score(3), 0.);
+    }
+
+    /// Needs the 3-piece WDL and DTZ files in the directory given by
+    /// `SYZYGY_PATH`
+    #[test]
+    #[ignore = "needs the Syzygy files of SYZYGY_PATH"]
+    fn probes_the_rook_and_pawn_endings() {
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH is set");
+        let tablebase = SyzygyTablebase::open(&path).unwrap();
+        assert!(tablebase.max_pieces() >= 3);
+
+        for (fen, expected) in [
+            (ROOK_ENDING, Wdl::Win),
+            ("8/8/8/4k3/8/8/8/R3K3 b - - 0 1", Wdl::Loss),
+            // The king in front of its pawn only wins with the opposition
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Draw),
+            ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss),
+            // The rook's pawn can't be escorted
+            ("k7/8/K7/P7/8/8/8/8 w - - 0 1", Wdl::Draw),
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            assert_eq!(tablebase.probe_wdl(&position), Some(expected), "{fen}");
+
+            let (best_move, wdl) = tablebase.probe_root(&position).unwrap();
+            assert_eq!(wdl, expected, "{fen}");
+            // The best move keeps the result for the opponent
+            let mut child = position.clone();
+            child.play(best_move).unwrap();
+            let opponent = match expected {
+                Wdl::Win => Wdl::Loss,
+                Wdl::Draw => Wdl::Draw,
+                Wdl::Loss => Wdl::Win,
+            };
+            assert_eq!(tablebase.probe_wdl(&child), Some(opponent), "{fen}");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+/// Default time kept aside on every move for the communication with the GUI
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 30;
+
+/// Number of moves the remaining time has to last when the clock doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Smallest budget allocated, as long as the clock allows it
+const MIN_BUDGET_MS: u64 = 10;
+
+/// Clock of the side to play, as given by UCI `go` (`wtime`, `winc`, `movestogo`)
+#[derive(Debug, Clone, Copy)]
+pub struct ClockState {
+    pub remaining: Duration,
+    pub increment: Duration,
+    /// Moves until the next time control, `None` for sudden death
+    pub moves_to_go: Option<u32>,
+}
+
+/// Settings of the time allocation
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSettings {
+    /// Time lost on every move outside of the search (UCI `Move Overhead`)
+    pub move_overhead_ms: u64,
+}
+
+impl Default for TimeSettings {
+    fn default() -> Self {
+        Self {
+            move_overhead_ms: DEFAULT_MOVE_OVERHEAD_MS,
+        }
+    }
+}
+
+/// Returns the time the next search can use, to give to `TreeSearchBuilder::max_time`
+///
+/// The remaining time minus the move overhead is shared between the moves
+/// to go, and most of the increment is added. The budget never exceeds the
+/// remaining time minus the overhead, so the engine can't lose on time
+/// because of the overhead.
+pub fn allocate(clock: &ClockState, settings: &TimeSettings) -> Duration {
+    let overhead = Duration::from_millis(settings.move_overhead_ms);
+    let available = clock.remaining.saturating_sub(overhead);
+
+    let moves_to_go = clock.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let budget = available / moves_to_go + clock.increment * 3 / 4;
+
+    budget
+        .max(Duration::from_millis(MIN_BUDGET_MS))
+        .min(available)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_allocates_more_than_remaining_minus_overhead() {
+        for remaining_ms in [0, 5, 30, 40, 100, 1_000, 60_000] {
+            for increment_ms in [0, 100, 2_000] {
+                for moves_to_go in [None, Some(0), Some(1), Some(40)] {
+                    for move_overhead_ms in [0, 30, 500] {
+                        let clock = ClockState {
+                            remaining: Duration::from_millis(remaining_ms),
+                            increment: Duration::from_millis(increment_ms),
+                            moves_to_go,
+                        };
+                        let settings = TimeSettings { move_overhead_ms };
+                        let budget = allocate(&clock, &settings);
+                        let limit = remaining_ms.saturating_sub(move_overhead_ms);
+                        assert!(
+                            budget <= Duration::from_millis(limit),
+                            "{clock:?} with {move_overhead_ms} ms of overhead: {budget:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_a_floor_when_the_clock_allows_it() {
+        let clock = ClockState {
+            remaining: Duration::from_millis(100),
+            increment: Duration::ZERO,
+            moves_to_go: None,
+        };
+        let budget = allocate(&clock, &TimeSettings::default());
+        assert_eq!(budget, Duration::from_millis(MIN_BUDGET_MS));
+    }
+}
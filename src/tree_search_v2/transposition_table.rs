@@ -1,6 +1,15 @@
 use crate::prelude::PlayerMove;
 use crate::static_evaluation::values;
-use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Number of independently locked parts of the table, so searches running
+/// in parallel rarely wait for each other
+const SHARD_COUNT: usize = 64;
+
+/// Entries sharing an index: the first one is kept for the deepest search,
+/// the second one always takes the newest entry
+type Bucket = [Option<TTEntry>; 2];
 
 /// Type of bound stored in the transposition table entry
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -10,6 +19,16 @@ pub enum BoundType {
     UpperBound,
 }
 
+/// Key of a position in the transposition table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TTKey {
+    /// Zobrist hash of the position, indexes the table
+    pub hash: u64,
+    /// Second key of the position (its occupancy), two positions with the
+    /// same hash but different signatures are a hash collision
+    pub signature: u64,
+}
+
 #[derive(Debug, Clone)]
 pub enum ProbeResult {
     Score(f32),
@@ -20,8 +39,8 @@ pub enum ProbeResult {
 /// Entry in the transposition table
 #[derive(Debug, Clone)]
 pub struct TTEntry {
-    /// Zobrist hash of the position
-    pub hash: u64,
+    /// Key of the position
+    pub key: TTKey,
     /// Best move found for this position
     pub best_move: Option<PlayerMove>,
     /// Evaluation score
@@ -38,7 +57,7 @@ pub struct TTEntry {
 
 impl TTEntry {
     pub fn new(
-        hash: u64,
+        key: TTKey,
         best_move: Option<PlayerMove>,
         score: f32,
         depth: usize,
@@ -47,7 +66,7 @@ impl TTEntry {
         age: u32,
     ) -> Self {
         Self {
-            hash,
+            key,
             best_move,
             score,
             depth,
@@ -59,25 +78,61 @@ impl TTEntry {
 }
 
 /// Transposition Table for storing search results
+///
+/// A fixed array of buckets of two entries, allocated once, indexed by the
+/// low bits of the hash. The entries can be shared between several searches,
+/// see `share`. The hit and miss counters are kept by each handle.
 pub struct TranspositionTable {
-    table: HashMap<u64, TTEntry>,
-    max_size: usize,
-    current_age: u32,
+    shards: Arc<[Mutex<Vec<Bucket>>]>,
+    /// Number of buckets of each shard, a power of two
+    shard_buckets: usize,
+    current_age: Arc<AtomicU32>,
     hits: u64,
     misses: u64,
 }
 
 impl TranspositionTable {
+    /// Creates a table of at most `max_size` entries, rounded down to a
+    /// power of two (at least one bucket per shard)
     pub fn with_capacity(max_size: usize) -> Self {
+        let buckets = (max_size / 2).max(SHARD_COUNT);
+        let shard_buckets = 1 << (usize::BITS - 1 - (buckets / SHARD_COUNT).leading_zeros());
+        let shards = (0..SHARD_COUNT)
+            .map(|_| Mutex::new(vec![[None, None]; shard_buckets]))
+            .collect();
+        Self {
+            shards,
+            shard_buckets,
+            current_age: Arc::new(AtomicU32::new(0)),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns a handle on the same entries, for a search running in
+    /// parallel: what one search stores, the others can probe
+    pub fn share(&self) -> Self {
         Self {
-            table: HashMap::with_capacity(max_size),
-            max_size,
-            current_age: 0,
+            shards: Arc::clone(&self.shards),
+            shard_buckets: self.shard_buckets,
+            current_age: Arc::clone(&self.current_age),
             hits: 0,
             misses: 0,
         }
     }
 
+    /// Locks the shard holding `hash`, and returns the index of its bucket
+    /// in the shard
+    fn shard(&self, hash: u64) -> (MutexGuard<'_, Vec<Bucket>>, usize) {
+        // The low bits index the bucket in the shard, the high ones pick it.
+        // A search panicking while holding the lock leaves valid entries
+        let shard_index = (hash >> (u64::BITS - SHARD_COUNT.trailing_zeros())) as usize;
+        let shard = self.shards[shard_index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        (shard, hash as usize & (self.shard_buckets - 1))
+    }
+
     pub fn with_default_capacity() -> Self {
         Self::with_capacity(1_000_000)
     }
@@ -85,19 +140,29 @@ impl TranspositionTable {
     /// Probe the transposition table for a position
     ///
     /// # Parameters
-    /// * `hash` - Zobrist hash of the position
+    /// * `key` - Key of the position, an entry stored with the same hash but
+    ///   an other signature is a collision and is ignored
     /// * `depth` - Remaining depth needed for this search
     /// * `ply` - Distance from root (for mate score adjustment)
     /// * `alpha`, `beta` - Alpha-beta bounds
     pub fn probe(
         &mut self,
-        hash: u64,
+        key: TTKey,
         depth: usize,
         ply: usize,
         alpha: f32,
         beta: f32,
     ) -> ProbeResult {
-        if let Some(entry) = self.table.get(&hash) {
+        let entry = {
+            let (shard, index) = self.shard(key.hash);
+            shard[index]
+                .iter()
+                .flatten()
+                .find(|entry| entry.key == key)
+                .cloned()
+        };
+
+        if let Some(entry) = entry {
             // Check if stored search was deep enough
             let has_usable_score = entry.depth >= depth;
 
@@ -144,7 +209,7 @@ impl TranspositionTable {
     /// Store an entry in the transposition table
     ///
     /// # Parameters
-    /// * `hash` - Zobrist hash of the position
+    /// * `key` - Key of the position
     /// * `best_move` - Best move found
     /// * `score` - Search score
     /// * `depth` - Remaining depth that was searched
@@ -152,17 +217,25 @@ impl TranspositionTable {
     /// * `bound_type` - Type of bound
     pub fn store(
         &mut self,
-        hash: u64,
+        key: TTKey,
         best_move: Option<PlayerMove>,
         score: f32,
         depth: usize,
         ply: usize,
         bound_type: BoundType,
     ) {
-        // Improved replacement logic considering both depth and ply
-        if let Some(existing) = self.table.get(&hash) {
+        let current_age = self.current_age.load(Ordering::Relaxed);
+        let (mut shard, index) = self.shard(key.hash);
+        let bucket = &mut shard[index];
+
+        // A deeper result of the same position, from a recent search, stays
+        let same_position = bucket
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|entry| entry.key == key));
+        if let Some(slot) = same_position {
+            let existing = bucket[slot].as_ref().expect("slot holds the position");
             let should_keep_existing =
-                existing.depth > depth + 1 && existing.age >= self.current_age.saturating_sub(1);
+                existing.depth > depth + 1 && existing.age >= current_age.saturating_sub(1);
 
             if should_keep_existing {
                 return;
@@ -173,22 +246,26 @@ impl TranspositionTable {
         let adjusted_score = self.adjust_mate_score_for_tt(score, ply);
 
         let entry = TTEntry::new(
-            hash,
+            key,
             best_move,
             adjusted_score,
             depth,
             ply,
             bound_type,
-            self.current_age,
+            current_age,
         );
 
-        // Check if we need to make room
-        if self.table.len() >= self.max_size && !self.table.contains_key(&hash) {
-            self.make_room();
+        // The first slot only gives way to a search as deep, or when its
+        // entry is from an older search; the second one takes the rest
+        let slot = same_position.unwrap_or(match &bucket[0] {
+            Some(first) if first.depth > depth && first.age == current_age => 1,
+            _ => 0,
+        });
+        if slot == 0 && same_position.is_none() {
+            // The entry pushed out of the first slot is still the newest of the second
+            bucket[1] = bucket[0].take();
         }
-
-        // Store the entry (will replace if key already exists)
-        self.table.insert(hash, entry);
+        bucket[slot] = Some(entry);
     }
 
     /// Adjust mate scores when storing to TT (relative to current position)
@@ -217,28 +294,29 @@ impl TranspositionTable {
         }
     }
 
-    /// Make room in the table by removing old/shallow entries
-    fn make_room(&mut self) {
-        // Improved replacement scheme considering depth, ply, and age
-        if let Some((&worst_hash, _)) = self
-            .table
-            .iter()
-            .min_by_key(|(_, entry)| (entry.age, entry.depth, std::cmp::Reverse(entry.ply)))
-        {
-            self.table.remove(&worst_hash);
-        }
-    }
-
-    /// Clear the transposition table
+    /// Clear the transposition table, for every handle sharing it
     pub fn clear(&mut self) {
-        self.table.clear();
+        for shard in self.shards.iter() {
+            shard
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .fill([None, None]);
+        }
         self.hits = 0;
         self.misses = 0;
     }
 
-    /// Advance to next generation (call at start of new search)
+    /// Advance to next generation (call at start of new search), for every
+    /// handle sharing the table
     pub fn new_search(&mut self) {
-        self.current_age += 1;
+        self.current_age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Adds the hits and misses of an other handle on the same table
+    #[cfg(feature = "parallel")]
+    pub(crate) fn add_counters(&mut self, other: &TranspositionTable) {
+        self.hits += other.hits;
+        self.misses += other.misses;
     }
 
     /// Get hit rate for debugging
@@ -252,7 +330,20 @@ impl TranspositionTable {
 
     /// Get table statistics
     pub fn stats(&self) -> (usize, u64, u64, f64) {
-        (self.table.len(), self.hits, self.misses, self.hit_rate())
+        let len = self
+            .shards
+            .iter()
+            .map(|shard| {
+                shard
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .count()
+            })
+            .sum();
+        (len, self.hits, self.misses, self.hit_rate())
     }
 }
 
@@ -266,3 +357,147 @@ pub fn get_bound_type(score: f32, alpha: f32, beta: f32) -> BoundType {
         BoundType::Exact // PV node
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Engine;
+
+    #[test]
+    fn signature_mismatch_prevents_a_cutoff() {
+        let position = Engine::new();
+        let mut other = Engine::new();
+        other.play_san("e4").unwrap();
+
+        // Both positions forced on the same hash, only the signature differs
+        let stored_key = TTKey {
+            hash: 0x1234,
+            signature: position.get_board().bitboard(),
+        };
+        let colliding_key = TTKey {
+            hash: 0x1234,
+            signature: other.get_board().bitboard(),
+        };
+
+        let mut tt = TranspositionTable::with_capacity(1 << 10);
+        tt.store(stored_key, None, 5., 4, 0, BoundType::Exact);
+
+        assert!(matches!(
+            tt.probe(colliding_key, 1, 0, f32::NEG_INFINITY, f32::INFINITY),
+            ProbeResult::Miss
+        ));
+        assert!(matches!(
+            tt.probe(stored_key, 1, 0, f32::NEG_INFINITY, f32::INFINITY),
+            ProbeResult::Score(score) if score == 5.
+        ));
+    }
+
+    /// Keys of positions sharing a bucket, told apart by their signature
+    fn same_bucket_key(signature: u64) -> TTKey {
+        TTKey {
+            hash: 0x1234,
+            signature,
+        }
+    }
+
+    fn stored_score(tt: &mut TranspositionTable, key: TTKey) -> Option<f32> {
+        match tt.probe(key, 0, 0, f32::NEG_INFINITY, f32::INFINITY) {
+            ProbeResult::Score(score) => Some(score),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn the_first_slot_keeps_the_deepest_entry_of_the_search() {
+        let [deep, shallow, newer] = [1, 2, 3].map(same_bucket_key);
+        let mut tt = TranspositionTable::with_capacity(1 << 10);
+
+        tt.store(deep, None, 1., 6, 0, BoundType::Exact);
+        tt.store(shallow, None, 2., 2, 0, BoundType::Exact);
+        // The second slot always takes the newest entry
+        tt.store(newer, None, 3., 3, 0, BoundType::Exact);
+
+        assert_eq!(stored_score(&mut tt, deep), Some(1.));
+        assert_eq!(stored_score(&mut tt, shallow), None);
+        // Found in the second slot
+        assert_eq!(stored_score(&mut tt, newer), Some(3.));
+        let (shard, index) = tt.shard(deep.hash);
+        assert_eq!(shard[index][0].as_ref().unwrap().key, deep);
+        assert_eq!(shard[index][1].as_ref().unwrap().key, newer);
+    }
+
+    #[test]
+    fn entries_of_older_searches_give_way_to_shallower_ones() {
+        let [old, new] = [1, 2].map(same_bucket_key);
+        let mut tt = TranspositionTable::with_capacity(1 << 10);
+
+        tt.store(old, None, 1., 6, 0, BoundType::Exact);
+        tt.new_search();
+        tt.store(new, None, 2., 1, 0, BoundType::Exact);
+
+        // The old entry moved to the second slot, where it is still found
+        let (shard, index) = tt.shard(old.hash);
+        assert_eq!(shard[index][0].as_ref().unwrap().key, new);
+        assert_eq!(shard[index][1].as_ref().unwrap().key, old);
+        drop(shard);
+        assert_eq!(stored_score(&mut tt, old), Some(1.));
+        assert_eq!(stored_score(&mut tt, new), Some(2.));
+    }
+
+    #[test]
+    fn a_deeper_result_of_the_same_position_stays_for_one_search() {
+        let key = same_bucket_key(1);
+        let mut tt = TranspositionTable::with_capacity(1 << 10);
+
+        tt.store(key, None, 1., 6, 0, BoundType::Exact);
+        tt.store(key, None, 2., 2, 0, BoundType::Exact);
+        assert_eq!(stored_score(&mut tt, key), Some(1.));
+
+        // Still kept during the next search, replaced after
+        tt.new_search();
+        tt.store(key, None, 2., 2, 0, BoundType::Exact);
+        assert_eq!(stored_score(&mut tt, key), Some(1.));
+        tt.new_search();
+        tt.store(key, None, 2., 2, 0, BoundType::Exact);
+        assert_eq!(stored_score(&mut tt, key), Some(2.));
+
+        // A search almost as deep replaces it at once
+        tt.store(key, None, 3., 3, 0, BoundType::Exact);
+        assert_eq!(stored_score(&mut tt, key), Some(3.));
+        assert_eq!(tt.stats().0, 1);
+    }
+
+    #[test]
+    fn capacity_is_rounded_down_to_a_power_of_two() {
+        let entries = |tt: &TranspositionTable| tt.shards.len() * tt.shard_buckets * 2;
+        for (max_size, expected) in [
+            (1 << 10, 1 << 10),
+            (1000, 512),
+            (1_000_000, 1 << 19),
+            // At least one bucket per shard
+            (0, 2 * SHARD_COUNT),
+        ] {
+            let tt = TranspositionTable::with_capacity(max_size);
+            assert!(tt.shard_buckets.is_power_of_two(), "{max_size}");
+            assert_eq!(entries(&tt), expected, "{max_size}");
+        }
+    }
+
+    #[test]
+    fn the_table_never_holds_more_than_its_capacity() {
+        let max_size = 1000;
+        let mut tt = TranspositionTable::with_capacity(max_size);
+        let mut hash: u64 = 0x9E37_79B9_7F4A_7C15;
+        for signature in 0..10 * max_size as u64 {
+            hash = hash.rotate_left(5).wrapping_mul(0x2545_F491_4F6C_DD1D);
+            tt.store(TTKey { hash, signature }, None, 0., 1, 0, BoundType::Exact);
+        }
+
+        let (len, _, _, _) = tt.stats();
+        assert!(len <= max_size, "{len}");
+        // Every shard is still its first allocation
+        for shard in tt.shards.iter() {
+            assert_eq!(shard.lock().unwrap().capacity(), tt.shard_buckets);
+        }
+    }
+}
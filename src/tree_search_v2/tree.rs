@@ -1,31 +1,282 @@
 use core::f32;
 use derive_builder::Builder;
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::game_engine::game_states::TerminalReason;
+use crate::game_engine::packed_move::PackedMove;
+use crate::game_engine::utility::get_color;
 use crate::pieces::Piece;
 use crate::prelude::evaluators::utility::get_value_by_piece;
 use crate::prelude::{Engine, PlayerMove};
 use crate::static_evaluation::evaluator_trait::Evaluator;
+use crate::static_evaluation::see::see_move;
 use crate::static_evaluation::values;
 
+use super::search_info::SearchInfo;
 use super::search_result::SearchResult;
-use super::transposition_table::{get_bound_type, ProbeResult, TranspositionTable};
+#[cfg(feature = "tablebase")]
+use super::tablebase::{Tablebase, Wdl};
+use super::transposition_table::{
+    get_bound_type, BoundType, ProbeResult, TTKey, TranspositionTable,
+};
 use super::tree_node::NodeHandle;
 use super::tree_node_pool::TreeNodePool;
 
+/// Number of visited nodes between two checks of the deadline
+const NODES_BETWEEN_TIME_CHECKS: usize = 256;
+
+/// Default hard cap on the recursion of `negamax`
+const DEFAULT_MAX_PLY: usize = 128;
+
+/// Default slack added to the exchange of a capture before it is skipped by the
+/// quiescence search, a pawn in evaluation units
+const DEFAULT_QSEARCH_SEE_MARGIN: f32 = 10.;
+
+/// Default quiescence depth after which hopeless captures are skipped
+const DEFAULT_QSEARCH_BAD_CAPTURE_DEPTH: usize = 2;
+
+/// Depth reduction of the search after a null move
+const NULL_MOVE_REDUCTION: usize = 2;
+
+/// Width of the null windows of the search (null move, PVS), a search with
+/// such a window only tells whether the score is above a bound
+const NULL_WINDOW: f32 = 0.01;
+
+/// Default number of children searched at full depth before late moves are reduced
+const DEFAULT_LATE_MOVE_REDUCTION_THRESHOLD: usize = 3;
+
+/// Default depth reduction of late quiet moves
+const DEFAULT_LATE_MOVE_REDUCTION: usize = 1;
+
+/// Ordering bonus of the two killer moves of a ply, in score units
+const KILLER_MOVE_BONUSES: [f32; 2] = [20., 15.];
+
+/// Ordering bonus of the quiet move with the highest history, the others get
+/// a share of it proportional to their history
+const MAX_HISTORY_BONUS: f32 = 10.;
+
+/// Positions searched by `bench`: opening, middlegames with both castles,
+/// tactics, and endgames with promotions
+const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/8/4k3/8/8/3KP3/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+/// Receives the progress of `iterative_search` after each completed depth
+pub type IterationCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+
+/// Receives the transposition table at the end of each search, to report
+/// its usage (`TranspositionTable::stats`)
+pub type TableStatsCallback = Box<dyn FnMut(&TranspositionTable) + Send>;
+
+/// A `TreeSearch` choosing its evaluator at runtime, at the cost of a
+/// virtual call for every evaluation
+pub type DynTreeSearch = TreeSearch<Box<dyn Evaluator + Send>>;
+
+/// Searches the best move of a position with `E` as static evaluation.
+/// A concrete evaluator is inlined in the search, see `DynTreeSearch` to
+/// pick one at runtime.
 #[derive(Builder)]
 #[builder(pattern = "owned")]
-pub struct TreeSearch {
+pub struct TreeSearch<E: Evaluator> {
     pool: TreeNodePool,
     tt: TranspositionTable,
-    evaluator: Box<dyn Evaluator>,
+    evaluator: E,
     max_depth: usize,
     max_q_depth: usize,
 
     #[builder(default = 0.)]
     window_margin: f32,
+
+    /// Half width of the root window around the score of the previous
+    /// iteration. When the root score falls outside of it, the score is
+    /// reported as a bound (see `SearchResult::bound`).
+    /// Default: infinite (full window)
+    #[builder(default = "f32::INFINITY")]
+    aspiration_window: f32,
+
+    /// Whether a depth whose root score falls outside of the aspiration
+    /// window is searched again with a full window, to get an exact score.
+    /// Default: true
+    #[builder(default = "true")]
+    aspiration_research: bool,
+
+    /// Wall-clock budget of a search. The search can stop in the middle of
+    /// an iteration, the result is then the one of the last completed depth.
+    /// Default: None (no time limit)
+    #[builder(default, setter(strip_option))]
+    max_time: Option<Duration>,
+
+    /// Budget of visited nodes of a search (UCI `go nodes`). As with the
+    /// time limit, the result is the one of the last completed depth.
+    /// Default: None (no node limit)
+    #[builder(default, setter(strip_option))]
+    max_nodes: Option<usize>,
+
+    /// Hard cap on the distance from the root, whatever the remaining depth.
+    /// Nodes at this ply return their static evaluation, so search
+    /// extensions can never recurse deep enough to overflow the stack.
+    /// Default: 128
+    #[builder(default = "DEFAULT_MAX_PLY")]
+    max_ply: usize,
+
+    /// Slack of the quiescence search: past `qsearch_bad_capture_depth`, a
+    /// capture is skipped when the material it wins (static exchange
+    /// evaluation) plus this margin can't bring the stand pat back to alpha.
+    /// Higher searches more captures. Captures losing material are always
+    /// skipped. In evaluation units, the same as the scores.
+    /// Default: 10 (a pawn)
+    #[builder(default = "DEFAULT_QSEARCH_SEE_MARGIN")]
+    qsearch_see_margin: f32,
+
+    /// Quiescence depth from which hopeless captures are skipped, shallower
+    /// captures are searched unless they lose material.
+    /// Default: 2
+    #[builder(default = "DEFAULT_QSEARCH_BAD_CAPTURE_DEPTH")]
+    qsearch_bad_capture_depth: usize,
+
+    /// Whether a node is cut when passing the turn (null move) and searching
+    /// `NULL_MOVE_REDUCTION` plies shallower still beats beta. Skipped in
+    /// check, near mate scores and when the side to move only has pawns
+    /// (zugzwang is likely).
+    /// Default: true
+    #[builder(default = "true")]
+    null_move_pruning: bool,
+
+    /// Whether the children after the first one are searched with a null
+    /// window, to prove they are worse than the first (principal variation
+    /// search). Only a child that turns out better is searched again with
+    /// the full window.
+    /// Default: true
+    #[builder(default = "true")]
+    principal_variation_search: bool,
+
+    /// Whether quiet moves that caused a beta cutoff are tried early in the
+    /// other nodes, with the killer and history tables
+    /// Default: true
+    #[builder(default = "true")]
+    quiet_move_ordering: bool,
+
+    /// Number of children searched at full depth before the late ones are
+    /// reduced. With a good ordering, the best move is rarely found later.
+    /// Default: 3
+    #[builder(default = "DEFAULT_LATE_MOVE_REDUCTION_THRESHOLD")]
+    late_move_reduction_threshold: usize,
+
+    /// Plies removed from the search of a late quiet move (not a capture, a
+    /// check nor a promotion, and not out of check). The move is searched
+    /// again at full depth if the reduced search raises alpha. 0 disables
+    /// the reductions.
+    /// Default: 1
+    #[builder(default = "DEFAULT_LATE_MOVE_REDUCTION")]
+    late_move_reduction: usize,
+
+    /// Whether the children of a node in check are searched at the depth of
+    /// the node instead of one ply shallower, so forced mates aren't cut by
+    /// the horizon. Nodes further from the root than the depth of the
+    /// iteration aren't extended, so a line is at most twice as long.
+    /// Default: true
+    #[builder(default = "true")]
+    check_extensions: bool,
+
+    /// How much a draw is worth less than 0 to the side to play at the root,
+    /// and more to its opponent. A positive contempt makes the engine avoid
+    /// the draws (repetitions, fifty-move rule, stalemates, insufficient
+    /// material) when it believes it's stronger, a negative one look for
+    /// them. Only the score of drawn positions changes, never the
+    /// evaluation of material.
+    /// Default: 0 (a draw is a draw)
+    #[builder(default = 0.)]
+    contempt: f32,
+
+    /// Called after each completed depth of `iterative_search`, with the
+    /// principal variation of that depth. Nothing is reported by default.
+    #[builder(default, setter(custom))]
+    on_iteration: Option<IterationCallback>,
+
+    /// Called at the end of each search with the transposition table, so
+    /// its statistics go wherever the integrator wants. Nothing is reported
+    /// by default.
+    #[builder(default, setter(custom))]
+    on_table_stats: Option<TableStatsCallback>,
+
+    /// Exact results of the positions with few pieces, probed at the root
+    /// and at every node with at most `Tablebase::max_pieces` pieces.
+    /// Default: None (nothing is probed)
+    #[cfg(feature = "tablebase")]
+    #[builder(default, setter(strip_option))]
+    tablebase: Option<Box<dyn Tablebase + Send>>,
+
+    /// Number of searches `parallel_search` runs at the same time, each on
+    /// its share of the root moves.
+    /// Default: 1
+    #[cfg(feature = "parallel")]
+    #[builder(default = "1")]
+    threads: usize,
+
+    /// Node pools of the searches of `parallel_search`, kept from one
+    /// search to the next
+    #[cfg(feature = "parallel")]
+    #[builder(setter(skip))]
+    worker_pools: Vec<TreeNodePool>,
+
+    /// Root moves `search_restricted` is limited to, `None` when every move
+    /// is searched
+    #[builder(setter(skip))]
+    root_moves: Option<Vec<PlayerMove>>,
+
+    /// Two quiet moves per ply that caused a beta cutoff, most recent first
+    #[builder(setter(skip))]
+    killer_moves: Vec<[Option<PlayerMove>; 2]>,
+
+    /// Butterfly table: for each (from, to) squares of a quiet move, how
+    /// much it caused beta cutoffs, weighted by the depth left
+    #[builder(setter(skip))]
+    history: Vec<u32>,
+
+    /// Highest value of `history`, the history bonus is relative to it
+    #[builder(setter(skip))]
+    max_history: u32,
+
+    /// Hashes of the positions from the root to the node being searched,
+    /// a position met again on this path is scored as a draw
+    #[builder(setter(skip))]
+    path_hashes: Vec<u64>,
+
+    /// Side to play at the root, the one `contempt` applies to
+    #[builder(setter(skip))]
+    root_white_to_play: bool,
+
+    /// Depth of the current iteration of the iterative deepening
+    #[builder(setter(skip))]
+    iteration_depth: usize,
+
+    #[builder(setter(skip))]
+    deadline: Option<Instant>,
+
+    #[builder(setter(skip))]
+    visited_nodes: usize,
 }
 
-impl TreeSearchBuilder {
+/// A `TreeSearch` only holds handles into its node pool, never shared
+/// pointers, so a prepared search can be moved to a worker thread. This
+/// fails to compile if a field ever breaks that.
+fn _assert_tree_search_is_send() {
+    fn is_send<T: Send>() {}
+    is_send::<DynTreeSearch>();
+    is_send::<Engine>();
+}
+
+impl<E: Evaluator> TreeSearchBuilder<E> {
     pub fn pool_capacity(mut self, capacity: usize) -> Self {
         self.pool = Some(TreeNodePool::with_capacity(capacity));
         self
@@ -35,73 +286,304 @@ impl TreeSearchBuilder {
         self.tt = Some(TranspositionTable::with_capacity(capacity));
         self
     }
+
+    pub fn on_iteration<F: FnMut(&SearchInfo) + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_iteration = Some(Some(Box::new(callback)));
+        self
+    }
+
+    pub fn on_table_stats<F: FnMut(&TranspositionTable) + Send + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.on_table_stats = Some(Some(Box::new(callback)));
+        self
+    }
 }
 
-impl TreeSearch {
+impl<E: Evaluator> TreeSearch<E> {
     pub fn iterative_search(&mut self, position: Engine) -> Option<SearchResult> {
+        self.tt.new_search();
+        let result = self.search_iteratively(position);
+        self.report_table_stats();
+        result
+    }
+
+    fn report_table_stats(&mut self) {
+        if let Some(callback) = self.on_table_stats.as_mut() {
+            callback(&self.tt);
+        }
+    }
+
+    /// Iterative deepening from `position`, the entries of the transposition
+    /// table stored by this search belong to the current generation
+    fn search_iteratively(&mut self, position: Engine) -> Option<SearchResult> {
+        let start = Instant::now();
+        self.deadline = self.max_time.map(|max_time| start + max_time);
+        self.visited_nodes = 0;
+
         // Clear pool for new search
         self.pool.clear();
-        self.tt.new_search();
+
+        // Ordering hints are only valid for the position they were found in
+        self.killer_moves.clear();
+        self.history.clear();
+        self.history.resize(64 * 64, 0);
+        self.max_history = 0;
+        self.path_hashes.clear();
+        self.root_white_to_play = position.white_to_play();
+
+        // A known endgame doesn't need a search
+        #[cfg(feature = "tablebase")]
+        if let Some(result) = self.probe_tablebase_root(&position) {
+            return Some(result);
+        }
 
         // Create root node
-        let root = self.pool.allocate_node(position, 0.0, None, None, None)?;
+        let root = self.pool.allocate_node(
+            position.zobrist_hash(),
+            position.is_king_checked(),
+            0.0,
+            None,
+            None,
+            None,
+        )?;
 
         // Init best score
         let mut score = 0.;
+        let mut bound = BoundType::Exact;
+        let mut best_move = None;
         let mut depth_reached = 0;
         let mut node_count_reached = 0;
 
         // Iterative deepening
         for i_depth in 1..=self.max_depth {
-            if let Ok(dscore) = self.negamax(root, i_depth, 0, f32::NEG_INFINITY, f32::INFINITY) {
+            // First iteration has no previous score to center the window on
+            let (mut alpha, mut beta) = if i_depth == 1 {
+                (f32::NEG_INFINITY, f32::INFINITY)
+            } else {
+                (score - self.aspiration_window, score + self.aspiration_window)
+            };
+
+            self.iteration_depth = i_depth;
+            let mut iteration = self.negamax(root, &position, i_depth, 0, alpha, beta);
+
+            // The window failed, the depth is searched again without it.
+            // Nodes of both searches are counted, they were all visited
+            if let Ok(dscore) = iteration {
+                if self.aspiration_research
+                    && get_bound_type(dscore, alpha, beta) != BoundType::Exact
+                {
+                    (alpha, beta) = (f32::NEG_INFINITY, f32::INFINITY);
+                    iteration = self.negamax(root, &position, i_depth, 0, alpha, beta);
+                }
+            }
+
+            if let Ok(dscore) = iteration {
                 score = dscore;
+                bound = get_bound_type(dscore, alpha, beta);
                 depth_reached = i_depth;
-                node_count_reached = self.pool.len();
+                node_count_reached = self.visited_nodes;
+
+                // Keep the move of this iteration, an interrupted
+                // iteration leaves the tree with partial scores
+                best_move = self.get_best_move(root, &position);
+
+                // The PV is only extracted when someone listens
+                if let Some(chess_move) = best_move.filter(|_| self.on_iteration.is_some()) {
+                    let pv = self.extract_principal_variation(root, chess_move, i_depth);
+                    let info = SearchInfo::new(
+                        i_depth,
+                        score,
+                        bound,
+                        node_count_reached,
+                        start.elapsed(),
+                        pv,
+                    )
+                    .with_centipawns_per_unit(self.evaluator.centipawns_per_unit());
+                    if let Some(callback) = self.on_iteration.as_mut() {
+                        callback(&info);
+                    }
+                }
             } else {
                 break;
             }
         }
 
-        // Extract principale variation
-        let best_move = self.get_best_move(root)?;
+        // Not even the first iteration completed, partial scores are all we have
+        let best_move = best_move.or_else(|| self.get_best_move(root, &position))?;
+        let pv = self.extract_principal_variation(root, best_move, depth_reached.max(1));
         let max_qdepth = self.get_tree_max_depth(root);
-        Some(SearchResult::new(
+        let result = SearchResult::new(
             best_move,
             score,
+            bound,
             depth_reached.into(),
             max_qdepth,
             node_count_reached,
-        ))
+            pv,
+        );
+        Some(result.with_centipawns_per_unit(self.evaluator.centipawns_per_unit()))
+    }
+
+    /// Changes the depth limit of the next searches
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Changes the time budget of the next searches, `None` removes it
+    pub fn set_max_time(&mut self, max_time: Option<Duration>) {
+        self.max_time = max_time;
     }
 
+    /// Changes the tablebase probed by the next searches, `None` removes it
+    #[cfg(feature = "tablebase")]
+    pub fn set_tablebase(&mut self, tablebase: Option<Box<dyn Tablebase + Send>>) {
+        self.tablebase = tablebase;
+    }
+
+    /// Forgets every stored position, for a new game (UCI `ucinewgame`)
+    pub fn clear_transposition_table(&mut self) {
+        self.tt.clear();
+    }
+
+    /// Same as `iterative_search`, but only the moves in `allowed` are
+    /// considered at the root (UCI `go searchmoves`)
+    ///
+    /// # Returns
+    /// `None` if none of the allowed moves is legal in `position`
+    pub fn search_restricted(
+        &mut self,
+        position: Engine,
+        allowed: &[PlayerMove],
+    ) -> Option<SearchResult> {
+        // A root without any child would be scored as a mate or a stalemate
+        let has_legal_move = position
+            .legal_moves()
+            .iter()
+            .any(|chess_move| allowed.contains(chess_move));
+        if !has_legal_move {
+            return None;
+        }
+
+        self.root_moves = Some(allowed.to_vec());
+        let result = self.iterative_search(position);
+        self.root_moves = None;
+        result
+    }
+
+    /// Searches a fixed set of positions to `depth` and returns the total
+    /// number of visited nodes
+    ///
+    /// The count only depends on the search settings and the code, any
+    /// change to pruning or move ordering changes it, which makes it a cheap
+    /// regression signal. The transposition table is cleared before every
+    /// position and the time limit is ignored.
+    pub fn bench(&mut self, depth: usize) -> u64 {
+        let max_depth = std::mem::replace(&mut self.max_depth, depth);
+        let max_time = self.max_time.take();
+
+        let mut node_count = 0;
+        for fen in BENCH_POSITIONS {
+            let position = Engine::from_fen(fen).expect("bench positions are valid FENs");
+            self.tt.clear();
+            if let Some(result) = self.iterative_search(position) {
+                node_count += result.node_count() as u64;
+            }
+        }
+
+        self.max_depth = max_depth;
+        self.max_time = max_time;
+        node_count
+    }
+
+    /// Searches a node, keeping its position on the path while its children
+    /// are searched
+    ///
+    /// A position already on the path is a repetition: whoever can repeat
+    /// can repeat forever (perpetual check), so it is scored as a draw.
     fn negamax(
         &mut self,
         node_handle: NodeHandle,
+        position: &Engine,
+        depth: usize,
+        ply: usize,
+        alpha: f32,
+        beta: f32,
+    ) -> Result<f32, ()> {
+        if let Some(score) = self.path_repetition_score(node_handle, position)? {
+            return Ok(score);
+        }
+
+        let hash = self.pool.get_node(node_handle).ok_or(())?.get_hash();
+        self.path_hashes.push(hash);
+        let score = self.negamax_node(node_handle, position, depth, ply, alpha, beta);
+        self.path_hashes.pop();
+        score
+    }
+
+    fn negamax_node(
+        &mut self,
+        node_handle: NodeHandle,
+        position: &Engine,
         depth: usize,
         ply: usize,
         mut alpha: f32,
         beta: f32,
     ) -> Result<f32, ()> {
+        self.check_deadline()?;
+
+        // Safety cap, independent from the depth left
+        if ply >= self.max_ply {
+            let static_eval = self.pool.get_node(node_handle).ok_or(())?.get_score();
+            self.pool
+                .get_node_mut(node_handle)
+                .ok_or(())?
+                .set_best_score(static_eval);
+            return Ok(static_eval);
+        }
+
         // TT handling
         let original_alpha = alpha;
         let mut best_move = None;
         let mut tt_move = None;
-        let hash = self
-            .pool
-            .get_node(node_handle)
-            .ok_or(())?
-            .get_engine()
-            .zobrist_hash();
+        let key = TTKey {
+            hash: self.pool.get_node(node_handle).ok_or(())?.get_hash(),
+            signature: position.get_board().bitboard(),
+        };
 
-        match self.tt.probe(hash, depth, ply, alpha, beta) {
-            ProbeResult::Score(score) => return Ok(score),
+        // The score of a restricted root isn't the score of the position
+        let is_restricted_root = ply == 0 && self.root_moves.is_some();
+
+        // The root never cuts off, its children are needed to pick the best move
+        match self.tt.probe(key, depth, ply, alpha, beta) {
+            ProbeResult::Score(score) if ply > 0 => {
+                // The node keeps the score, the best move is read from it
+                self.pool
+                    .get_node_mut(node_handle)
+                    .ok_or(())?
+                    .set_best_score(score);
+                return Ok(score);
+            }
             ProbeResult::Move(tt_best_move) => tt_move = Some(tt_best_move),
             _ => {}
         }
 
+        // An exact result replaces the search below the node
+        #[cfg(feature = "tablebase")]
+        if ply > 0 {
+            if let Some(score) = self.probe_tablebase(position, ply) {
+                self.pool
+                    .get_node_mut(node_handle)
+                    .ok_or(())?
+                    .set_best_score(score);
+                return Ok(score);
+            }
+        }
+
         let final_score = if depth == 0 {
             if self.is_tactical_node(node_handle) {
-                self.quiescence_search(node_handle, alpha, beta, 0)?
+                self.quiescence_node(node_handle, position, alpha, beta, 0)?
             } else {
                 let static_eval = self.pool.get_node(node_handle).ok_or(())?.get_score();
                 self.pool
@@ -111,6 +593,16 @@ impl TreeSearch {
                 static_eval
             }
         } else {
+            if self.try_null_move_cutoff(node_handle, position, depth, ply, beta)? {
+                self.tt
+                    .store(key, None, beta, depth, ply, BoundType::LowerBound);
+                self.pool
+                    .get_node_mut(node_handle)
+                    .ok_or(())?
+                    .set_best_score(beta);
+                return Ok(beta);
+            }
+
             // Generate children if needed
             if !self
                 .pool
@@ -118,10 +610,14 @@ impl TreeSearch {
                 .expect("valid handle")
                 .has_children_computed()
             {
-                self.generate_children(node_handle)?;
+                self.generate_children(node_handle, position, ply == 0)?;
             }
 
-            let children = self.get_children_sorted_by_score(node_handle, tt_move)?;
+            let mut children =
+                self.get_children_sorted_by_score(node_handle, position, tt_move, Some(ply))?;
+            if is_restricted_root {
+                children.retain(|&child_handle| self.is_allowed_root_move(child_handle));
+            }
 
             if children.is_empty() {
                 // Terminal position
@@ -132,17 +628,59 @@ impl TreeSearch {
             } else {
                 // Main search loop
                 let mut best_score = f32::NEG_INFINITY;
+                let child_depth = if self.extends_check(position, ply) {
+                    depth
+                } else {
+                    depth - 1
+                };
 
-                for child_handle in children {
+                for (index, child_handle) in children.into_iter().enumerate() {
                     let widened_alpha = alpha - self.window_margin;
                     let widened_beta = beta + self.window_margin;
-                    let score = -self.negamax(
-                        child_handle,
-                        depth - 1,
-                        ply + 1,
-                        -widened_beta,
-                        -widened_alpha,
-                    )?;
+                    let child_position = self.child_position(position, child_handle)?;
+
+                    let mut score = f32::NEG_INFINITY;
+                    let mut needs_full_search = true;
+
+                    // LMR: a late quiet move is first searched shallower
+                    let reduction =
+                        self.late_move_reduction(node_handle, child_handle, index, depth)?;
+                    if reduction > 0 {
+                        score = -self.negamax(
+                            child_handle,
+                            &child_position,
+                            child_depth - reduction,
+                            ply + 1,
+                            -widened_alpha - NULL_WINDOW,
+                            -widened_alpha,
+                        )?;
+                        needs_full_search = score > widened_alpha;
+                    }
+
+                    // PVS: with a good ordering the first child is the best,
+                    // the others only have to be proven worse
+                    if needs_full_search && index > 0 && self.principal_variation_search {
+                        score = -self.negamax(
+                            child_handle,
+                            &child_position,
+                            child_depth,
+                            ply + 1,
+                            -widened_alpha - NULL_WINDOW,
+                            -widened_alpha,
+                        )?;
+                        needs_full_search = score > widened_alpha && score < widened_beta;
+                    }
+
+                    if needs_full_search {
+                        score = -self.negamax(
+                            child_handle,
+                            &child_position,
+                            child_depth,
+                            ply + 1,
+                            -widened_beta,
+                            -widened_alpha,
+                        )?;
+                    }
 
                     let adjusted_score = if score.abs() > values::MATE_THRESHOLD {
                         if score > 0.0 {
@@ -157,12 +695,13 @@ impl TreeSearch {
                     if adjusted_score > best_score {
                         best_score = adjusted_score;
                         if let Some(child_node) = self.pool.get_node(child_handle) {
-                            best_move = child_node.get_move().clone();
+                            best_move = child_node.get_move();
                         }
                     }
                     alpha = alpha.max(adjusted_score);
 
                     if alpha >= beta {
+                        self.record_cutoff(child_handle, depth, ply);
                         break;
                     }
                 }
@@ -172,9 +711,11 @@ impl TreeSearch {
         };
 
         // Store in transposition table for ALL paths
-        let bound_type = get_bound_type(final_score, original_alpha, beta);
-        self.tt
-            .store(hash, best_move, final_score, depth, ply, bound_type);
+        if !is_restricted_root {
+            let bound_type = get_bound_type(final_score, original_alpha, beta);
+            self.tt
+                .store(key, best_move, final_score, depth, ply, bound_type);
+        }
 
         // Store the best score for this node
         self.pool
@@ -185,13 +726,136 @@ impl TreeSearch {
         Ok(final_score)
     }
 
+    /// Whether the children of a node are searched at its own depth because
+    /// its side to move is in check
+    fn extends_check(&self, position: &Engine, ply: usize) -> bool {
+        self.check_extensions && position.is_king_checked() && ply < self.iteration_depth
+    }
+
+    /// Plies to remove from the search of a child, 0 when it is searched at full depth
+    ///
+    /// # Parameters
+    /// * `node_handle` - Parent node
+    /// * `child_handle` - Child about to be searched
+    /// * `index` - Rank of the child in the ordered children
+    /// * `depth` - Depth left at the parent
+    fn late_move_reduction(
+        &self,
+        node_handle: NodeHandle,
+        child_handle: NodeHandle,
+        index: usize,
+        depth: usize,
+    ) -> Result<usize, ()> {
+        if self.late_move_reduction == 0
+            || index < self.late_move_reduction_threshold
+            || depth < 3
+            || self.pool.get_node(node_handle).ok_or(())?.is_king_checked()
+            || self.is_tactical_node(child_handle)
+        {
+            return Ok(0);
+        }
+
+        // Always leave at least one ply to the reduced search
+        Ok(self.late_move_reduction.min(depth - 2))
+    }
+
+    /// Lets the opponent play twice in a row: if the reduced search of that
+    /// position still beats beta, a real move would too and the node is cut
+    ///
+    /// # Returns
+    /// `true` if the node fails high, `false` if it has to be searched
+    fn try_null_move_cutoff(
+        &mut self,
+        node_handle: NodeHandle,
+        position: &Engine,
+        depth: usize,
+        ply: usize,
+        beta: f32,
+    ) -> Result<bool, ()> {
+        let node = self.pool.get_node(node_handle).ok_or(())?;
+
+        // Only the root and the null move nodes have no move, two null moves
+        // in a row would search the same position
+        let is_null_node = ply > 0 && node.get_move().is_none();
+        if !self.null_move_pruning
+            || ply == 0
+            || is_null_node
+            || depth <= NULL_MOVE_REDUCTION
+            || node.is_king_checked()
+            || beta.abs() >= values::MATE_THRESHOLD
+            || node.get_score() < beta
+        {
+            return Ok(false);
+        }
+
+        // With pawns only, passing is often the best move (zugzwang)
+        let color = get_color(position.white_to_play());
+        let board = position.get_board();
+        let pieces = board.get_bitboard_by_type(Piece::Knight, color)
+            | board.get_bitboard_by_type(Piece::Bishop, color)
+            | board.get_bitboard_by_type(Piece::Rook, color)
+            | board.get_bitboard_by_type(Piece::Queen, color);
+        if pieces == 0 {
+            return Ok(false);
+        }
+
+        let mut null_position = position.clone();
+        if null_position.make_null_move().is_err() {
+            return Ok(false);
+        }
+
+        // The null node isn't a child, it is never picked as a best move
+        let score = self.evaluator.evaluate_engine_state(&null_position, 0);
+        let null_handle = self
+            .pool
+            .allocate_node(null_position.zobrist_hash(), false, score, None, None, None)
+            .ok_or(())?;
+
+        // Passing isn't a legal move, a repetition through it isn't a draw
+        let path_hashes = std::mem::take(&mut self.path_hashes);
+        let null_score = self.negamax(
+            null_handle,
+            &null_position,
+            depth - 1 - NULL_MOVE_REDUCTION,
+            ply + 1,
+            -beta,
+            -beta + NULL_WINDOW,
+        );
+        self.path_hashes = path_hashes;
+
+        Ok(-null_score? >= beta)
+    }
+
+    /// Same as `negamax` for the quiescence search: checks can repeat too
     fn quiescence_search(
         &mut self,
         node_handle: NodeHandle,
+        position: &Engine,
+        alpha: f32,
+        beta: f32,
+        current_q_depth: usize,
+    ) -> Result<f32, ()> {
+        if let Some(score) = self.path_repetition_score(node_handle, position)? {
+            return Ok(score);
+        }
+
+        let hash = self.pool.get_node(node_handle).ok_or(())?.get_hash();
+        self.path_hashes.push(hash);
+        let score = self.quiescence_node(node_handle, position, alpha, beta, current_q_depth);
+        self.path_hashes.pop();
+        score
+    }
+
+    fn quiescence_node(
+        &mut self,
+        node_handle: NodeHandle,
+        position: &Engine,
         mut alpha: f32,
         beta: f32,
         current_q_depth: usize,
     ) -> Result<f32, ()> {
+        self.check_deadline()?;
+
         let stand_pat = self.pool.get_node(node_handle).ok_or(())?.get_score();
 
         if stand_pat >= beta {
@@ -229,10 +893,10 @@ impl TreeSearch {
             .unwrap()
             .has_children_computed()
         {
-            self.generate_children(node_handle)?;
+            self.generate_children(node_handle, position, false)?;
         }
 
-        let children = self.get_children_sorted_by_score(node_handle, None)?;
+        let children = self.get_children_sorted_by_score(node_handle, position, None, None)?;
         if children.is_empty() {
             // Terminal position - return the static evaluation
             return Ok(self
@@ -246,21 +910,29 @@ impl TreeSearch {
 
         for child_handle in children {
             if self.is_tactical_node(child_handle) {
-                //skip obvious bad captures at deeper levels
-                if current_q_depth > 2 {
-                    if let Some(child_node) = self.pool.get_node(child_handle) {
-                        // Skip if this looks like a losing capture
-                        if let Some(captured_piece) = child_node.get_captured_piece() {
-                            let capture_value = get_value_by_piece(captured_piece);
-                            if stand_pat + capture_value + 100.0 < alpha {
-                                continue; // Skip this capture
-                            }
-                        }
+                if let Some(see) = self.plain_capture_see(position, child_handle)? {
+                    // The recapture refutes a capture losing material
+                    if see < 0. {
+                        continue;
+                    }
+
+                    // Deeper, skip captures that can't bring the stand pat
+                    // back to alpha even when the exchange is won
+                    if current_q_depth > self.qsearch_bad_capture_depth
+                        && stand_pat + see + self.qsearch_see_margin < alpha
+                    {
+                        continue;
                     }
                 }
 
-                let score =
-                    -self.quiescence_search(child_handle, -beta, -alpha, current_q_depth + 1)?;
+                let child_position = self.child_position(position, child_handle)?;
+                let score = -self.quiescence_search(
+                    child_handle,
+                    &child_position,
+                    -beta,
+                    -alpha,
+                    current_q_depth + 1,
+                )?;
 
                 best_score = best_score.max(score);
                 alpha = alpha.max(score);
@@ -279,6 +951,117 @@ impl TreeSearch {
         Ok(best_score)
     }
 
+    /// Returns the static exchange evaluation of the move of a child if it
+    /// is a plain capture: no promotion and no check, which are worth more
+    /// than the material they win
+    ///
+    /// # Returns
+    /// `None` for any other child, that is always searched
+    fn plain_capture_see(
+        &self,
+        position: &Engine,
+        child_handle: NodeHandle,
+    ) -> Result<Option<f32>, ()> {
+        let child = self.pool.get_node(child_handle).ok_or(())?;
+        match child.get_move() {
+            Some(chess_move @ PlayerMove::Normal(_))
+                if child.get_captured_piece().is_some() && !child.is_king_checked() =>
+            {
+                Ok(Some(see_move(position, chess_move)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the draw score if the position of a node is already on the
+    /// path from the root, `None` otherwise
+    fn path_repetition_score(
+        &mut self,
+        node_handle: NodeHandle,
+        position: &Engine,
+    ) -> Result<Option<f32>, ()> {
+        let draw_score = self.draw_score(position);
+        let node = self.pool.get_node_mut(node_handle).ok_or(())?;
+        if !self.path_hashes.contains(&node.get_hash()) {
+            return Ok(None);
+        }
+        node.set_best_score(draw_score);
+        Ok(Some(draw_score))
+    }
+
+    /// Score of a draw for the side to play in `position`, `contempt` below
+    /// 0 for the side to play at the root
+    fn draw_score(&self, position: &Engine) -> f32 {
+        if position.white_to_play() == self.root_white_to_play {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// Returns the tablebase score of a position, `None` without tablebase,
+    /// with too many pieces or if the position isn't found
+    #[cfg(feature = "tablebase")]
+    fn probe_tablebase(&self, position: &Engine, ply: usize) -> Option<f32> {
+        let tablebase = self.tablebase.as_ref()?;
+        if position.get_board().bitboard().count_ones() > tablebase.max_pieces() {
+            return None;
+        }
+        tablebase.probe_wdl(position).map(|wdl| match wdl {
+            Wdl::Draw => self.draw_score(position),
+            _ => wdl.score(ply),
+        })
+    }
+
+    /// Returns the tablebase move of the root as a search result, `None`
+    /// when the root isn't in the tablebase or only some moves are allowed
+    #[cfg(feature = "tablebase")]
+    fn probe_tablebase_root(&self, position: &Engine) -> Option<SearchResult> {
+        let tablebase = self.tablebase.as_ref()?;
+        if self.root_moves.is_some()
+            || position.get_board().bitboard().count_ones() > tablebase.max_pieces()
+        {
+            return None;
+        }
+
+        let (best_move, wdl) = tablebase.probe_root(position)?;
+        let result = SearchResult::new(
+            best_move,
+            wdl.score(0),
+            BoundType::Exact,
+            0,
+            0,
+            0,
+            vec![best_move],
+        );
+        Some(result.with_centipawns_per_unit(self.evaluator.centipawns_per_unit()))
+    }
+
+    /// Counts a visited node and, every `NODES_BETWEEN_TIME_CHECKS` nodes,
+    /// checks whether the deadline is over
+    ///
+    /// # Returns
+    /// `Err(())` once the deadline or the node budget is over, which unwinds
+    /// the search the same way a full pool does
+    fn check_deadline(&mut self) -> Result<(), ()> {
+        self.visited_nodes += 1;
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| self.visited_nodes > max_nodes)
+        {
+            return Err(());
+        }
+        match self.deadline {
+            Some(deadline)
+                if self.visited_nodes.is_multiple_of(NODES_BETWEEN_TIME_CHECKS)
+                    && Instant::now() >= deadline =>
+            {
+                Err(())
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn is_tactical_node(&self, handle: NodeHandle) -> bool {
         let node = self
             .pool
@@ -286,7 +1069,7 @@ impl TreeSearch {
             .expect("`is_tactical_node` needs a valid handle");
 
         // Check if the move is a capture or gives check
-        if node.get_engine().is_king_checked() {
+        if node.is_king_checked() {
             true
         } else if node.get_captured_piece().is_some() {
             true
@@ -301,35 +1084,52 @@ impl TreeSearch {
     ///
     /// # Parameters
     /// * `handle` - Node handle for which to generate children
+    /// * `position` - Position represented by the node
+    /// * `is_root` - Whether the node is the root of the search
     ///
     /// # Note
     /// Also handles terminal positions (checkmate/stalemate)
-    fn generate_children(&mut self, handle: NodeHandle) -> Result<(), ()> {
-        // First, get the possible moves
-        let (engine_copy, already_computed) = {
-            let node = self.pool.get_node(handle).ok_or(())?;
-            (node.get_engine(), node.has_children_computed())
-        };
-
+    fn generate_children(
+        &mut self,
+        handle: NodeHandle,
+        position: &Engine,
+        is_root: bool,
+    ) -> Result<(), ()> {
         // Early return if already computed
-        if already_computed {
+        if self.pool.get_node(handle).ok_or(())?.has_children_computed() {
+            return Ok(());
+        }
+
+        // Drawn positions have no continuation, except the root that must
+        // still return a move
+        if let Some(reason) = position.draw_by_rule().filter(|_| !is_root) {
+            self.evaluate_terminal_node(handle, position, reason.into());
+            self.pool.get_node_mut(handle).ok_or(())?.set_computed(true);
             return Ok(());
         }
 
-        // Generate moves from the cloned engine
-        let possible_moves = engine_copy
+        // Generate moves from the node position
+        let possible_moves = position
             .generate_moves_with_engine_state()
             .unwrap_or_default();
 
         // If there is no possible moves, it means it's either stalemate
         // or checkmate
         if possible_moves.is_empty() {
-            self.evaluate_terminal_node(handle);
+            let reason = if position.is_king_checked() {
+                TerminalReason::Checkmate {
+                    winner: get_color(!position.white_to_play()),
+                }
+            } else {
+                TerminalReason::Stalemate
+            };
+            self.evaluate_terminal_node(handle, position, reason);
             return Ok(());
         }
 
         // Collect child handles first
         let mut child_handles = Vec::new();
+        let color = get_color(position.white_to_play());
 
         // Create all child nodes
         for possible_move in possible_moves.into_iter() {
@@ -342,9 +1142,13 @@ impl TreeSearch {
             let child_handle = self
                 .pool
                 .allocate_node(
-                    possible_move.engine,
+                    possible_move.engine.zobrist_hash(),
+                    possible_move.engine.is_king_checked(),
                     score,
-                    Some(possible_move.player_move),
+                    Some(PackedMove::from_player_move(
+                        possible_move.player_move,
+                        color,
+                    )),
                     Some(possible_move.piece),
                     possible_move.captured_piece,
                 )
@@ -363,47 +1167,137 @@ impl TreeSearch {
         Ok(())
     }
 
-    /// Evaluates a terminal node (checkmate or stalemate)
+    /// Evaluates a terminal node and records why it is terminal
     ///
     /// # Parameters
-    /// * `node` - Terminal node to evaluate
+    /// * `handle` - Terminal node to evaluate
+    /// * `position` - Position represented by the node
+    /// * `reason` - Why the game ends at this node
     ///
     /// # Returns
-    /// Score for the terminal position (0 for stalemate, CHECK_MATE_VALUE for checkmate)
-    fn evaluate_terminal_node(&mut self, handle: NodeHandle) -> f32 {
+    /// Score for the terminal position (-CHECK_MATE for checkmate, the
+    /// contempt adjusted draw score for any draw)
+    fn evaluate_terminal_node(
+        &mut self,
+        handle: NodeHandle,
+        position: &Engine,
+        reason: TerminalReason,
+    ) -> f32 {
+        let draw_score = self.draw_score(position);
+
         // Get the node
         let node = self
             .pool
             .get_node_mut(handle)
             .expect("`evaluate_terminal_node` needs a valid handle");
 
-        // if it's terminal node (number of moves == 0)
-        // it means it's either check mate or stale mate
-        if node.get_engine().is_king_checked() {
-            let score = -values::CHECK_MATE;
-            node.set_score(score);
-            node.set_best_score(score);
-            score
+        // The side to move is always the one mated
+        let score = match reason {
+            TerminalReason::Checkmate { .. } => -values::CHECK_MATE,
+            _ => draw_score,
+        };
+        node.set_score(score);
+        node.set_best_score(score);
+        node.set_terminal_reason(reason);
+        score
+    }
+
+    /// Rebuilds the position of a child node by playing its move
+    ///
+    /// The move was generated for the parent position, so it is played
+    /// without checking its legality again.
+    ///
+    /// # Parameters
+    /// * `position` - Position of the parent node
+    /// * `child_handle` - Child node to rebuild the position of
+    fn child_position(&self, position: &Engine, child_handle: NodeHandle) -> Result<Engine, ()> {
+        let chess_move = self
+            .pool
+            .get_node(child_handle)
+            .ok_or(())?
+            .get_move()
+            .ok_or(())?;
+        let mut child_position = position.clone();
+        child_position.play_unchecked(chess_move).map_err(|_| ())?;
+        Ok(child_position)
+    }
+
+    /// Remembers the move of a child that caused a beta cutoff, if it is
+    /// quiet, to try it early in sibling nodes (killer) and everywhere else
+    /// (history)
+    fn record_cutoff(&mut self, child_handle: NodeHandle, depth: usize, ply: usize) {
+        if !self.quiet_move_ordering {
+            return;
+        }
+        let Some(child) = self.pool.get_node(child_handle) else {
+            return;
+        };
+        // Captures and promotions are already ordered first
+        let chess_move = match child.get_move() {
+            Some(chess_move @ PlayerMove::Normal(_)) if child.get_captured_piece().is_none() => {
+                chess_move
+            }
+            _ => return,
+        };
+
+        if self.killer_moves.len() <= ply {
+            self.killer_moves.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killer_moves[ply];
+        if killers[0] != Some(chess_move) {
+            killers[1] = killers[0];
+            killers[0] = Some(chess_move);
+        }
+
+        if let Some(entry) = history_index(chess_move).and_then(|index| self.history.get_mut(index))
+        {
+            *entry = entry.saturating_add((depth * depth) as u32);
+            self.max_history = self.max_history.max(*entry);
+        }
+    }
+
+    /// Ordering bonus of a quiet move from the killer and history tables
+    fn quiet_move_bonus(&self, chess_move: PlayerMove, ply: usize) -> f32 {
+        let killer_bonus = self
+            .killer_moves
+            .get(ply)
+            .and_then(|killers| {
+                killers
+                    .iter()
+                    .position(|killer| *killer == Some(chess_move))
+            })
+            .map_or(0., |rank| KILLER_MOVE_BONUSES[rank]);
+
+        let history = history_index(chess_move)
+            .and_then(|index| self.history.get(index))
+            .copied()
+            .unwrap_or(0);
+        let history_bonus = if self.max_history > 0 {
+            MAX_HISTORY_BONUS * history as f32 / self.max_history as f32
         } else {
-            // This is a stalemate case
-            node.set_score(0.);
-            node.set_best_score(0.);
             0.
-        }
+        };
+
+        killer_bonus + history_bonus
     }
 
     /// Returns sorted children nodes with their evaluation scores
     ///
     /// # Parameters
     /// * `node` - Parent node whose children to sort
-    /// * `shallow_depth` - Depth for preliminary evaluation
+    /// * `position` - Position of the parent node, used by the move heuristic
+    /// * `tt_move` - Move of the transposition table, always tried first
+    /// * `ply` - Distance from the root, quiet moves get the killer and history
+    ///   bonuses when given
     ///
     /// # Returns
     /// Vector of node handles sorted by there score
     fn get_children_sorted_by_score(
         &self,
         handle: NodeHandle,
+        position: &Engine,
         tt_move: Option<PlayerMove>,
+        ply: Option<usize>,
     ) -> Result<Vec<NodeHandle>, ()> {
         let children = self.pool.get_node(handle).ok_or(())?.get_children().clone();
 
@@ -417,16 +1311,24 @@ impl TreeSearch {
                 let player_move = child.get_move().ok_or(())?;
                 let moved_piece = child.get_moved_piece();
                 let captured_piece_opt = child.get_captured_piece();
-                let is_king_checked = child.get_engine().is_king_checked();
+                let is_king_checked = child.is_king_checked();
 
                 let bonus = self.evaluator.evaluate_heuristic_move(
+                    position,
                     player_move,
                     moved_piece,
                     captured_piece_opt,
                     is_king_checked,
                 ) * values::HEURISTIC_WEIGHT;
 
-                Ok((child_handle, base_score + bonus))
+                let is_quiet =
+                    captured_piece_opt.is_none() && matches!(player_move, PlayerMove::Normal(_));
+                let quiet_bonus = match ply {
+                    Some(ply) if is_quiet => self.quiet_move_bonus(player_move, ply),
+                    _ => 0.,
+                };
+
+                Ok((child_handle, base_score + bonus + quiet_bonus))
             })
             .collect::<Result<Vec<_>, ()>>()?;
 
@@ -455,17 +1357,23 @@ impl TreeSearch {
             .collect())
     }
 
-    fn get_best_move(&self, root_handle: NodeHandle) -> Option<PlayerMove> {
+    fn get_best_move(&self, root_handle: NodeHandle, position: &Engine) -> Option<PlayerMove> {
         // init the best move at none
         let mut best_move = None;
         let mut best_score = f32::NEG_INFINITY;
 
         // Explore children and return the one with the best score
-        for node_handle in self.get_children_sorted_by_score(root_handle, None).ok()? {
+        for node_handle in self
+            .get_children_sorted_by_score(root_handle, position, None, None)
+            .ok()?
+        {
+            if !self.is_allowed_root_move(node_handle) {
+                continue;
+            }
             if let Some(node) = self.pool.get_node(node_handle) {
                 let score = -node.get_best_score()?;
                 if score > best_score {
-                    best_move = Some(node.get_move().clone()?);
+                    best_move = Some(node.get_move()?);
                     best_score = score;
                 }
             }
@@ -474,116 +1382,67 @@ impl TreeSearch {
         best_move
     }
 
-    // fn extract_principal_variation(&self, mut current_handle: NodeHandle) -> Vec<PlayerMove> {
-    //     let mut pv = Vec::new();
-
-    //     loop {
-    //         let current_node = match self.pool.get_node(current_handle) {
-    //             Some(node) => node,
-    //             None => {
-    //                 println!("PV: No node found for handle");
-    //                 break;
-    //             }
-    //         };
-
-    //         if current_node.get_children().is_empty() {
-    //             // DEBUG: Check why this node has no children
-    //             let engine = current_node.get_engine();
-    //             let is_check = engine.is_king_checked();
-    //             let has_children_computed = current_node.has_children_computed();
-
-    //             println!("PV: No children, stopping at depth {}", pv.len());
-    //             println!("PV: Node has_children_computed: {}", has_children_computed);
-    //             println!("PV: King in check: {}", is_check);
-
-    //             if has_children_computed {
-    //                 println!("PV: This is a TERMINAL position (mate or stalemate)");
-    //                 if is_check {
-    //                     println!("PV: *** CHECKMATE FOUND! ***");
-    //                 } else {
-    //                     println!("PV: Stalemate position");
-    //                 }
-    //             } else {
-    //                 println!("PV: Children not computed (search depth limit or leaf)");
-    //             }
-
-    //             // Also check the node's score for confirmation
-    //             if let Some(best_score) = current_node.get_best_score() {
-    //                 println!("PV: Final position score: {}", best_score);
-    //                 if best_score.abs() > values::MATE_THRESHOLD {
-    //                     println!("PV: *** MATE SCORE CONFIRMED! ***");
-    //                 }
-    //             }
-
-    //             break;
-    //         }
-
-    //         println!(
-    //             "PV: At depth {}, checking {} children",
-    //             pv.len(),
-    //             current_node.get_children().len()
-    //         );
-
-    //         let mut best_child_handle = None;
-    //         let mut best_score = f32::NEG_INFINITY;
-    //         let mut evaluated_children = 0;
-
-    //         for &child_handle in current_node.get_children() {
-    //             if let Some(child_node) = self.pool.get_node(child_handle) {
-    //                 // Check if child has best_score
-    //                 if let Some(child_best_score) = child_node.get_best_score() {
-    //                     evaluated_children += 1;
-    //                     let child_score = -child_best_score;
-
-    //                     if child_score > best_score {
-    //                         best_score = child_score;
-    //                         best_child_handle = Some(child_handle);
-    //                     }
-    //                 } else {
-    //                     // This child wasn't fully evaluated
-    //                     println!("PV: Child without best_score found");
-    //                 }
-    //             }
-    //         }
-
-    //         println!(
-    //             "PV: {}/{} children have best_score",
-    //             evaluated_children,
-    //             current_node.get_children().len()
-    //         );
-
-    //         match best_child_handle {
-    //             Some(handle) => {
-    //                 if let Some(best_child) = self.pool.get_node(handle) {
-    //                     if let Some(move_) = best_child.get_move() {
-    //                         pv.push(move_.clone());
-    //                         println!("PV: Added move, now {} moves deep", pv.len());
-    //                         current_handle = handle;
-    //                     } else {
-    //                         println!("PV: Best child has no move");
-    //                         break;
-    //                     }
-    //                 } else {
-    //                     println!("PV: Best child handle invalid");
-    //                     break;
-    //                 }
-    //             }
-    //             None => {
-    //                 println!("PV: No best child found (no evaluated children)");
-    //                 break;
-    //             }
-    //         }
-
-    //         // Safety limit
-    //         if pv.len() >= 20 {
-    //             println!("PV: Hit safety limit");
-    //             break;
-    //         }
-    //     }
-
-    //     println!("PV: Final length: {}", pv.len());
-    //     pv
-    // }
+    /// Whether a child of the root can be played, always true outside of
+    /// `search_restricted`
+    fn is_allowed_root_move(&self, child_handle: NodeHandle) -> bool {
+        match (&self.root_moves, self.pool.get_node(child_handle)) {
+            (None, _) => true,
+            (Some(allowed), Some(child)) => child
+                .get_move()
+                .is_some_and(|chess_move| allowed.contains(&chess_move)),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Follows the best child of each node, starting with `best_move` at the root
+    ///
+    /// # Parameters
+    /// * `root_handle` - Root of the search
+    /// * `best_move` - Move chosen at the root
+    /// * `max_length` - The line stops after this many moves, deeper nodes
+    ///   only come from the quiescence search
+    fn extract_principal_variation(
+        &self,
+        root_handle: NodeHandle,
+        best_move: PlayerMove,
+        max_length: usize,
+    ) -> Vec<PlayerMove> {
+        let mut pv = vec![best_move];
+        let mut current_handle = self.pool.get_node(root_handle).and_then(|root| {
+            root.get_children().iter().copied().find(|&child_handle| {
+                self.pool
+                    .get_node(child_handle)
+                    .is_some_and(|child| child.get_move() == Some(best_move))
+            })
+        });
+
+        while let Some(handle) = current_handle {
+            if pv.len() >= max_length {
+                break;
+            }
+
+            // The best child is the one with the worst score for the opponent
+            current_handle = self.pool.get_node(handle).and_then(|node| {
+                node.get_children()
+                    .iter()
+                    .filter_map(|&child_handle| {
+                        let score = self.pool.get_node(child_handle)?.get_best_score()?;
+                        Some((child_handle, -score))
+                    })
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(child_handle, _)| child_handle)
+            });
+
+            if let Some(chess_move) = current_handle
+                .and_then(|child_handle| self.pool.get_node(child_handle))
+                .and_then(|child| child.get_move())
+            {
+                pv.push(chess_move);
+            }
+        }
+
+        pv
+    }
 
     fn get_tree_max_depth(&self, handle: NodeHandle) -> usize {
         if let Some(node) = self.pool.get_node(handle) {
@@ -594,3 +1453,939 @@ impl TreeSearch {
         0
     }
 }
+
+#[cfg(feature = "parallel")]
+impl<E: Evaluator + Sync> TreeSearch<E> {
+    /// Same as `iterative_search`, with the root moves split between
+    /// `threads` searches running in parallel
+    ///
+    /// The searches share the transposition table, so what one of them
+    /// stores speeds up the others, but each one has its own node pool of
+    /// the same capacity as this one. The result is the one of the best
+    /// search (see `best_worker_result`), with the nodes of all of them.
+    /// Progress isn't reported (`on_iteration`) and the tablebase is only
+    /// probed at the root.
+    pub fn parallel_search(&mut self, position: Engine) -> Option<SearchResult> {
+        let root_moves: Vec<PlayerMove> = position
+            .legal_moves()
+            .into_iter()
+            .filter(|chess_move| {
+                self.root_moves
+                    .as_ref()
+                    .is_none_or(|allowed| allowed.contains(chess_move))
+            })
+            .collect();
+        let thread_count = self.threads.min(root_moves.len());
+        if thread_count <= 1 {
+            return self.iterative_search(position);
+        }
+
+        #[cfg(feature = "tablebase")]
+        if let Some(result) = self.probe_tablebase_root(&position) {
+            return Some(result);
+        }
+
+        // Each search gets every `thread_count`-th move, good and bad moves
+        // of the generation order are mixed in all of them
+        let mut shares = vec![Vec::new(); thread_count];
+        for (index, chess_move) in root_moves.into_iter().enumerate() {
+            shares[index % thread_count].push(chess_move);
+        }
+
+        self.tt.new_search();
+        let mut pools = std::mem::take(&mut self.worker_pools);
+        pools.resize_with(thread_count, || {
+            TreeNodePool::with_capacity(self.pool.capacity())
+        });
+        let workers: Vec<TreeSearch<&E>> = shares
+            .into_iter()
+            .zip(pools)
+            .map(|(share, pool)| self.worker(share, pool))
+            .collect();
+        let outcomes: Vec<(Option<SearchResult>, TreeNodePool, TranspositionTable)> = workers
+            .into_par_iter()
+            .map(|mut worker| {
+                let result = worker.search_iteratively(position.clone());
+                (result, worker.pool, worker.tt)
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for (result, pool, tt) in outcomes {
+            results.extend(result);
+            self.worker_pools.push(pool);
+            self.tt.add_counters(&tt);
+        }
+        self.report_table_stats();
+
+        let node_count = results.iter().map(SearchResult::node_count).sum();
+        best_worker_result(results).map(|result| result.with_node_count(node_count))
+    }
+
+    /// A search with the settings and the transposition table of this one,
+    /// limited to `root_moves`
+    fn worker(&self, root_moves: Vec<PlayerMove>, pool: TreeNodePool) -> TreeSearch<&E> {
+        TreeSearch {
+            pool,
+            tt: self.tt.share(),
+            evaluator: &self.evaluator,
+            max_depth: self.max_depth,
+            max_q_depth: self.max_q_depth,
+            window_margin: self.window_margin,
+            aspiration_window: self.aspiration_window,
+            aspiration_research: self.aspiration_research,
+            max_time: self.max_time,
+            max_nodes: self.max_nodes,
+            max_ply: self.max_ply,
+            qsearch_see_margin: self.qsearch_see_margin,
+            qsearch_bad_capture_depth: self.qsearch_bad_capture_depth,
+            null_move_pruning: self.null_move_pruning,
+            principal_variation_search: self.principal_variation_search,
+            quiet_move_ordering: self.quiet_move_ordering,
+            late_move_reduction_threshold: self.late_move_reduction_threshold,
+            late_move_reduction: self.late_move_reduction,
+            check_extensions: self.check_extensions,
+            contempt: self.contempt,
+            on_iteration: None,
+            on_table_stats: None,
+            #[cfg(feature = "tablebase")]
+            tablebase: None,
+            threads: 1,
+            worker_pools: Vec::new(),
+            root_moves: Some(root_moves),
+            killer_moves: Vec::new(),
+            history: Vec::new(),
+            max_history: 0,
+            path_hashes: Vec::new(),
+            root_white_to_play: true,
+            iteration_depth: 0,
+            deadline: None,
+            visited_nodes: 0,
+        }
+    }
+}
+
+/// Picks the result of `parallel_search` among the results of its searches
+///
+/// With a time limit, the searches can stop at different depths, and scores
+/// are only comparable at the same depth: the result comes from the deepest
+/// completed depth. At that depth, exact scores come before the bounds of a
+/// failed aspiration window, then the best score wins.
+#[cfg(feature = "parallel")]
+fn best_worker_result(results: Vec<SearchResult>) -> Option<SearchResult> {
+    let deepest = results.iter().map(SearchResult::depth).max()?;
+    let is_exact = |result: &SearchResult| result.bound() == BoundType::Exact;
+    results
+        .into_iter()
+        .filter(|result| result.depth() == deepest)
+        .max_by(|a, b| {
+            is_exact(a)
+                .cmp(&is_exact(b))
+                .then(a.score().total_cmp(&b.score()))
+        })
+}
+
+/// Index of a move in the history table, `None` for castling
+fn history_index(chess_move: PlayerMove) -> Option<usize> {
+    let (from, to) = match chess_move {
+        PlayerMove::Normal(normal_move) => normal_move.squares(),
+        PlayerMove::Promotion(promotion_move) => promotion_move.squares(),
+        PlayerMove::Castling(_) => return None,
+    };
+    Some(from.trailing_zeros() as usize * 64 + to.trailing_zeros() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::static_evaluation::evaluators::BasicEvaluator;
+
+    type Builder = TreeSearchBuilder<BasicEvaluator>;
+
+    /// A search with the capacities of the tests, `configure` changes its
+    /// default settings
+    fn search_with(
+        max_depth: usize,
+        configure: impl FnOnce(Builder) -> Builder,
+    ) -> TreeSearch<BasicEvaluator> {
+        configure(
+            TreeSearchBuilder::default()
+                .pool_capacity(1_000_000)
+                .tt_capacity(1 << 16)
+                .evaluator(BasicEvaluator::default())
+                .max_depth(max_depth),
+        )
+        .build()
+        .unwrap()
+    }
+
+    /// Settings of `plain_search`, for the tests that change some more
+    fn plain(builder: Builder) -> Builder {
+        builder
+            .max_q_depth(0)
+            .null_move_pruning(false)
+            .late_move_reduction(0)
+            .check_extensions(false)
+    }
+
+    /// A search without pruning nor extension, its score is the minimax score
+    fn plain_search(max_depth: usize) -> TreeSearch<BasicEvaluator> {
+        search_with(max_depth, plain)
+    }
+
+    /// Minimax on full positions, relative to the side to play
+    fn minimax(position: &Engine, depth: usize, evaluator: &BasicEvaluator) -> f32 {
+        if depth == 0 {
+            return evaluator.evaluate_engine_state(position, 0);
+        }
+        position
+            .generate_moves_with_engine_state()
+            .unwrap()
+            .iter()
+            .map(|child| -minimax(&child.engine, depth - 1, evaluator))
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    #[test]
+    fn rebuilt_positions_give_the_minimax_score() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let result = plain_search(3).iterative_search(position.clone()).unwrap();
+            let expected = minimax(&position, 3, &BasicEvaluator::default());
+            assert_eq!(result.score(), expected, "{fen}");
+        }
+    }
+
+    fn narrow_window_search(max_depth: usize) -> TreeSearch<BasicEvaluator> {
+        search_with(max_depth, |builder| {
+            builder
+                .max_q_depth(0)
+                .aspiration_window(0.01)
+                .aspiration_research(false)
+        })
+    }
+
+    #[test]
+    fn narrow_window_without_research_keeps_the_bound() {
+        // Depth 1 grabs the defended pawn, depth 2 sees the recapture: fail low
+        let position = Engine::from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let result = narrow_window_search(2).iterative_search(position).unwrap();
+        assert_eq!(result.bound(), BoundType::UpperBound);
+
+        // Depth 1 only sees a check, depth 2 finds the back rank mate: fail high
+        let position = Engine::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let result = narrow_window_search(2).iterative_search(position).unwrap();
+        assert_eq!(result.bound(), BoundType::LowerBound);
+    }
+
+    #[test]
+    fn full_window_gives_an_exact_bound() {
+        for fen in [
+            "4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let result = plain_search(2).iterative_search(position).unwrap();
+            assert_eq!(result.bound(), BoundType::Exact, "{fen}");
+        }
+    }
+
+    #[test]
+    fn node_limit_keeps_the_move_of_the_last_completed_depth() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let search = |max_depth: usize, max_nodes: Option<usize>| {
+            search_with(max_depth, |builder| {
+                let builder = builder.max_q_depth(4);
+                match max_nodes {
+                    Some(max_nodes) => builder.max_nodes(max_nodes),
+                    None => builder,
+                }
+            })
+            .iterative_search(Engine::from_fen(fen).unwrap())
+            .unwrap()
+        };
+
+        // A budget one node past the end of depth 3 cuts the fourth iteration
+        let completed = search(3, None);
+        assert_eq!(completed.depth(), 3);
+        let interrupted = search(64, Some(completed.node_count() + 1));
+        assert_eq!(interrupted.depth(), 3);
+        assert_eq!(interrupted.node_count(), completed.node_count());
+        assert_eq!(interrupted.best_move(), completed.best_move());
+        assert_eq!(interrupted.score(), completed.score());
+        assert_eq!(interrupted.bound(), completed.bound());
+    }
+
+    #[test]
+    fn max_ply_caps_the_recursion() {
+        // The cap cuts a deep search down to a two ply minimax
+        let position =
+            Engine::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+                .unwrap();
+        let mut search = search_with(8, |builder| plain(builder).max_ply(2));
+        let result = search.iterative_search(position.clone()).unwrap();
+        assert_eq!(
+            result.score(),
+            minimax(&position, 2, &BasicEvaluator::default())
+        );
+
+        // Queen checks on every move extend the search, the cap still holds
+        let position = Engine::from_fen("6k1/6p1/7p/8/8/8/q5PP/3Q3K w - - 0 1").unwrap();
+        let mut search = search_with(8, |builder| builder.max_q_depth(4).max_ply(4));
+        assert!(search.iterative_search(position).is_some());
+    }
+
+    #[test]
+    fn on_iteration_reports_one_pv_per_depth() {
+        use std::sync::{Arc, Mutex};
+
+        let position =
+            Engine::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+                .unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let mut search = search_with(4, |builder| {
+            builder.max_q_depth(2).on_iteration(move |info| {
+                sink.lock()
+                    .unwrap()
+                    .push((info.depth(), info.pv().to_vec()))
+            })
+        });
+        let result = search.iterative_search(position.clone()).unwrap();
+
+        let reports = reports.lock().unwrap();
+        let depths: Vec<usize> = reports.iter().map(|(depth, _)| *depth).collect();
+        assert_eq!(depths, [1, 2, 3, 4]);
+        for (_, pv) in reports.iter() {
+            let mut engine = position.clone();
+            for &chess_move in pv {
+                engine.play(chess_move).unwrap();
+            }
+        }
+        assert_eq!(reports[3].1.as_slice(), result.pv());
+
+        // Without a callback nothing is reported, the search itself is the same
+        let mut silent = search_with(4, |builder| builder.max_q_depth(2));
+        let silent_result = silent.iterative_search(position).unwrap();
+        assert_eq!(silent_result.best_move(), result.best_move());
+        assert_eq!(silent_result.pv(), result.pv());
+    }
+
+    #[test]
+    fn restricted_search_plays_the_only_allowed_move() {
+        use crate::game_engine::player_move::NormalMove;
+
+        // Qd2xd5 loses the queen to c6xd5, the search still has to play it
+        let position = Engine::from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let blunder = PlayerMove::Normal(NormalMove::new(1 << 11, 1 << 35));
+        let result = plain_search(2)
+            .search_restricted(position.clone(), &[blunder])
+            .unwrap();
+        assert_eq!(result.best_move(), &blunder);
+
+        let mut after = position.clone();
+        after.play(blunder).unwrap();
+        assert_eq!(
+            result.score(),
+            -minimax(&after, 1, &BasicEvaluator::default())
+        );
+
+        let free = plain_search(2).iterative_search(position.clone()).unwrap();
+        assert_ne!(free.best_move(), &blunder);
+        assert!(free.score() > result.score());
+
+        // None of the allowed moves is legal
+        let illegal = PlayerMove::Normal(NormalMove::new(1 << 11, 1 << 43));
+        assert!(plain_search(2)
+            .search_restricted(position, &[illegal])
+            .is_none());
+    }
+
+    #[test]
+    fn terminal_nodes_record_why_the_game_ends() {
+        use crate::game_engine::player_move::NormalMove;
+        use crate::pieces::Color;
+
+        let mut repeated = Engine::new();
+        for _ in 0..2 {
+            for (from, to) in [(6, 21), (62, 45), (21, 6), (45, 62)] {
+                repeated
+                    .play(PlayerMove::Normal(NormalMove::new(1 << from, 1 << to)))
+                    .unwrap();
+            }
+        }
+
+        let cases = [
+            (
+                Engine::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+                    .unwrap(),
+                Some(TerminalReason::Checkmate {
+                    winner: Color::Black,
+                }),
+            ),
+            (
+                Engine::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap(),
+                Some(TerminalReason::Stalemate),
+            ),
+            (
+                Engine::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap(),
+                Some(TerminalReason::InsufficientMaterial),
+            ),
+            (
+                Engine::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap(),
+                Some(TerminalReason::FiftyMove),
+            ),
+            (repeated, Some(TerminalReason::Repetition)),
+            (Engine::new(), None),
+        ];
+
+        // Nodes without a move, as after a null move, are only roots when
+        // the search says so
+        let mut search = plain_search(1);
+        for (position, reason) in cases {
+            let node = |search: &mut TreeSearch<BasicEvaluator>| {
+                search
+                    .pool
+                    .allocate_node(
+                        position.zobrist_hash(),
+                        position.is_king_checked(),
+                        0.,
+                        None,
+                        None,
+                        None,
+                    )
+                    .unwrap()
+            };
+            let handle = node(&mut search);
+            search.generate_children(handle, &position, false).unwrap();
+            let inner = search.pool.get_node(handle).unwrap();
+            assert_eq!(inner.get_terminal_reason(), reason, "{reason:?}");
+
+            // A drawn root keeps its moves, only mates and stalemates end it
+            let handle = node(&mut search);
+            search.generate_children(handle, &position, true).unwrap();
+            let root = search.pool.get_node(handle).unwrap();
+            assert_eq!(
+                root.get_children().is_empty(),
+                position.first_legal_move().is_none(),
+                "{reason:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_built_search_runs_on_a_worker_thread() {
+        let position = Engine::new();
+        let mut search = plain_search(3);
+        let worker_position = position.clone();
+        let worker = std::thread::spawn(move || search.iterative_search(worker_position));
+        let result = worker.join().unwrap().unwrap();
+
+        let local = plain_search(3).iterative_search(position).unwrap();
+        assert_eq!(result.best_move(), local.best_move());
+        assert_eq!(result.score(), local.score());
+    }
+
+    #[test]
+    fn higher_see_margin_searches_more_captures() {
+        let kiwipete = Engine::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let node_count = |margin: f32| {
+            search_with(2, |builder| {
+                builder
+                    .max_q_depth(6)
+                    .qsearch_see_margin(margin)
+                    .qsearch_bad_capture_depth(0)
+            })
+            .iterative_search(kiwipete.clone())
+            .unwrap()
+            .node_count()
+        };
+        assert!(node_count(0.) < node_count(get_value_by_piece(Piece::Queen)));
+    }
+
+    #[test]
+    fn see_margin_skips_captures_that_delta_pruning_keeps() {
+        // exd5 wins a pawn, short of the alpha but well within a queen of it
+        let position = Engine::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let stand_pat = BasicEvaluator::default().evaluate_engine_state(&position, 0);
+        let alpha = stand_pat + 3. * get_value_by_piece(Piece::Pawn);
+        let search = |margin: f32| {
+            let mut search = search_with(1, |builder| {
+                builder
+                    .max_q_depth(6)
+                    .qsearch_see_margin(margin)
+                    .qsearch_bad_capture_depth(0)
+            });
+            let handle = search
+                .pool
+                .allocate_node(position.zobrist_hash(), false, stand_pat, None, None, None)
+                .unwrap();
+            let score = search
+                .quiescence_search(handle, &position, alpha, alpha + 1., 1)
+                .unwrap();
+            (score, search.visited_nodes)
+        };
+
+        // The capture is skipped, the node fails low on its stand pat
+        assert_eq!(search(DEFAULT_QSEARCH_SEE_MARGIN), (stand_pat, 1));
+
+        // A margin as wide as delta pruning's searches it
+        let (_, visited_nodes) = search(get_value_by_piece(Piece::Queen));
+        assert_eq!(visited_nodes, 2);
+    }
+
+    #[test]
+    fn the_pv_of_a_mate_search_ends_in_checkmate() {
+        use crate::pieces::Color;
+
+        let position = Engine::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let result = plain_search(3).iterative_search(position.clone()).unwrap();
+        assert_eq!(result.score_mate(), Some(1));
+
+        let mated = result.apply_pv(&position).unwrap();
+        assert_eq!(
+            mated.terminal_reason(),
+            Some(TerminalReason::Checkmate {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn re_searches_count_in_the_node_count() {
+        let position = Engine::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let search = |aspiration_window: f32| {
+            search_with(3, |builder| {
+                builder.max_q_depth(2).aspiration_window(aspiration_window)
+            })
+            .iterative_search(position.clone())
+            .unwrap()
+        };
+
+        // The narrow window fails and its depths are searched twice
+        let full = search(f32::INFINITY);
+        let narrow = search(0.01);
+        assert_eq!(narrow.bound(), BoundType::Exact);
+        assert_eq!(narrow.score(), full.score());
+        assert!(narrow.node_count() > full.node_count());
+    }
+
+    #[test]
+    fn bench_is_deterministic() {
+        let build = || {
+            search_with(6, |builder| {
+                builder.max_q_depth(2).max_time(Duration::from_millis(1))
+            })
+        };
+
+        // Twice from the same search, its state must not leak into the count,
+        // and once from another search with the same settings
+        let mut search = build();
+        let nodes = search.bench(2);
+        assert!(nodes > 0);
+        assert_eq!(search.bench(2), nodes);
+        assert_eq!(build().bench(2), nodes);
+    }
+
+    #[test]
+    #[ignore = "timing only, run with `cargo test --release -- --ignored --nocapture`"]
+    fn boxed_and_generic_evaluators_search_the_same_nodes() {
+        const DEPTH: usize = 5;
+        const RUNS: usize = 3;
+
+        /// Best time of a few bench runs, with the nodes of a run
+        fn time_bench(mut bench: impl FnMut() -> u64) -> (u64, Duration) {
+            let mut nodes = 0;
+            let mut best = Duration::MAX;
+            for _ in 0..RUNS {
+                let start = Instant::now();
+                nodes = bench();
+                best = best.min(start.elapsed());
+            }
+            (nodes, best)
+        }
+
+        let mut generic = search_with(DEPTH, |builder| builder.max_q_depth(2));
+        let mut boxed: DynTreeSearch = TreeSearchBuilder::default()
+            .pool_capacity(1_000_000)
+            .tt_capacity(1 << 16)
+            .evaluator(Box::new(BasicEvaluator::default()) as Box<dyn Evaluator + Send>)
+            .max_depth(DEPTH)
+            .max_q_depth(2)
+            .build()
+            .unwrap();
+
+        let (generic_nodes, generic_time) = time_bench(|| generic.bench(DEPTH));
+        let (boxed_nodes, boxed_time) = time_bench(|| boxed.bench(DEPTH));
+        assert_eq!(generic_nodes, boxed_nodes);
+
+        let nodes_per_second = |time: Duration| generic_nodes as f64 / time.as_secs_f64();
+        println!(
+            "{generic_nodes} nodes, generic {:.0} nodes/s, boxed {:.0} nodes/s",
+            nodes_per_second(generic_time),
+            nodes_per_second(boxed_time)
+        );
+    }
+
+    #[test]
+    fn the_mirrored_position_gets_the_mirrored_best_move() {
+        // Positions with a single best move, ties may be broken differently
+        for fen in [
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+            "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/4K2R w Kkq - 0 1",
+            "3rk3/8/8/8/8/8/8/3QK3 b - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let result = plain_search(3).iterative_search(position.clone()).unwrap();
+            let mirrored = plain_search(3)
+                .iterative_search(position.flipped())
+                .unwrap();
+            assert_eq!(mirrored.best_move(), &result.best_move().flipped(), "{fen}");
+            assert_eq!(mirrored.score(), result.score(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn castling_through_an_attacked_square_is_never_searched() {
+        use crate::game_engine::player_move::CastlingMove;
+
+        // The rook on f8 attacks f1, the square the king crosses
+        let position = Engine::from_fen("4kr2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let short = PlayerMove::Castling(CastlingMove::Short);
+        assert!(plain_search(2)
+            .search_restricted(position.clone(), &[short])
+            .is_none());
+
+        let result = plain_search(2).iterative_search(position).unwrap();
+        assert_ne!(result.best_move(), &short);
+        assert!(!result.pv().contains(&short));
+    }
+
+    #[test]
+    fn killer_and_history_tables_save_nodes() {
+        let build = |quiet_move_ordering: bool| {
+            search_with(3, |builder| {
+                builder
+                    .max_q_depth(2)
+                    .quiet_move_ordering(quiet_move_ordering)
+            })
+        };
+        assert!(build(true).bench(3) < build(false).bench(3));
+    }
+
+    #[test]
+    fn principal_variation_search_keeps_the_alpha_beta_score() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let search = |principal_variation_search: bool| {
+                search_with(3, |builder| {
+                    builder
+                        .max_q_depth(2)
+                        .null_move_pruning(false)
+                        .late_move_reduction(0)
+                        .principal_variation_search(principal_variation_search)
+                })
+                .iterative_search(position.clone())
+                .unwrap()
+            };
+            assert_eq!(search(true).score(), search(false).score(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn late_move_reductions_keep_the_tactical_shots() {
+        for (fen, best) in [
+            ("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", "Ra8"),
+            (
+                "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+                "Qxf7",
+            ),
+            ("6rk/6pp/8/6N1/8/8/8/6K1 w - - 0 1", "Nf7"),
+            ("3q3k/8/8/6N1/8/8/8/6K1 w - - 0 1", "Nf7"),
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let search = |late_move_reduction: usize| {
+                search_with(4, |builder| {
+                    builder
+                        .max_q_depth(4)
+                        .late_move_reduction(late_move_reduction)
+                })
+                .iterative_search(position.clone())
+                .unwrap()
+            };
+            let reduced = search(DEFAULT_LATE_MOVE_REDUCTION);
+            assert_eq!(
+                reduced.best_move(),
+                &position.get_move_by_san(best).unwrap(),
+                "{fen}"
+            );
+            assert_eq!(reduced.score(), search(0).score(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn a_perpetual_check_is_scored_as_a_draw() {
+        // Four rooks down for a queen, Qh5+ and Qe8+ give perpetual check
+        let position = Engine::from_fen("8/6pk/8/8/rr6/rr6/5PPP/3Q2K1 w - - 0 1").unwrap();
+        let search = || search_with(6, |builder| builder.max_q_depth(4));
+
+        let result = search().iterative_search(position.clone()).unwrap();
+        assert_eq!(result.score(), 0.);
+
+        let check = position.get_move_by_san("Qh5+").unwrap();
+        let result = search().search_restricted(position, &[check]).unwrap();
+        assert_eq!(result.score(), 0.);
+    }
+
+    #[test]
+    fn the_only_move_avoiding_a_draw_is_played() {
+        // Any move but the capture completes fifty moves without one
+        let position = Engine::from_fen("4k3/8/8/3n4/8/8/8/3RK3 w - - 99 80").unwrap();
+        let capture = position.get_move_by_san("Rxd5").unwrap();
+        let king_move = position.get_move_by_san("Kf2").unwrap();
+
+        for contempt in [0., 2.] {
+            let search = || search_with(3, |builder| builder.max_q_depth(2).contempt(contempt));
+
+            let result = search().iterative_search(position.clone()).unwrap();
+            assert_eq!(result.best_move(), &capture, "contempt {contempt}");
+            assert!(result.score() > 0., "contempt {contempt}");
+
+            // The draw is worth the contempt less than 0 to the side to play
+            let drawn = search()
+                .search_restricted(position.clone(), &[king_move])
+                .unwrap();
+            assert_eq!(drawn.score(), -contempt);
+        }
+
+        // Contempt also applies to repetitions, the perpetual check is still
+        // better than the lost ending
+        let position = Engine::from_fen("8/6pk/8/8/rr6/rr6/5PPP/3Q2K1 w - - 0 1").unwrap();
+        let result = search_with(6, |builder| builder.max_q_depth(4).contempt(2.))
+            .iterative_search(position)
+            .unwrap();
+        assert_eq!(result.score(), -2.);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_results_come_from_the_deepest_exact_search() {
+        let position = Engine::new();
+        let [e4, d4, c4, nf3]: [PlayerMove; 4] =
+            ["e4", "d4", "c4", "Nf3"].map(|san| position.get_move_by_san(san).unwrap());
+        let result = |chess_move, score, bound, depth| {
+            SearchResult::new(chess_move, score, bound, depth, depth, 0, vec![chess_move])
+        };
+
+        // A shallower search can't win with a better score
+        let best = best_worker_result(vec![
+            result(e4, 1., BoundType::Exact, 5),
+            result(d4, 3., BoundType::Exact, 4),
+        ])
+        .unwrap();
+        assert_eq!(best.best_move(), &e4);
+
+        // At the same depth, an exact score beats a bound
+        let best = best_worker_result(vec![
+            result(e4, 3., BoundType::LowerBound, 5),
+            result(d4, 1., BoundType::Exact, 5),
+            result(c4, 2., BoundType::Exact, 5),
+            result(nf3, 4., BoundType::UpperBound, 5),
+        ])
+        .unwrap();
+        assert_eq!(best.best_move(), &c4);
+
+        assert!(best_worker_result(Vec::new()).is_none());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn one_and_several_threads_agree_at_a_fixed_depth() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let search = |threads: usize| {
+                search_with(3, |builder| plain(builder).threads(threads))
+                    .parallel_search(position.clone())
+                    .unwrap()
+            };
+
+            let single = search(1);
+            for threads in [2, 4] {
+                let parallel = search(threads);
+                assert_eq!(parallel.score(), single.score(), "{fen} {threads}");
+                assert_eq!(parallel.depth(), single.depth(), "{fen} {threads}");
+                assert_eq!(parallel.bound(), BoundType::Exact, "{fen} {threads}");
+            }
+        }
+    }
+
+    /// Tablebase that knows a few positions, each given by the start of its
+    /// FEN with the move to play and the result
+    #[cfg(feature = "tablebase")]
+    struct KnownEndings(Vec<(&'static str, &'static str, Wdl)>);
+
+    #[cfg(feature = "tablebase")]
+    impl KnownEndings {
+        fn find(&self, engine: &Engine) -> Option<(&'static str, Wdl)> {
+            let fen = engine.to_string();
+            self.0
+                .iter()
+                .find(|(position, _, _)| fen.starts_with(position))
+                .map(|&(_, uci, wdl)| (uci, wdl))
+        }
+    }
+
+    #[cfg(feature = "tablebase")]
+    impl Tablebase for KnownEndings {
+        fn max_pieces(&self) -> u32 {
+            3
+        }
+
+        fn probe_wdl(&self, engine: &Engine) -> Option<Wdl> {
+            self.find(engine).map(|(_, wdl)| wdl)
+        }
+
+        fn probe_root(&self, engine: &Engine) -> Option<(PlayerMove, Wdl)> {
+            let (uci, wdl) = self.find(engine)?;
+            Some((PlayerMove::from_uci(uci, engine).ok()?, wdl))
+        }
+    }
+
+    #[cfg(feature = "tablebase")]
+    #[test]
+    fn the_tablebase_gives_the_move_and_result_of_the_root() {
+        let endings = || {
+            KnownEndings(vec![
+                ("8/8/8/4k3/8/8/8/R3K3 w", "a1a5", Wdl::Win),
+                ("4k3/8/4K3/4P3/8/8/8/8 w", "e6d6", Wdl::Draw),
+                ("4k3/8/4K3/4P3/8/8/8/8 b", "e8d8", Wdl::Loss),
+            ])
+        };
+        for (fen, uci, score) in [
+            ("8/8/8/4k3/8/8/8/R3K3 w - - 0 1", "a1a5", Wdl::Win.score(0)),
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", "e6d6", 0.),
+            (
+                "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1",
+                "e8d8",
+                Wdl::Loss.score(0),
+            ),
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let result = search_with(4, |builder| plain(builder).tablebase(Box::new(endings())))
+                .iterative_search(position.clone())
+                .unwrap();
+            assert_eq!(
+                result.best_move(),
+                &PlayerMove::from_uci(uci, &position).unwrap(),
+                "{fen}"
+            );
+            assert_eq!(result.score(), score, "{fen}");
+            assert_eq!(result.bound(), BoundType::Exact, "{fen}");
+            // The root is answered without searching
+            assert_eq!(result.node_count(), 0, "{fen}");
+        }
+    }
+
+    #[cfg(feature = "tablebase")]
+    #[test]
+    fn the_tablebase_score_replaces_the_search_of_interior_nodes() {
+        // Taking the pawn leaves a rook ending the tablebase knows
+        let position = Engine::from_fen("8/8/8/8/8/2k5/p7/R3K3 w - - 0 1").unwrap();
+        let rook_takes = PlayerMove::from_uci("a1a2", &position).unwrap();
+
+        let searched = plain_search(2).iterative_search(position.clone()).unwrap();
+        assert!(searched.score() < values::VALUE_TB_WIN_IN_MAX_PLY / 2.);
+
+        let endings = KnownEndings(vec![("8/8/8/8/8/2k5/R7/4K3 b", "c3b3", Wdl::Loss)]);
+        let probed = search_with(2, |builder| plain(builder).tablebase(Box::new(endings)))
+            .iterative_search(position)
+            .unwrap();
+        assert_eq!(probed.best_move(), &rook_takes);
+        // The loss of black one ply below the root
+        assert_eq!(probed.score(), Wdl::Win.score(1));
+    }
+
+    #[test]
+    fn check_extensions_find_mates_an_iteration_earlier() {
+        for fen in [
+            "5rk1/1p1q2bp/p2pN1p1/2pP2Bn/2P3P1/1P6/P4QKP/5R2 w - - 0 1",
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+            "1r6/4b2k/1q1pNrpp/p2Pp3/4P3/1P1R3Q/5PPP/5RK1 w - - 0 1",
+            "r1b2k1r/ppppq3/5N1p/4P2Q/4PP2/1B6/PP5P/n2K2R1 w - - 1 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let first_mate_depth = |check_extensions: bool| {
+                (1..=4).find(|&depth| {
+                    search_with(depth, |builder| {
+                        builder.max_q_depth(4).check_extensions(check_extensions)
+                    })
+                    .iterative_search(position.clone())
+                    .unwrap()
+                    .score_mate()
+                    .is_some_and(|moves| moves > 0)
+                })
+            };
+
+            assert_eq!(first_mate_depth(true), Some(3), "{fen}");
+            assert_eq!(first_mate_depth(false), Some(4), "{fen}");
+        }
+    }
+
+    #[test]
+    fn quiescence_returns_the_stand_pat_of_a_quiet_leaf() {
+        let evaluator = BasicEvaluator::default();
+        let mut search = search_with(1, |builder| builder.max_q_depth(4));
+
+        for fen in [
+            // No capture at all
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            // The only capture loses the queen
+            "4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1",
+        ] {
+            let position = Engine::from_fen(fen).unwrap();
+            let stand_pat = evaluator.evaluate_engine_state(&position, 0);
+            let handle = search
+                .pool
+                .allocate_node(
+                    position.zobrist_hash(),
+                    position.is_king_checked(),
+                    stand_pat,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+
+            let score = search
+                .quiescence_search(handle, &position, f32::NEG_INFINITY, f32::INFINITY, 0)
+                .unwrap();
+            assert_eq!(score, stand_pat, "{fen}");
+            assert_eq!(
+                search.pool.get_node(handle).unwrap().get_best_score(),
+                Some(stand_pat),
+                "{fen}"
+            );
+        }
+    }
+}
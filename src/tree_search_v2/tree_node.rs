@@ -1,13 +1,22 @@
+use crate::game_engine::game_states::TerminalReason;
+use crate::game_engine::packed_move::PackedMove;
 use crate::pieces::Piece;
-use crate::prelude::{Engine, PlayerMove};
+use crate::prelude::PlayerMove;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NodeHandle(pub(super) usize);
 
+/// A node of the search tree
+///
+/// Nodes don't own the position they represent: it is rebuilt while walking
+/// down from the root by playing `chess_move`. Only what the search needs
+/// without the position (hash, check flag, scores) is stored here, and the
+/// move is packed in 2 bytes.
 pub struct TreeNode {
     // About the game
-    engine: Engine,
-    chess_move: Option<PlayerMove>,
+    hash: u64,
+    is_king_checked: bool,
+    chess_move: Option<PackedMove>,
     moved_piece: Option<Piece>,
     captured_piece: Option<Piece>,
 
@@ -16,19 +25,22 @@ pub struct TreeNode {
     best_score: Option<f32>,
     computed: bool,
     children: Vec<NodeHandle>,
+    terminal_reason: Option<TerminalReason>,
 }
 
 impl TreeNode {
     pub fn new(
-        engine: Engine,
+        hash: u64,
+        is_king_checked: bool,
         score: f32,
-        chess_move: Option<PlayerMove>,
+        chess_move: Option<PackedMove>,
         moved_piece: Option<Piece>,
         captured_piece: Option<Piece>,
     ) -> Self {
         // create the node
         TreeNode {
-            engine,
+            hash,
+            is_king_checked,
             children: Vec::new(),
             score,
             chess_move,
@@ -36,12 +48,18 @@ impl TreeNode {
             computed: false,
             moved_piece,
             captured_piece,
+            terminal_reason: None,
         }
     }
 
-    /// Returns a reference to the underlying chess engine
-    pub fn get_engine(&self) -> &Engine {
-        &self.engine
+    /// Returns the zobrist hash of this position
+    pub fn get_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns whether the side to move is in check in this position
+    pub fn is_king_checked(&self) -> bool {
+        self.is_king_checked
     }
 
     /// Returns the raw evaluation score of this position
@@ -59,9 +77,11 @@ impl TreeNode {
         &self.children
     }
 
-    /// Returns a reference to the optional chess move that led to this position
-    pub fn get_move(&self) -> &Option<PlayerMove> {
-        &self.chess_move
+    /// Returns the chess move that led to this position, `None` for the root
+    /// and null move nodes
+    pub fn get_move(&self) -> Option<PlayerMove> {
+        self.chess_move
+            .map(|chess_move| chess_move.to_player_move())
     }
 
     /// Returns whether children nodes have been computed for this position
@@ -81,6 +101,12 @@ impl TreeNode {
         self.captured_piece
     }
 
+    /// Returns why the game ends at this position, `None` if it goes on
+    /// (or if the children were never generated)
+    pub fn get_terminal_reason(&self) -> Option<TerminalReason> {
+        self.terminal_reason
+    }
+
     // SETTER
 
     /// Set the best score
@@ -98,6 +124,11 @@ impl TreeNode {
         self.score = score;
     }
 
+    /// Marks the position as the end of the game
+    pub fn set_terminal_reason(&mut self, reason: TerminalReason) {
+        self.terminal_reason = Some(reason);
+    }
+
     /// Add a child to this node
     pub fn add_child(&mut self, child_handle: NodeHandle) {
         self.children.push(child_handle)
@@ -1,5 +1,5 @@
+use crate::game_engine::packed_move::PackedMove;
 use crate::pieces::Piece;
-use crate::prelude::{Engine, PlayerMove};
 
 use super::tree_node::{NodeHandle, TreeNode};
 
@@ -35,14 +35,22 @@ impl TreeNodePool {
     /// Allocate a new node in the pool, returns None if pool is full
     pub fn allocate_node(
         &mut self,
-        engine: Engine,
+        hash: u64,
+        is_king_checked: bool,
         score: f32,
-        chess_move: Option<PlayerMove>,
+        chess_move: Option<PackedMove>,
         moved_piece: Option<Piece>,
         captured_piece: Option<Piece>,
     ) -> Option<NodeHandle> {
         if let Some(index) = self.free_indices.pop() {
-            let node = TreeNode::new(engine, score, chess_move, moved_piece, captured_piece);
+            let node = TreeNode::new(
+                hash,
+                is_king_checked,
+                score,
+                chess_move,
+                moved_piece,
+                captured_piece,
+            );
             self.nodes[index] = Some(node);
             self.allocated_count += 1;
             Some(NodeHandle(index))
@@ -0,0 +1,541 @@
+//! Minimal UCI front-end, built with the `uci` feature
+//!
+//! Reads commands from stdin and answers on stdout until `quit`. Handles
+//! `uci`, `isready`, `ucinewgame`, `setoption` (`Move Overhead`, and
+//! `SyzygyPath` with the `tablebase` feature), `position`
+//! and `go` with `depth`, `movetime` or the clock (`wtime`, `btime`, `winc`,
+//! `binc`, `movestogo`), optionally restricted to `searchmoves`. The search blocks the loop, so `stop` is never needed
+//! and unknown commands are ignored, as the protocol asks.
+
+use std::io::{self, BufRead};
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::game_engine::utility::get_color;
+use crate::prelude::evaluators::BasicEvaluator;
+use crate::prelude::{Engine, PlayerMove, TreeSearch};
+use crate::tree_search_v2::search_info::SearchInfo;
+#[cfg(feature = "tablebase")]
+use crate::tree_search_v2::tablebase::{SyzygyTablebase, Tablebase};
+use crate::tree_search_v2::time_management::{self, ClockState, TimeSettings};
+use crate::tree_search_v2::tree::TreeSearchBuilder;
+
+const ENGINE_NAME: &str = "chess_engine";
+const ENGINE_AUTHOR: &str = "sacha-renault";
+
+/// Depth searched by a `go` without `depth` nor `movetime`
+const DEFAULT_DEPTH: usize = 6;
+
+/// Depth limit of a `go movetime` or a `go` with a clock, the time budget
+/// stops the search first
+const MOVETIME_MAX_DEPTH: usize = 64;
+
+/// Upper bound of the `Move Overhead` option, in milliseconds
+const MAX_MOVE_OVERHEAD_MS: u64 = 5_000;
+
+const POOL_CAPACITY: usize = 5_000_000;
+const TT_CAPACITY: usize = 1_000_000;
+const MAX_Q_DEPTH: usize = 4;
+
+/// Runs the UCI loop on stdin / stdout until `quit` or the end of the input
+///
+/// # Returns
+/// `Err` only if stdin can't be read
+pub fn run() -> io::Result<()> {
+    // The side to play at the root, to write the castling of the PV
+    // reported during the search
+    let root_white_to_play = Arc::new(AtomicBool::new(true));
+    let mut tree = build_tree(Arc::clone(&root_white_to_play));
+    let mut position = Engine::new();
+    let mut time_settings = TimeSettings::default();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max {}",
+                    time_management::DEFAULT_MOVE_OVERHEAD_MS,
+                    MAX_MOVE_OVERHEAD_MS
+                );
+                #[cfg(feature = "tablebase")]
+                println!("option name SyzygyPath type string default <empty>");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") => {
+                if let Some((name, value)) = parse_setoption(tokens) {
+                    if name.eq_ignore_ascii_case("Move Overhead") {
+                        if let Ok(millis) = value.parse::<u64>() {
+                            time_settings.move_overhead_ms = millis.min(MAX_MOVE_OVERHEAD_MS);
+                        }
+                    }
+                    #[cfg(feature = "tablebase")]
+                    if name.eq_ignore_ascii_case("SyzygyPath") {
+                        set_syzygy_path(&mut tree, &value);
+                    }
+                }
+            }
+            Some("ucinewgame") => {
+                position = Engine::new();
+                tree.clear_transposition_table();
+            }
+            Some("position") => set_position(&mut position, tokens),
+            Some("go") => {
+                root_white_to_play.store(position.white_to_play(), Ordering::Relaxed);
+                let best_move = go(&mut tree, &position, parse_go(tokens), &time_settings);
+                match best_move {
+                    Some(best_move) => println!(
+                        "bestmove {}",
+                        best_move.to_uci(get_color(position.white_to_play()))
+                    ),
+                    // Mate or stalemate, there is no move to play
+                    None => println!("bestmove 0000"),
+                }
+            }
+            Some("quit") => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn build_tree(root_white_to_play: Arc<AtomicBool>) -> TreeSearch<BasicEvaluator> {
+    TreeSearchBuilder::default()
+        .pool_capacity(POOL_CAPACITY)
+        .tt_capacity(TT_CAPACITY)
+        .evaluator(BasicEvaluator::default())
+        .max_depth(DEFAULT_DEPTH)
+        .max_q_depth(MAX_Q_DEPTH)
+        .on_iteration(move |info| {
+            println!(
+                "{}",
+                info_line(info, root_white_to_play.load(Ordering::Relaxed))
+            )
+        })
+        .build()
+        .expect("every required field of the tree is set")
+}
+
+/// Probes the Syzygy tables of `path` in the next searches. An empty path,
+/// or `<empty>`, removes the tablebase; an unreadable one is reported and
+/// removes it too.
+#[cfg(feature = "tablebase")]
+fn set_syzygy_path(tree: &mut TreeSearch<BasicEvaluator>, path: &str) {
+    if path.is_empty() || path == "<empty>" {
+        tree.set_tablebase(None);
+        return;
+    }
+    match SyzygyTablebase::open(path) {
+        Ok(tablebase) => {
+            println!("info string {} pieces tablebase", tablebase.max_pieces());
+            tree.set_tablebase(Some(Box::new(tablebase)));
+        }
+        Err(error) => {
+            println!("info string can't read the tablebase: {}", error);
+            tree.set_tablebase(None);
+        }
+    }
+}
+
+/// Replaces `position` by the one given to `position`, an invalid position
+/// keeps the previous one
+fn set_position(position: &mut Engine, tokens: SplitWhitespace) {
+    if let Some(new_position) = parse_position(tokens) {
+        *position = new_position;
+    }
+}
+
+/// Parses the arguments of `position`: `startpos` or `fen <fen>`, then
+/// optionally `moves` followed by the moves to play
+///
+/// # Returns
+/// `None` if the FEN or one of the moves is invalid
+fn parse_position(mut tokens: SplitWhitespace) -> Option<Engine> {
+    let mut position = match tokens.next()? {
+        "startpos" => Engine::new(),
+        "fen" => {
+            let fen: Vec<&str> = tokens
+                .by_ref()
+                .take_while(|&token| token != "moves")
+                .collect();
+            let position = Engine::from_fen(&fen.join(" ")).ok()?;
+            // `moves` was consumed by `take_while`
+            return play_uci_moves(position, tokens);
+        }
+        _ => return None,
+    };
+
+    if tokens.next() == Some("moves") {
+        position = play_uci_moves(position, tokens)?;
+    }
+    Some(position)
+}
+
+fn play_uci_moves(mut position: Engine, tokens: SplitWhitespace) -> Option<Engine> {
+    for token in tokens {
+        let chess_move = PlayerMove::from_uci(token, &position).ok()?;
+        position.play(chess_move).ok()?;
+    }
+    Some(position)
+}
+
+/// Parses the arguments of `setoption`: `name <name> [value <value>]`, both
+/// can contain spaces
+///
+/// # Returns
+/// `None` if the name is missing
+fn parse_setoption(mut tokens: SplitWhitespace) -> Option<(String, String)> {
+    if tokens.next()? != "name" {
+        return None;
+    }
+    let name: Vec<&str> = tokens
+        .by_ref()
+        .take_while(|&token| token != "value")
+        .collect();
+    if name.is_empty() {
+        return None;
+    }
+    let value: Vec<&str> = tokens.collect();
+    Some((name.join(" "), value.join(" ")))
+}
+
+/// Limits of a search, read from the arguments of `go`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct GoLimits {
+    depth: Option<usize>,
+    movetime: Option<Duration>,
+    wtime: Option<Duration>,
+    btime: Option<Duration>,
+    winc: Option<Duration>,
+    binc: Option<Duration>,
+    moves_to_go: Option<u32>,
+    /// Root moves of `searchmoves`, in long algebraic notation
+    search_moves: Vec<String>,
+}
+
+impl GoLimits {
+    /// Clock of the side to play, `None` if `go` didn't give its time
+    fn clock(&self, white_to_play: bool) -> Option<ClockState> {
+        let (remaining, increment) = if white_to_play {
+            (self.wtime?, self.winc)
+        } else {
+            (self.btime?, self.binc)
+        };
+        Some(ClockState {
+            remaining,
+            increment: increment.unwrap_or_default(),
+            moves_to_go: self.moves_to_go,
+        })
+    }
+}
+
+/// Arguments of `go`, they end the moves of `searchmoves`
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
+/// Parses the arguments of `go`, unknown and invalid arguments are ignored
+fn parse_go(tokens: SplitWhitespace) -> GoLimits {
+    let mut limits = GoLimits::default();
+    let mut tokens = tokens.peekable();
+
+    while let Some(token) = tokens.next() {
+        let value = |tokens: &mut Peekable<SplitWhitespace>| tokens.next()?.parse::<u64>().ok();
+        let millis =
+            |tokens: &mut Peekable<SplitWhitespace>| value(tokens).map(Duration::from_millis);
+        match token {
+            "depth" => limits.depth = value(&mut tokens).map(|depth| depth as usize),
+            "movetime" => limits.movetime = millis(&mut tokens),
+            "wtime" => limits.wtime = millis(&mut tokens),
+            "btime" => limits.btime = millis(&mut tokens),
+            "winc" => limits.winc = millis(&mut tokens),
+            "binc" => limits.binc = millis(&mut tokens),
+            "movestogo" => {
+                limits.moves_to_go = value(&mut tokens).map(|moves| moves as u32);
+            }
+            "searchmoves" => {
+                while let Some(chess_move) = tokens.next_if(|token| !GO_KEYWORDS.contains(token)) {
+                    limits.search_moves.push(chess_move.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+/// Searches `position` within the limits of `go`
+///
+/// A `movetime` is used as is, otherwise the clock of the side to play is
+/// turned into a budget by `time_management::allocate`. Without any of them
+/// the search stops at `depth`, or `DEFAULT_DEPTH`. With `searchmoves`,
+/// only the given moves are searched at the root, the illegal ones are
+/// ignored.
+///
+/// # Returns
+/// The best move, `None` if the side to play has no legal move
+fn go(
+    tree: &mut TreeSearch<BasicEvaluator>,
+    position: &Engine,
+    limits: GoLimits,
+    time_settings: &TimeSettings,
+) -> Option<PlayerMove> {
+    let max_time = limits.movetime.or_else(|| {
+        limits
+            .clock(position.white_to_play())
+            .map(|clock| time_management::allocate(&clock, time_settings))
+    });
+    let max_depth = match (limits.depth, max_time) {
+        (Some(depth), _) => depth,
+        (None, Some(_)) => MOVETIME_MAX_DEPTH,
+        (None, None) => DEFAULT_DEPTH,
+    };
+
+    tree.set_max_depth(max_depth.max(1));
+    tree.set_max_time(max_time);
+    position.first_legal_move()?;
+
+    let legal_moves = position.legal_moves();
+    let search_moves: Vec<PlayerMove> = limits
+        .search_moves
+        .iter()
+        .filter_map(|chess_move| PlayerMove::from_uci(chess_move, position).ok())
+        .filter(|chess_move| legal_moves.contains(chess_move))
+        .collect();
+    let result = if search_moves.is_empty() {
+        tree.iterative_search(position.clone())
+    } else {
+        tree.search_restricted(position.clone(), &search_moves)
+    };
+    result.map(|result| *result.best_move())
+}
+
+/// Writes an `info` line for a completed depth
+fn info_line(info: &SearchInfo, root_white_to_play: bool) -> String {
+    let score = match info.score_mate() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score_cp()),
+    };
+
+    // Castling is written as a king move, which depends on the side playing it
+    let pv: Vec<String> = info
+        .pv()
+        .iter()
+        .enumerate()
+        .map(|(ply, &chess_move)| {
+            chess_move.to_uci(get_color(root_white_to_play == (ply % 2 == 0)))
+        })
+        .collect();
+
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        info.depth(),
+        score,
+        info.node_count(),
+        info.nodes_per_second(),
+        info.elapsed().as_millis(),
+        pv.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::static_evaluation::values;
+    use crate::tree_search_v2::transposition_table::BoundType;
+
+    #[test]
+    fn reads_the_clock_of_the_side_to_play() {
+        let limits =
+            parse_go("wtime 60000 btime 30000 winc 1000 binc 500 movestogo 20".split_whitespace());
+        let white = limits.clock(true).unwrap();
+        assert_eq!(white.remaining, Duration::from_millis(60_000));
+        assert_eq!(white.increment, Duration::from_millis(1_000));
+        assert_eq!(white.moves_to_go, Some(20));
+        let black = limits.clock(false).unwrap();
+        assert_eq!(black.remaining, Duration::from_millis(30_000));
+        assert_eq!(black.increment, Duration::from_millis(500));
+
+        // Only the time of white, black has no clock
+        let limits = parse_go("wtime 1000".split_whitespace());
+        assert_eq!(limits.clock(true).unwrap().increment, Duration::ZERO);
+        assert!(limits.clock(false).is_none());
+    }
+
+    #[test]
+    fn reads_options_with_spaces_in_their_name() {
+        assert_eq!(
+            parse_setoption("name Move Overhead value 100".split_whitespace()),
+            Some(("Move Overhead".to_string(), "100".to_string()))
+        );
+        assert_eq!(parse_setoption("name value 100".split_whitespace()), None);
+        assert_eq!(parse_setoption("value 100".split_whitespace()), None);
+    }
+
+    fn fen_of(arguments: &str) -> Option<String> {
+        parse_position(arguments.split_whitespace()).map(|position| position.to_string())
+    }
+
+    #[test]
+    fn reads_positions_and_plays_their_moves() {
+        assert_eq!(fen_of("startpos"), Some(Engine::new().to_string()));
+        assert_eq!(
+            fen_of("startpos moves e2e4 e7e5 g1f3").as_deref(),
+            Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+        );
+        assert_eq!(
+            fen_of("fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").as_deref(),
+            Some("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+        );
+
+        // Castling is read as a king move, promotions end with the piece
+        assert_eq!(
+            fen_of("fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1g1 e8c8").as_deref(),
+            Some("2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2")
+        );
+        assert_eq!(
+            fen_of("fen 8/P7/8/8/8/8/8/k6K w - - 0 1 moves a7a8n").as_deref(),
+            Some("N7/8/8/8/8/8/8/k6K b - - 0 1")
+        );
+    }
+
+    #[test]
+    fn an_invalid_position_keeps_the_previous_one() {
+        let mut position = Engine::new();
+        set_position(&mut position, "startpos moves e2e4".split_whitespace());
+        let previous = position.to_string();
+
+        for arguments in [
+            "fen not a position",
+            "fen 8/8/8/8/8/8/8/8 w - - 0 1",
+            "startpos moves e2e5",
+            "startpos moves e2e4 e7e5 e4e5",
+            "fen",
+            "",
+        ] {
+            set_position(&mut position, arguments.split_whitespace());
+            assert_eq!(position.to_string(), previous, "{arguments}");
+        }
+    }
+
+    #[test]
+    fn reads_the_limits_of_go() {
+        assert_eq!(
+            parse_go("depth 5 movetime 200".split_whitespace()),
+            GoLimits {
+                depth: Some(5),
+                movetime: Some(Duration::from_millis(200)),
+                ..GoLimits::default()
+            }
+        );
+
+        // The moves of searchmoves end at the next argument
+        assert_eq!(
+            parse_go("searchmoves e2e4 d2d4 depth 3".split_whitespace()),
+            GoLimits {
+                depth: Some(3),
+                search_moves: vec!["e2e4".to_string(), "d2d4".to_string()],
+                ..GoLimits::default()
+            }
+        );
+        assert_eq!(
+            parse_go("infinite searchmoves a7a8q".split_whitespace()).search_moves,
+            ["a7a8q"]
+        );
+
+        // Invalid values and unknown arguments are skipped
+        assert_eq!(
+            parse_go("depth x ponder wtime -5 movestogo 10".split_whitespace()),
+            GoLimits {
+                moves_to_go: Some(10),
+                ..GoLimits::default()
+            }
+        );
+    }
+
+    #[test]
+    fn searchmoves_restricts_the_root_moves() {
+        let mut tree = TreeSearchBuilder::default()
+            .pool_capacity(100_000)
+            .tt_capacity(1 << 12)
+            .evaluator(BasicEvaluator::default())
+            .max_depth(2)
+            .max_q_depth(0)
+            .build()
+            .unwrap();
+        let position = Engine::new();
+        let settings = TimeSettings::default();
+        let go_with = |tree: &mut TreeSearch<BasicEvaluator>, arguments: &str| {
+            go(
+                tree,
+                &position,
+                parse_go(arguments.split_whitespace()),
+                &settings,
+            )
+            .map(|chess_move| chess_move.to_uci(get_color(true)))
+        };
+
+        assert_eq!(
+            go_with(&mut tree, "depth 2 searchmoves a2a3").as_deref(),
+            Some("a2a3")
+        );
+        let allowed = ["h2h3", "h2h4"];
+        let best = go_with(&mut tree, "searchmoves h2h3 h2h4 depth 2").unwrap();
+        assert!(allowed.contains(&best.as_str()), "{best}");
+
+        // Without any legal move to search, the whole position is searched
+        assert!(go_with(&mut tree, "depth 2 searchmoves e2e5").is_some());
+    }
+
+    #[test]
+    fn info_lines_write_castling_as_a_king_move_and_mates_in_moves() {
+        let position = Engine::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let short = PlayerMove::from_uci("e1g1", &position).unwrap();
+        let mut after_short = position.clone();
+        after_short.play(short).unwrap();
+        let long = PlayerMove::from_uci("e8c8", &after_short).unwrap();
+
+        let info = |score: f32, pv: Vec<PlayerMove>| {
+            SearchInfo::new(
+                3,
+                score,
+                BoundType::Exact,
+                1_000,
+                Duration::from_millis(10),
+                pv,
+            )
+        };
+        assert_eq!(
+            info_line(&info(values::CHECK_MATE - 3., vec![short, long]), true),
+            "info depth 3 score mate 2 nodes 1000 nps 100000 time 10 pv e1g1 e8c8"
+        );
+        assert_eq!(
+            info_line(&info(-(values::CHECK_MATE - 2.), vec![long]), false),
+            "info depth 3 score mate -1 nodes 1000 nps 100000 time 10 pv e8c8"
+        );
+        assert_eq!(
+            info_line(&info(1.5, vec![short]), true),
+            "info depth 3 score cp 15 nodes 1000 nps 100000 time 10 pv e1g1"
+        );
+    }
+}
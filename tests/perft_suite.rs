@@ -0,0 +1,164 @@
+//! Perft counts of the Chess Programming Wiki test positions
+//!
+//! https://www.chessprogramming.org/Perft_Results
+//!
+//! The deepest counts take a while in debug builds, they are ignored by
+//! default: `cargo test --release -- --ignored` runs them.
+
+use chess_engine::game_engine::perft::PerftTable;
+use chess_engine::prelude::Engine;
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+/// Checks the counts of `fen`, the first one is for depth 1
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let engine = Engine::from_fen(fen).unwrap();
+    for (depth, &nodes) in (1..).zip(expected) {
+        assert_eq!(engine.perft(depth), nodes, "{fen} at depth {depth}");
+    }
+}
+
+/// Checks that the cached count of `fen` at `depth` is the plain one
+fn assert_hashed_perft(fen: &str, depth: usize, table: &mut PerftTable) {
+    let engine = Engine::from_fen(fen).unwrap();
+    assert_eq!(
+        engine.perft_hashed(depth, table),
+        engine.perft(depth),
+        "{fen} at depth {depth}"
+    );
+}
+
+/// Checks the count of `fen` at `depth` only
+fn assert_deep_perft(fen: &str, depth: usize, nodes: u64) {
+    let engine = Engine::from_fen(fen).unwrap();
+    assert_eq!(engine.perft(depth), nodes, "{fen} at depth {depth}");
+}
+
+#[test]
+fn start_position() {
+    assert_perft(START, &[20, 400, 8_902, 197_281]);
+}
+
+#[test]
+fn kiwipete() {
+    assert_perft(KIWIPETE, &[48, 2_039, 97_862]);
+}
+
+#[test]
+fn position_3() {
+    assert_perft(POSITION_3, &[14, 191, 2_812, 43_238]);
+}
+
+#[test]
+fn position_4() {
+    assert_perft(POSITION_4, &[6, 264, 9_467]);
+}
+
+#[test]
+fn position_5() {
+    assert_perft(POSITION_5, &[44, 1_486, 62_379]);
+}
+
+#[test]
+fn position_6() {
+    assert_perft(POSITION_6, &[46, 2_079, 89_890]);
+}
+
+#[test]
+fn sliders_stop_at_the_first_piece() {
+    // Rooks and bishops only, a slider passing a piece would add moves
+    for (fen, expected) in [
+        (
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            [26, 568, 13_744, 314_346],
+        ),
+        (
+            "B6b/8/8/8/2K5/4k3/8/b6B w - - 0 1",
+            [17, 278, 4_607, 76_778],
+        ),
+    ] {
+        assert_perft(fen, &expected);
+    }
+}
+
+#[test]
+fn perft_divide_splits_the_count_by_root_move() {
+    for (fen, depth, nodes, root_moves) in [
+        (START, 3, 8_902, 20),
+        (KIWIPETE, 2, 2_039, 48),
+        (POSITION_4, 3, 9_467, 6),
+    ] {
+        let engine = Engine::from_fen(fen).unwrap();
+        let divide = engine.perft_divide(depth);
+        assert_eq!(divide.len(), root_moves, "{fen}");
+        assert_eq!(
+            divide.iter().map(|(_, count)| count).sum::<u64>(),
+            nodes,
+            "{fen}"
+        );
+
+        for (chess_move, count) in divide {
+            let mut child = engine.clone();
+            child.play(chess_move).unwrap();
+            assert_eq!(child.perft(depth - 1), count, "{fen} {chess_move:?}");
+        }
+    }
+}
+
+#[test]
+fn hashed_perft_agrees_with_perft() {
+    // The table is shared, entries of one position must not leak into another
+    let mut table = PerftTable::new();
+    assert_hashed_perft(START, 4, &mut table);
+    assert_hashed_perft(KIWIPETE, 3, &mut table);
+    assert_hashed_perft(POSITION_3, 4, &mut table);
+}
+
+#[test]
+#[ignore]
+fn start_position_deep() {
+    assert_deep_perft(START, 5, 4_865_609);
+}
+
+#[test]
+#[ignore]
+fn kiwipete_deep() {
+    assert_deep_perft(KIWIPETE, 4, 4_085_603);
+}
+
+#[test]
+#[ignore]
+fn position_3_deep() {
+    assert_deep_perft(POSITION_3, 5, 674_624);
+}
+
+#[test]
+#[ignore]
+fn position_4_deep() {
+    assert_deep_perft(POSITION_4, 4, 422_333);
+}
+
+#[test]
+#[ignore]
+fn position_5_deep() {
+    assert_deep_perft(POSITION_5, 4, 2_103_487);
+}
+
+#[test]
+#[ignore]
+fn position_6_deep() {
+    assert_deep_perft(POSITION_6, 4, 3_894_594);
+}
+
+#[test]
+#[ignore]
+fn hashed_perft_agrees_with_perft_deep() {
+    let mut table = PerftTable::new();
+    assert_hashed_perft(START, 5, &mut table);
+    assert_hashed_perft(KIWIPETE, 5, &mut table);
+}